@@ -0,0 +1,4 @@
+ALTER TABLE game DROP COLUMN player_ids;
+ALTER TABLE game DROP COLUMN player_names;
+ALTER TABLE game DROP COLUMN player_colors;
+ALTER TABLE game DROP COLUMN player_points;
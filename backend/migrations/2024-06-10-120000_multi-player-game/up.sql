@@ -0,0 +1,11 @@
+-- players of a game in seat order. `player0_*` and `player1_*` are kept as the first two seats
+ALTER TABLE game ADD COLUMN player_ids INT[] DEFAULT '{}' NOT NULL;
+ALTER TABLE game ADD COLUMN player_names TEXT[] DEFAULT '{}' NOT NULL;
+ALTER TABLE game ADD COLUMN player_colors INT[] DEFAULT '{}' NOT NULL;
+ALTER TABLE game ADD COLUMN player_points INT[] DEFAULT '{}' NOT NULL;
+
+UPDATE game SET
+  player_ids = ARRAY[player0_id, player1_id],
+  player_names = ARRAY[player0_name, player1_name],
+  player_colors = ARRAY[player0_color, player1_color],
+  player_points = ARRAY[player0_point, player1_point];
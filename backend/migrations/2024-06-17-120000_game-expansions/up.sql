@@ -0,0 +1,2 @@
+-- expansions used in a game (e.g., 'inns_and_cathedrals')
+ALTER TABLE game ADD COLUMN expansions TEXT[] DEFAULT '{}' NOT NULL;
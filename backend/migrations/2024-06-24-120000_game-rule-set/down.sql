@@ -0,0 +1,4 @@
+ALTER TABLE game DROP COLUMN field_edition;
+ALTER TABLE game DROP COLUMN meeples_per_player;
+ALTER TABLE game DROP COLUMN tie_is_draw;
+ALTER TABLE game DROP COLUMN redraw_on_discard;
@@ -0,0 +1,5 @@
+-- rule variants of a game (see `RuleSet`). the defaults are the rules used before they became configurable
+ALTER TABLE game ADD COLUMN field_edition TEXT DEFAULT 'third' NOT NULL;
+ALTER TABLE game ADD COLUMN meeples_per_player INTEGER DEFAULT 7 NOT NULL;
+ALTER TABLE game ADD COLUMN tie_is_draw BOOLEAN DEFAULT FALSE NOT NULL;
+ALTER TABLE game ADD COLUMN redraw_on_discard BOOLEAN DEFAULT TRUE NOT NULL;
@@ -0,0 +1,4 @@
+-- what a player is allowed to do with problems (see `Role`)
+ALTER TABLE player ADD COLUMN role TEXT DEFAULT 'player' NOT NULL;
+-- the player who has administered problems so far
+UPDATE player SET role = 'admin' WHERE id = 2;
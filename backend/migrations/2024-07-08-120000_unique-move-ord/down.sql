@@ -0,0 +1 @@
+DROP INDEX move_game_id_ord_key;
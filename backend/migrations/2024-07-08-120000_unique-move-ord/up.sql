@@ -0,0 +1,14 @@
+-- moves submitted twice before this index existed share an `ord`. keep the first of them (lowest id) and take the
+-- others out of the game by setting their `ord` to -1, rather than deleting them, since votes and optimal moves may
+-- refer to them
+UPDATE move SET ord = -1
+WHERE ord <> -1
+  AND EXISTS (
+    SELECT 1 FROM move AS earlier
+    WHERE earlier.game_id = move.game_id
+      AND earlier.ord = move.ord
+      AND earlier.id < move.id
+  );
+
+-- a game can't have two moves with the same `ord`. moves with `ord` -1 are not part of the game (e.g., optimal moves)
+CREATE UNIQUE INDEX move_game_id_ord_key ON move (game_id, ord) WHERE ord <> -1;
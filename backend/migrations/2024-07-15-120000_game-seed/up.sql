@@ -0,0 +1,4 @@
+-- seed the deck of a game is shuffled with (see `tile::deck`). games created before get random ones,
+-- which reproduce their remaining draws since the tiles already drawn are skipped
+ALTER TABLE game ADD COLUMN seed BIGINT DEFAULT floor(random() * 9007199254740991)::BIGINT NOT NULL;
+ALTER TABLE game ALTER COLUMN seed DROP DEFAULT;
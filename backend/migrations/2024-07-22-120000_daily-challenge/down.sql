@@ -0,0 +1,3 @@
+DROP INDEX game_challenge_id_player0_id_key;
+ALTER TABLE game DROP COLUMN challenge_id;
+DROP TABLE challenge;
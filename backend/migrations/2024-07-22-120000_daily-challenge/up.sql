@@ -0,0 +1,12 @@
+-- one challenge a day. every participant plays the AI with the deck shuffled with `seed`
+CREATE TABLE challenge (
+  id SERIAL PRIMARY KEY,
+  date DATE NOT NULL UNIQUE,
+  seed BIGINT NOT NULL,
+  ai_first BOOLEAN NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE game ADD COLUMN challenge_id INT REFERENCES challenge (id);
+-- each player takes part in a challenge once. participants always take the first seat
+CREATE UNIQUE INDEX game_challenge_id_player0_id_key ON game (challenge_id, player0_id) WHERE challenge_id IS NOT NULL;
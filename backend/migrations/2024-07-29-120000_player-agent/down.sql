@@ -0,0 +1,2 @@
+ALTER TABLE game DROP COLUMN player_agents;
+ALTER TABLE player DROP COLUMN agent;
@@ -0,0 +1,13 @@
+-- the engine a bot account plays with (see `agent::by_name`). NULL for human players
+ALTER TABLE player ADD COLUMN agent TEXT;
+-- the AI which has played so far
+UPDATE player SET agent = 'heuristic' WHERE id = 1;
+
+-- the agent playing each seat, in the order of `player_ids`. NULL for the seats of human players
+ALTER TABLE game ADD COLUMN player_agents TEXT[];
+UPDATE game SET player_agents = ARRAY(
+  SELECT CASE WHEN u.pid = 1 THEN 'heuristic' END
+  FROM unnest(player_ids) WITH ORDINALITY AS u(pid, seat)
+  ORDER BY u.seat
+);
+ALTER TABLE game ALTER COLUMN player_agents SET NOT NULL;
@@ -0,0 +1,7 @@
+-- the difficulty levels of the AI, which are the agents bots can play with (see `agent::LEVELS`).
+-- each level has its own rating, updated by rated games against it
+CREATE TABLE ai_level (
+  name TEXT PRIMARY KEY,
+  rating INT NOT NULL DEFAULT 1500
+);
+INSERT INTO ai_level (name) VALUES ('random'), ('greedy'), ('heuristic'), ('mcts');
@@ -0,0 +1,2 @@
+-- a game which ended in a draw has no winner, so `winner_player_id` stays NULL
+ALTER TABLE game ADD COLUMN is_draw BOOLEAN NOT NULL DEFAULT FALSE;
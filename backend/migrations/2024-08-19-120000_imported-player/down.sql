@@ -0,0 +1 @@
+DELETE FROM player WHERE id IN (-4, -5, -6);
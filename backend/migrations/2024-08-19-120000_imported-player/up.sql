@@ -0,0 +1,9 @@
+-- the players of imported games, who aren't registered. seat i plays as player -2 - i, so that the first two seats
+-- share the players of problems, and the ids of the seats satisfy the foreign keys on games and moves
+INSERT INTO player (id, name, email, user_id) VALUES
+  (-2, 'You', '', ''),
+  (-3, 'Opponent', '', ''),
+  (-4, 'Player 3', '', ''),
+  (-5, 'Player 4', '', ''),
+  (-6, 'Player 5', '', '')
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,367 @@
+use std::env;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::Deserialize;
+
+use crate::error::{internal_server_error, unauthorized_error, Error};
+use crate::player::{self, DbPool};
+
+// public keys Firebase signs ID tokens with
+const FIREBASE_JWKS_URL: &str =
+    "https://www.googleapis.com/service_accounts/v1/jwk/securetoken@system.gserviceaccount.com";
+
+// keys are reloaded at most this often, so that tokens with made-up key ids can't flood the key endpoint
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JwksSource {
+    Path(String),
+    Url(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Claims {
+    pub sub: String,
+}
+
+// verifies Firebase ID tokens. keys are loaded once and reloaded when a token is signed by an unknown key,
+// since Firebase rotates them
+pub struct Verifier {
+    project_id: String,
+    source: JwksSource,
+    keys: RwLock<JwkSet>,
+    fetched_at: Mutex<Instant>,
+    refetch_interval: Duration,
+}
+
+impl Verifier {
+    pub async fn new(project_id: String, source: JwksSource) -> Result<Verifier, Error> {
+        let keys = fetch_keys(&source).await?;
+        Ok(Verifier {
+            project_id,
+            source,
+            keys: RwLock::new(keys),
+            fetched_at: Mutex::new(Instant::now()),
+            refetch_interval: MIN_REFETCH_INTERVAL,
+        })
+    }
+
+    // `FIREBASE_PROJECT_ID` is required. keys are read from `FIREBASE_JWKS_PATH` if set,
+    // otherwise from `FIREBASE_JWKS_URL` (Google's endpoint by default)
+    pub async fn from_env() -> Result<Verifier, Error> {
+        let project_id = match env::var("FIREBASE_PROJECT_ID") {
+            Ok(id) => id,
+            Err(_) => {
+                return Err(internal_server_error(
+                    "FIREBASE_PROJECT_ID must be set".to_string(),
+                ))
+            }
+        };
+        let source = match env::var("FIREBASE_JWKS_PATH") {
+            Ok(path) => JwksSource::Path(path),
+            Err(_) => JwksSource::Url(
+                env::var("FIREBASE_JWKS_URL").unwrap_or(FIREBASE_JWKS_URL.to_string()),
+            ),
+        };
+        Verifier::new(project_id, source).await
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<Claims, Error> {
+        let header = match decode_header(token) {
+            Ok(h) => h,
+            Err(e) => return Err(unauthorized_error(format!("malformed token: {}", e))),
+        };
+        let kid = match header.kid {
+            Some(kid) => kid,
+            None => return Err(unauthorized_error("token has no key id".to_string())),
+        };
+
+        let mut key = self.decoding_key(&kid)?;
+        if key.is_none() && self.start_refetch() {
+            let keys = fetch_keys(&self.source).await?;
+            *self.keys.write().unwrap() = keys;
+            key = self.decoding_key(&kid)?;
+        }
+        let key = match key {
+            Some(key) => key,
+            None => return Err(unauthorized_error(format!("unknown key id {}", kid))),
+        };
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&self.project_id]);
+        validation.set_issuer(&[format!(
+            "https://securetoken.google.com/{}",
+            self.project_id
+        )]);
+        validation.set_required_spec_claims(&["exp", "sub", "aud", "iss"]);
+        match decode::<Claims>(token, &key, &validation) {
+            Ok(data) if !data.claims.sub.is_empty() => Ok(data.claims),
+            Ok(_) => Err(unauthorized_error("token has an empty subject".to_string())),
+            Err(e) => Err(unauthorized_error(format!("invalid token: {}", e))),
+        }
+    }
+
+    // whether the keys may be reloaded now. the time is taken before fetching,
+    // so that concurrent requests with unknown keys don't all fetch at once
+    fn start_refetch(&self) -> bool {
+        let mut fetched_at = self.fetched_at.lock().unwrap();
+        if fetched_at.elapsed() < self.refetch_interval {
+            return false;
+        }
+        *fetched_at = Instant::now();
+        true
+    }
+
+    fn decoding_key(&self, kid: &str) -> Result<Option<DecodingKey>, Error> {
+        match self.keys.read().unwrap().find(kid) {
+            Some(jwk) => match DecodingKey::from_jwk(jwk) {
+                Ok(key) => Ok(Some(key)),
+                Err(e) => Err(internal_server_error(format!("invalid key {}: {}", kid, e))),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+async fn fetch_keys(source: &JwksSource) -> Result<JwkSet, Error> {
+    let body = match source {
+        JwksSource::Path(path) => match std::fs::read_to_string(path) {
+            Ok(body) => body,
+            Err(e) => {
+                return Err(internal_server_error(format!(
+                    "failed to read {}: {}",
+                    path, e
+                )))
+            }
+        },
+        JwksSource::Url(url) => match reqwest::get(url).await {
+            Ok(res) => match res.text().await {
+                Ok(body) => body,
+                Err(e) => {
+                    return Err(internal_server_error(format!(
+                        "failed to fetch {}: {}",
+                        url, e
+                    )))
+                }
+            },
+            Err(e) => {
+                return Err(internal_server_error(format!(
+                    "failed to fetch {}: {}",
+                    url, e
+                )))
+            }
+        },
+    };
+    match serde_json::from_str(&body) {
+        Ok(keys) => Ok(keys),
+        Err(e) => Err(internal_server_error(format!("invalid JWKS: {}", e))),
+    }
+}
+
+fn bearer_token<'r>(req: &'r Request<'_>) -> Result<&'r str, Error> {
+    match req.headers().get_one("Authorization") {
+        Some(value) => match value.strip_prefix("Bearer ") {
+            Some(token) => Ok(token.trim()),
+            None => Err(unauthorized_error(
+                "Authorization header must be a Bearer token".to_string(),
+            )),
+        },
+        None => Err(unauthorized_error(
+            "Authorization header is required".to_string(),
+        )),
+    }
+}
+
+// a caller with a valid Firebase ID token, who may not have a player yet
+pub struct AuthenticatedUser {
+    pub claims: Claims,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = Error;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let verifier = match req.rocket().state::<Verifier>() {
+            Some(v) => v,
+            None => {
+                let e = internal_server_error("token verifier is not configured".to_string());
+                return Outcome::Failure((e.status, e));
+            }
+        };
+        let token = match bearer_token(req) {
+            Ok(token) => token,
+            Err(e) => return Outcome::Failure((e.status, e)),
+        };
+        match verifier.verify(token).await {
+            Ok(claims) => Outcome::Success(AuthenticatedUser { claims }),
+            Err(e) => Outcome::Failure((e.status, e)),
+        }
+    }
+}
+
+// the player whose `user_id` is the subject of the caller's token.
+// handlers take `Result<AuthenticatedPlayer, Error>` so that failures are returned as JSON like other errors
+pub struct AuthenticatedPlayer(pub player::Player);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedPlayer {
+    type Error = Error;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match req.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => user,
+            Outcome::Failure(f) => return Outcome::Failure(f),
+            Outcome::Forward(f) => return Outcome::Forward(f),
+        };
+        let db = match req.rocket().state::<DbPool>() {
+            Some(db) => db,
+            None => {
+                let e = internal_server_error("database is not configured".to_string());
+                return Outcome::Failure((e.status, e));
+            }
+        };
+        match player::get_player_by_uid(db, user.claims.sub) {
+            Ok(p) => Outcome::Success(AuthenticatedPlayer(p)),
+            Err(e) if e.status == Status::NotFound => {
+                let e = unauthorized_error("no player is registered for this user".to_string());
+                Outcome::Failure((e.status, e))
+            }
+            Err(e) => Outcome::Failure((e.status, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use openssl::rsa::Rsa;
+    use rocket::serde::json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const PROJECT_ID: &str = "carcassonne-test";
+
+    // generates a key pair, writes its public half as a JWKS file and returns the private key
+    fn generate_keys(kid: &str) -> (EncodingKey, String) {
+        let rsa = Rsa::generate(2048).unwrap();
+        let jwks = json!({
+            "keys": [{
+                "kty": "RSA",
+                "alg": "RS256",
+                "use": "sig",
+                "kid": kid,
+                "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+            }]
+        });
+        let path = env::temp_dir().join(format!("jwks-{}-{}.json", kid, std::process::id()));
+        std::fs::write(&path, jwks.to_string()).unwrap();
+        let key = EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap();
+        (key, path.to_str().unwrap().to_string())
+    }
+
+    fn sign(key: &EncodingKey, kid: &str, iss: &str, aud: &str, exp_offset: i64) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let claims = json!({
+            "iss": iss,
+            "aud": aud,
+            "sub": "firebase-uid",
+            "email": "player@example.com",
+            "iat": now,
+            "exp": now + exp_offset,
+        });
+        encode(&header, &claims, key).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn verify_test() {
+        let (key, path) = generate_keys("key0");
+        let verifier = Verifier::new(PROJECT_ID.to_string(), JwksSource::Path(path))
+            .await
+            .unwrap();
+        let iss = format!("https://securetoken.google.com/{}", PROJECT_ID);
+
+        let claims = verifier
+            .verify(&sign(&key, "key0", &iss, PROJECT_ID, 3600))
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, "firebase-uid");
+
+        // expired
+        let e = verifier
+            .verify(&sign(&key, "key0", &iss, PROJECT_ID, -3600))
+            .await
+            .unwrap_err();
+        assert_eq!(e.status, Status::Unauthorized);
+
+        // issued for another project
+        let e = verifier
+            .verify(&sign(&key, "key0", &iss, "other-project", 3600))
+            .await
+            .unwrap_err();
+        assert_eq!(e.status, Status::Unauthorized);
+        let e = verifier
+            .verify(&sign(
+                &key,
+                "key0",
+                "https://securetoken.google.com/other-project",
+                PROJECT_ID,
+                3600,
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(e.status, Status::Unauthorized);
+
+        // signed by a key which is not in the JWKS
+        let (other_key, _) = generate_keys("key1");
+        let e = verifier
+            .verify(&sign(&other_key, "key1", &iss, PROJECT_ID, 3600))
+            .await
+            .unwrap_err();
+        assert_eq!(e.detail.msg, "unknown key id key1");
+
+        // signed by another key under a known key id
+        let e = verifier
+            .verify(&sign(&other_key, "key0", &iss, PROJECT_ID, 3600))
+            .await
+            .unwrap_err();
+        assert_eq!(e.status, Status::Unauthorized);
+
+        let e = verifier.verify("not a token").await.unwrap_err();
+        assert_eq!(e.status, Status::Unauthorized);
+    }
+
+    #[rocket::async_test]
+    async fn refetch_test() {
+        let (_, path) = generate_keys("key2");
+        let mut verifier = Verifier::new(PROJECT_ID.to_string(), JwksSource::Path(path.clone()))
+            .await
+            .unwrap();
+        let iss = format!("https://securetoken.google.com/{}", PROJECT_ID);
+
+        // the keys are rotated, but they were loaded too recently to be reloaded
+        let (new_key, new_path) = generate_keys("key3");
+        std::fs::copy(&new_path, &path).unwrap();
+        let token = sign(&new_key, "key3", &iss, PROJECT_ID, 3600);
+        let e = verifier.verify(&token).await.unwrap_err();
+        assert_eq!(e.detail.msg, "unknown key id key3");
+
+        verifier.refetch_interval = Duration::ZERO;
+        let claims = verifier.verify(&token).await.unwrap();
+        assert_eq!(claims.sub, "firebase-uid");
+    }
+}
@@ -0,0 +1,164 @@
+use diesel::pg::PgConnection;
+use diesel::r2d2::ConnectionManager;
+use diesel::r2d2::Pool;
+use diesel::Queryable;
+use rand::Rng;
+use rocket::serde::Serialize;
+
+use crate::database;
+use crate::error::{conflict_error, Error};
+use crate::game::{self, agent, rule_set::RuleSet, Deal, Game};
+use crate::player::Player;
+
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+// the agent of the bot every participant plays against
+const AI_AGENT: &str = agent::HEURISTIC;
+const COLOR_NUM: i32 = 5;
+
+// a game against the AI played by everyone on the same day, with the same deck and the same first player
+#[derive(Serialize, Queryable, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Challenge {
+    pub id: i32,
+    pub date: chrono::NaiveDate,
+    // kept secret until the day is over, since all participants draw from the same deck
+    #[serde(skip_serializing)]
+    pub seed: i64,
+    pub ai_first: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct LeaderboardEntry {
+    // participants with the same point difference share a rank
+    pub rank: i32,
+    pub player_id: i32,
+    pub player_name: String,
+    // hidden until the day is over, since the moves of a game tell the deck shared by the challenge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_id: Option<i32>,
+    pub point_diff: i32,
+}
+
+// the challenge of the day (in UTC), which is created by its first request
+pub fn get_today_challenge(db: &DbPool) -> Result<Challenge, Error> {
+    let mut rng = rand::thread_rng();
+    database::get_or_create_challenge(db, chrono::Utc::now().date_naive(), rng.gen(), rng.gen())
+}
+
+// starts the player's game of today's challenge. each player takes part once
+pub fn create_challenge_game(db: &DbPool, player: &Player) -> Result<Game, Error> {
+    let challenge = get_today_challenge(db)?;
+    if database::get_challenge_game(db, challenge.id, player.id)?.is_some() {
+        return Err(conflict_error(
+            "the player has already taken part in the challenge".to_string(),
+        ));
+    }
+
+    let ai = database::get_bot(db, AI_AGENT)?;
+    let ai_color = if ai.meeple_color == player.meeple_color {
+        (ai.meeple_color + 1) % COLOR_NUM
+    } else {
+        ai.meeple_color
+    };
+    // the participant always takes the first seat
+    game::create_dealt_game(
+        db,
+        vec![player.id, ai.id],
+        vec![player.meeple_color, ai_color],
+        false,
+        vec![],
+        RuleSet::default(),
+        Deal {
+            seed: challenge.seed,
+            first_seat: if challenge.ai_first { 1 } else { 0 },
+            challenge_id: Some(challenge.id),
+        },
+        None,
+    )
+}
+
+pub fn get_leaderboard(db: &DbPool, challenge_id: i32) -> Result<Vec<LeaderboardEntry>, Error> {
+    let challenge = database::get_challenge(db, challenge_id)?;
+    let games = database::get_challenge_leaderboard(db, challenge.id)?;
+    let is_over = challenge.date < chrono::Utc::now().date_naive();
+    Ok(rank(&games, is_over))
+}
+
+// `games` must be sorted by the point difference, the best first
+fn rank(games: &[Game], with_game_ids: bool) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = vec![];
+    for (i, gm) in games.iter().enumerate() {
+        let point_diff = gm.player0_point - gm.player1_point;
+        let rank = match entries.last() {
+            Some(e) if e.point_diff == point_diff => e.rank,
+            _ => i as i32 + 1,
+        };
+        entries.push(LeaderboardEntry {
+            rank,
+            player_id: gm.player0_id,
+            player_name: gm.player0_name.clone(),
+            game_id: if with_game_ids { Some(gm.id) } else { None },
+            point_diff,
+        });
+    }
+    entries
+}
+
+#[test]
+fn rank_test() {
+    let game = |id: i32, player0_point: i32, player1_point: i32| Game {
+        id,
+        player0_id: id * 10,
+        player1_id: 1,
+        player0_point,
+        player1_point,
+        next_tile_id: None,
+        next_player_id: None,
+        created_at: chrono::NaiveDateTime::default(),
+        ended_at: Some(chrono::NaiveDateTime::default()),
+        current_player_id: None,
+        current_tile_id: None,
+        player0_name: format!("player{}", id),
+        player1_name: "AI".to_string(),
+        player0_color: 0,
+        player1_color: 1,
+        is_rated: false,
+        before_player0_rating: None,
+        before_player1_rating: None,
+        after_player0_rating: None,
+        after_player1_rating: None,
+        first_player_id: None,
+        winner_player_id: None,
+        player_ids: vec![id * 10, 1],
+        player_names: vec![format!("player{}", id), "AI".to_string()],
+        player_colors: vec![0, 1],
+        player_points: vec![player0_point, player1_point],
+        expansions: vec![],
+        field_edition: "third".to_string(),
+        meeples_per_player: 7,
+        tie_is_draw: false,
+        redraw_on_discard: true,
+        seed: 0,
+        challenge_id: Some(1),
+        player_agents: vec![None, Some(game::agent::HEURISTIC.to_string())],
+        is_draw: false,
+    };
+    let games = [game(1, 80, 60), game(2, 70, 50), game(3, 60, 70)];
+    let entries = rank(&games, true);
+    assert_eq!(
+        entries
+            .iter()
+            .map(|e| (e.rank, e.game_id, e.point_diff))
+            .collect::<Vec<_>>(),
+        vec![(1, Some(1), 20), (1, Some(2), 20), (3, Some(3), -10)]
+    );
+    assert_eq!(entries[0].player_id, 10);
+    assert_eq!(entries[0].player_name, "player1");
+    assert!(rank(&[], true).is_empty());
+
+    // the games aren't told while the challenge goes on
+    assert!(rank(&games, false).iter().all(|e| e.game_id.is_none()));
+}
@@ -3,12 +3,16 @@ use diesel::prelude::*;
 use diesel::r2d2::Pool;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+pub type DbConn = PooledConnection<ConnectionManager<PgConnection>>;
 
-use crate::error::{internal_server_error, not_found_error, Error};
+use crate::challenge;
+use crate::error::{
+    conflict_error, internal_server_error, move_error, not_found_error, Error, MoveError,
+};
 use crate::game;
 use crate::game::mov;
+use crate::game::rule_set::RuleSet;
 use crate::game::tile;
-use crate::optimal_move;
 use crate::player::{self};
 use crate::problem::{self, ProblemProposal};
 use crate::schema;
@@ -35,6 +39,8 @@ pub struct QueryPlayer {
     pub rating: Option<i32>,
     pub profile_image_url: String,
     pub tile_edition: String,
+    pub role: String,
+    pub agent: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -49,6 +55,14 @@ struct NewWaitingGameHistory {
     player_id: i32,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = schema::challenge)]
+struct NewChallenge {
+    date: chrono::NaiveDate,
+    seed: i64,
+    ai_first: bool,
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = schema::game)]
 struct NewGame {
@@ -71,15 +85,18 @@ struct NewGame {
     after_player1_rating: Option<i32>,
     first_player_id: Option<i32>,
     winner_player_id: Option<i32>,
-}
-
-#[derive(Insertable)]
-#[diesel(table_name = schema::optimal_move)]
-struct NewOptimalMove {
-    game_id: i32,
-    last_n: i32,
-    tile_move_id: i32,
-    meeple_move_id: i32,
+    player_ids: Vec<i32>,
+    player_names: Vec<String>,
+    player_colors: Vec<i32>,
+    player_points: Vec<i32>,
+    expansions: Vec<String>,
+    field_edition: String,
+    meeples_per_player: i32,
+    tie_is_draw: bool,
+    redraw_on_discard: bool,
+    seed: i64,
+    challenge_id: Option<i32>,
+    player_agents: Vec<Option<String>>,
 }
 
 #[derive(Insertable)]
@@ -204,7 +221,10 @@ pub struct NewProblemProposal {
 }
 
 pub fn get_player(db: &DbPool, pid: i32) -> Result<player::Player, Error> {
-    let conn = &mut db.get().unwrap();
+    find_player(&mut db.get().unwrap(), pid)
+}
+
+pub fn find_player(conn: &mut DbConn, pid: i32) -> Result<player::Player, Error> {
     use self::schema::player::dsl::{id, player as p};
 
     match p.filter(id.eq(pid)).load::<QueryPlayer>(conn) {
@@ -222,11 +242,11 @@ pub fn get_player(db: &DbPool, pid: i32) -> Result<player::Player, Error> {
 
 pub fn get_players(db: &DbPool) -> Result<Vec<player::Player>, Error> {
     let conn = &mut db.get().unwrap();
-    use self::schema::player::dsl::{id, player as p, rating};
+    use self::schema::player::dsl::{agent, player as p, rating};
 
     match p
         .filter(rating.is_not_null())
-        .filter(id.ne(1)) // not AI
+        .filter(agent.is_null()) // not bots
         .order(rating.desc())
         .limit(10)
         .load::<QueryPlayer>(conn)
@@ -243,10 +263,10 @@ pub fn get_players(db: &DbPool) -> Result<Vec<player::Player>, Error> {
 #[allow(dead_code)]
 pub fn get_all_players(db: &DbPool) -> Result<Vec<player::Player>, Error> {
     let conn = &mut db.get().unwrap();
-    use self::schema::player::dsl::{id, player as p};
+    use self::schema::player::dsl::{agent, player as p};
 
     match p
-        .filter(id.ne(1)) // not AI
+        .filter(agent.is_null()) // not bots
         .limit(300)
         .load::<QueryPlayer>(conn)
     {
@@ -259,6 +279,25 @@ pub fn get_all_players(db: &DbPool) -> Result<Vec<player::Player>, Error> {
     }
 }
 
+// the bot account played by `agent_name`, the oldest one if there are several
+pub fn get_bot(db: &DbPool, agent_name: &str) -> Result<player::Player, Error> {
+    let conn = &mut db.get().unwrap();
+    use self::schema::player::dsl::{agent, id, player as p};
+
+    match p
+        .filter(agent.eq(agent_name))
+        .order(id.asc())
+        .limit(1)
+        .load::<QueryPlayer>(conn)
+    {
+        Ok(ps) => match ps.into_iter().next() {
+            Some(v) => Ok(to_player(v)),
+            None => Err(not_found_error(format!("{} bot", agent_name))),
+        },
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
 pub fn get_player_by_uid(db: &DbPool, uid: String) -> Result<player::Player, Error> {
     let conn = &mut db.get().unwrap();
     use self::schema::player::dsl::{player as p, user_id};
@@ -333,6 +372,21 @@ pub fn update_player(
     }
 }
 
+pub fn update_player_rating(
+    conn: &mut DbConn,
+    pid: i32,
+    rat: Option<i32>,
+) -> Result<player::Player, Error> {
+    use self::schema::player::dsl::{player, rating};
+    match diesel::update(player.find(pid))
+        .set(rating.eq(rat))
+        .get_result(conn)
+    {
+        Ok(p) => Ok(to_player(p)),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
 pub fn get_games(
     db: &DbPool,
     player_id: Option<i32>,
@@ -340,7 +394,7 @@ pub fn get_games(
     input_limit: Option<i32>,
 ) -> Result<Vec<game::Game>, Error> {
     let conn = &mut db.get().unwrap();
-    use self::schema::game::dsl::{created_at, game as g, is_rated, player0_id, player1_id};
+    use self::schema::game::dsl::{created_at, game as g, is_rated, player_ids};
     let is_rtd = match input_is_rated {
         Some(i) => i,
         None => false,
@@ -353,7 +407,7 @@ pub fn get_games(
     match player_id {
         Some(pid) => {
             match g
-                .filter(player0_id.eq(pid).or(player1_id.eq(pid)))
+                .filter(player_ids.contains(vec![pid]))
                 .filter(is_rated.eq(is_rtd))
                 .order(created_at.desc())
                 .limit(lmt as i64)
@@ -399,6 +453,20 @@ pub fn get_game(db: &DbPool, gmid: i32) -> Result<game::Game, Error> {
     }
 }
 
+// locks the game row until the end of the transaction, so that moves of a game are made one at a time
+pub fn lock_game(conn: &mut DbConn, gmid: i32) -> Result<game::Game, Error> {
+    use self::schema::game::dsl::{game as g, id};
+    match g.filter(id.eq(gmid)).for_update().load::<game::Game>(conn) {
+        Ok(games) => {
+            if games.len() == 0 {
+                return Err(not_found_error("game".to_string()));
+            }
+            Ok(games[0].clone())
+        }
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
 pub fn get_waiting_games(db: &DbPool) -> Result<Vec<game::WaitingGame>, Error> {
     let conn = &mut db.get().unwrap();
     use self::schema::waiting_game::dsl::*;
@@ -408,6 +476,22 @@ pub fn get_waiting_games(db: &DbPool) -> Result<Vec<game::WaitingGame>, Error> {
     }
 }
 
+pub fn get_waiting_game(db: &DbPool, wid: i32) -> Result<game::WaitingGame, Error> {
+    let conn = &mut db.get().unwrap();
+    use self::schema::waiting_game::dsl::{id, waiting_game};
+    match waiting_game
+        .filter(id.eq(wid))
+        .limit(1)
+        .load::<game::WaitingGame>(conn)
+    {
+        Ok(games) => match games.first() {
+            Some(gm) => Ok(gm.clone()),
+            None => Err(not_found_error("waiting game".to_string())),
+        },
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
 pub fn create_waiting_game(db: &DbPool, player_id: i32) -> Result<game::WaitingGame, Error> {
     let conn = &mut db.get().unwrap();
 
@@ -456,34 +540,41 @@ pub fn delete_waiting_game(db: &DbPool, pid: i32) -> Result<(), Error> {
     }
 }
 
-pub fn create_game(
-    db: &DbPool,
-    player0_id: i32,
-    player1_id: i32,
+pub fn insert_game(
+    conn: &mut DbConn,
+    player_ids: Vec<i32>,
     next_tile_id: Option<i32>,
     next_player_id: Option<i32>,
     current_tile_id: Option<i32>,
     current_player_id: Option<i32>,
-    player0_name: String,
-    player1_name: String,
-    player0_color: i32,
-    player1_color: i32,
+    player_names: Vec<String>,
+    player_colors: Vec<i32>,
     is_rated: bool,
     first_player_id: Option<i32>,
+    expansions: Vec<String>,
+    rule_set: &RuleSet,
+    seed: i64,
+    challenge_id: Option<i32>,
+    player_agents: Vec<Option<String>>,
 ) -> Result<game::Game, Error> {
+    assert!(player_ids.len() >= 2);
+    assert_eq!(player_ids.len(), player_names.len());
+    assert_eq!(player_ids.len(), player_colors.len());
+    assert_eq!(player_ids.len(), player_agents.len());
+    // `player0_*` and `player1_*` are the first two seats
     let new_game = NewGame {
-        player0_id,
-        player1_id,
+        player0_id: player_ids[0],
+        player1_id: player_ids[1],
         player0_point: 0,
         player1_point: 0,
         next_tile_id,
         next_player_id,
         current_tile_id,
         current_player_id,
-        player0_name,
-        player1_name,
-        player0_color,
-        player1_color,
+        player0_name: player_names[0].clone(),
+        player1_name: player_names[1].clone(),
+        player0_color: player_colors[0],
+        player1_color: player_colors[1],
         is_rated,
         before_player0_rating: None,
         before_player1_rating: None,
@@ -491,8 +582,19 @@ pub fn create_game(
         after_player1_rating: None,
         first_player_id,
         winner_player_id: None,
+        player_points: vec![0; player_ids.len()],
+        player_ids,
+        player_names,
+        player_colors,
+        expansions,
+        field_edition: rule_set.field_edition.name(),
+        meeples_per_player: rule_set.meeples_per_player,
+        tie_is_draw: rule_set.tie_is_draw,
+        redraw_on_discard: rule_set.redraw_on_discard,
+        seed,
+        challenge_id,
+        player_agents,
     };
-    let conn = &mut db.get().unwrap();
     match diesel::insert_into(schema::game::table)
         .values(&new_game)
         .get_result(conn)
@@ -500,6 +602,15 @@ pub fn create_game(
         Ok(gm) => {
             return Ok(gm);
         }
+        // the only unique key on games is the one of challenge participants
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => {
+            return Err(conflict_error(
+                "the player has already taken part in the challenge".to_string(),
+            ));
+        }
         Err(e) => {
             return Err(internal_server_error(e.to_string()));
         }
@@ -507,12 +618,11 @@ pub fn create_game(
 }
 
 pub fn update_game(
-    db: &DbPool,
+    conn: &mut DbConn,
     gmid: i32,
     next_tid: i32,
     next_pid: i32,
-    p0_point: i32,
-    p1_point: i32,
+    points: Vec<i32>,
     cur_tid: i32,
     cur_pid: i32,
     b_rating0: Option<i32>,
@@ -525,13 +635,13 @@ pub fn update_game(
     use self::schema::game::dsl::{
         after_player0_rating, after_player1_rating, before_player0_rating, before_player1_rating,
         current_player_id, current_tile_id, first_player_id, game, next_player_id, next_tile_id,
-        player0_point, player1_point, winner_player_id,
+        player0_point, player1_point, player_points, winner_player_id,
     };
-    let conn = &mut db.get().unwrap();
     match diesel::update(game.find(gmid))
         .set((
-            player0_point.eq(p0_point),
-            player1_point.eq(p1_point),
+            player0_point.eq(points[0]),
+            player1_point.eq(points[1]),
+            player_points.eq(points.clone()),
             next_tile_id.eq(next_tid),
             next_player_id.eq(next_pid),
             current_tile_id.eq(cur_tid),
@@ -554,6 +664,45 @@ pub fn update_game(
     }
 }
 
+// records the final result of a game, which ends now
+pub fn end_game(
+    conn: &mut DbConn,
+    gmid: i32,
+    points: Vec<i32>,
+    b_rating0: Option<i32>,
+    b_rating1: Option<i32>,
+    a_rating0: Option<i32>,
+    a_rating1: Option<i32>,
+    first_pid: i32,
+    // None for a draw
+    winner_pid: Option<i32>,
+) -> Result<game::Game, Error> {
+    use self::schema::game::dsl::{
+        after_player0_rating, after_player1_rating, before_player0_rating, before_player1_rating,
+        ended_at, first_player_id, game, is_draw, player0_point, player1_point, player_points,
+        winner_player_id,
+    };
+    match diesel::update(game.find(gmid))
+        .set((
+            player0_point.eq(points[0]),
+            player1_point.eq(points[1]),
+            player_points.eq(points.clone()),
+            before_player0_rating.eq(b_rating0),
+            before_player1_rating.eq(b_rating1),
+            after_player0_rating.eq(a_rating0),
+            after_player1_rating.eq(a_rating1),
+            first_player_id.eq(first_pid),
+            winner_player_id.eq(winner_pid),
+            is_draw.eq(winner_pid.is_none()),
+            ended_at.eq(diesel::dsl::now),
+        ))
+        .get_result(conn)
+    {
+        Ok(gm) => Ok(gm),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
 pub fn get_tile_move(
     conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
     mid: i32,
@@ -594,7 +743,14 @@ pub fn get_move(
 }
 
 pub fn list_moves(db: &DbPool, gmid: i32, move_id: Option<i32>) -> Result<Vec<mov::Move>, Error> {
-    let conn = &mut db.get().unwrap();
+    find_moves(&mut db.get().unwrap(), gmid, move_id)
+}
+
+pub fn find_moves(
+    conn: &mut DbConn,
+    gmid: i32,
+    move_id: Option<i32>,
+) -> Result<Vec<mov::Move>, Error> {
     use self::schema::move_::dsl::*;
     let max_ord = match move_id {
         Some(mid) => mid,
@@ -617,8 +773,10 @@ pub fn list_moves(db: &DbPool, gmid: i32, move_id: Option<i32>) -> Result<Vec<mo
 }
 
 pub fn create_move(db: &DbPool, mv: mov::Move) -> Result<mov::Move, Error> {
-    let conn = &mut db.get().unwrap();
+    insert_move(&mut db.get().unwrap(), mv)
+}
 
+pub fn insert_move(conn: &mut DbConn, mv: mov::Move) -> Result<mov::Move, Error> {
     let new_move = match mv {
         mov::Move::TMove(m) => InsertMove {
             ord: m.ord,
@@ -665,38 +823,23 @@ pub fn create_move(db: &DbPool, mv: mov::Move) -> Result<mov::Move, Error> {
         Ok(query_move) => {
             return Ok(to_move(query_move));
         }
+        // another request has made the move of the same `ord` in the meantime
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => {
+            return Err(move_error(MoveError::MoveConflict { ord: new_move.ord }));
+        }
         Err(e) => {
             return Err(internal_server_error(e.to_string()));
         }
     }
 }
 
-pub fn create_optimal_move(
-    db: &DbPool,
-    game_id: i32,
-    last_n: i32,
-    tile_move_id: i32,
-    meeple_move_id: i32,
-) -> Result<optimal_move::OptimalMove, Error> {
-    let conn = &mut db.get().unwrap();
-    let nom = NewOptimalMove {
-        game_id,
-        last_n,
-        tile_move_id,
-        meeple_move_id,
-    };
-    match diesel::insert_into(schema::optimal_move::table)
-        .values(&nom)
-        .get_result(conn)
-    {
-        Ok(r) => Ok(r),
-        Err(e) => Err(internal_server_error(e.to_string())),
-    }
-}
-
-pub fn create_problem(db: &DbPool, new_problem: &NewProblem) -> Result<problem::Problem, Error> {
-    let conn = &mut db.get().unwrap();
-
+pub fn insert_problem(
+    conn: &mut DbConn,
+    new_problem: &NewProblem,
+) -> Result<problem::Problem, Error> {
     match diesel::insert_into(schema::problem::table)
         .values(new_problem)
         .get_result(conn)
@@ -1092,6 +1235,8 @@ fn to_player(v: QueryPlayer) -> player::Player {
         rating: v.rating,
         tile_edition: v.tile_edition,
         profile_image_url: v.profile_image_url,
+        role: player::Role::from_name(&v.role).unwrap_or(player::Role::Player),
+        agent: v.agent,
     }
 }
 
@@ -1152,3 +1297,106 @@ fn to_problem(p: QueryProblem) -> problem::Problem {
         favorited: None,
     }
 }
+
+pub fn get_challenge(db: &DbPool, cid: i32) -> Result<challenge::Challenge, Error> {
+    let conn = &mut db.get().unwrap();
+    use self::schema::challenge::dsl::challenge as c;
+    match c.find(cid).first(conn) {
+        Ok(ch) => Ok(ch),
+        Err(diesel::result::Error::NotFound) => Err(not_found_error("challenge".to_string())),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
+// the challenge of `d`. when there's none yet, it's created with `seed` and `ai_first`.
+// concurrent requests all get the challenge created first
+pub fn get_or_create_challenge(
+    db: &DbPool,
+    d: chrono::NaiveDate,
+    seed: i64,
+    ai_first: bool,
+) -> Result<challenge::Challenge, Error> {
+    let conn = &mut db.get().unwrap();
+    use self::schema::challenge::dsl::{challenge as c, date};
+    let new_challenge = NewChallenge {
+        date: d,
+        seed,
+        ai_first,
+    };
+    if let Err(e) = diesel::insert_into(c)
+        .values(&new_challenge)
+        .on_conflict(date)
+        .do_nothing()
+        .execute(conn)
+    {
+        return Err(internal_server_error(e.to_string()));
+    }
+    match c.filter(date.eq(d)).first(conn) {
+        Ok(ch) => Ok(ch),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
+pub fn get_challenge_game(db: &DbPool, cid: i32, pid: i32) -> Result<Option<game::Game>, Error> {
+    let conn = &mut db.get().unwrap();
+    use self::schema::game::dsl::{challenge_id, game as g, player0_id};
+    match g
+        .filter(challenge_id.eq(cid))
+        .filter(player0_id.eq(pid))
+        .first::<game::Game>(conn)
+        .optional()
+    {
+        Ok(gm) => Ok(gm),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
+// ended games of a challenge, the best first. participants take the first seat,
+// and ties go to the one who finished earlier
+pub fn get_challenge_leaderboard(db: &DbPool, cid: i32) -> Result<Vec<game::Game>, Error> {
+    let conn = &mut db.get().unwrap();
+    use self::schema::game::dsl::{
+        challenge_id, ended_at, game as g, player0_point, player1_point,
+    };
+    match g
+        .filter(challenge_id.eq(cid))
+        .filter(ended_at.is_not_null())
+        .order(((player0_point - player1_point).desc(), ended_at.asc()))
+        .load::<game::Game>(conn)
+    {
+        Ok(gms) => Ok(gms),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
+// the difficulty levels of the AI in the order of `agent::LEVELS`, with their ratings
+pub fn get_ai_levels(db: &DbPool) -> Result<Vec<game::AILevel>, Error> {
+    let conn = &mut db.get().unwrap();
+    use self::schema::ai_level::dsl::ai_level;
+    match ai_level.load::<game::AILevel>(conn) {
+        Ok(mut levels) => {
+            levels.sort_by_key(|l| game::agent::LEVELS.iter().position(|n| *n == l.name));
+            Ok(levels)
+        }
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
+pub fn find_ai_level(conn: &mut DbConn, level: &str) -> Result<Option<game::AILevel>, Error> {
+    use self::schema::ai_level::dsl::ai_level;
+    match ai_level.find(level).first(conn).optional() {
+        Ok(l) => Ok(l),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
+
+pub fn update_ai_level_rating(conn: &mut DbConn, level: &str, rat: i32) -> Result<(), Error> {
+    use self::schema::ai_level::dsl::{ai_level, rating};
+    match diesel::update(ai_level.find(level))
+        .set(rating.eq(rat))
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(internal_server_error(e.to_string())),
+    }
+}
@@ -1,10 +1,236 @@
 use rocket::{http::Status, serde::Serialize};
 
+use crate::game::calculate::Side;
+use crate::game::tile::Tile;
+
 #[derive(Debug, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct Detail {
     pub title: String,
     pub msg: String,
+    // why a move was rejected, serialized as `code` and the fields of the case
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<MoveError>,
+    // why a BGA replay couldn't be decoded, serialized in the same way. boxed to keep `Error` small
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub decode_reason: Option<Box<DecodeError>>,
+}
+
+// reasons a move is rejected. `code` is stable so that clients can show (and localise) their own messages
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", tag = "code", rename_all = "snake_case")]
+pub enum MoveError {
+    TileNotInGame {
+        tile: Tile,
+    },
+    // `rot` must be one of 0, 1, 2 and 3
+    InvalidRotation {
+        rot: i32,
+    },
+    PositionOccupied {
+        pos: (i32, i32),
+    },
+    NoAdjacentTile {
+        pos: (i32, i32),
+    },
+    // `dir` is the side of the placed tile ("right", "top", "left" or "bottom")
+    EdgeMismatch {
+        dir: String,
+        adjacent_side: Side,
+        tile_side: Side,
+    },
+    RiverWithoutSpring,
+    RiverEnded,
+    RiverNotContinued,
+    RiverUTurn,
+    NoTileAtPosition {
+        pos: (i32, i32),
+    },
+    InvalidMeeplePosition {
+        meeple_pos: i32,
+    },
+    UnknownMeeple {
+        meeple_id: i32,
+    },
+    MeepleOnBoard {
+        meeple_id: i32,
+    },
+    NoMeeplesLeft {
+        player: usize,
+    },
+    FeatureOccupied {
+        meeple_pos: i32,
+    },
+    // the meeple belongs to another player than the one who moves
+    NotOwnMeeple {
+        meeple_id: i32,
+    },
+    // a meeple move must be on the tile placed in the same turn
+    NotPlacedTile {
+        tile_pos: (i32, i32),
+    },
+    TileNotCurrent {
+        tile: Tile,
+        current_tile: Tile,
+    },
+    TilePlaceable {
+        tile: Tile,
+    },
+    TileMoveAfterTileMove,
+    MeepleMoveWithoutTileMove,
+    GameEnded,
+    NotCurrentPlayer {
+        player_id: i32,
+        current_player_id: Option<i32>,
+    },
+    WrongTurn {
+        player_id: i32,
+    },
+    // the move was made concurrently with another one
+    MoveConflict {
+        ord: i32,
+    },
+}
+
+impl MoveError {
+    pub fn msg(&self) -> String {
+        match self {
+            MoveError::TileNotInGame { tile } => format!("tile {:?} is not used in this game", tile),
+            MoveError::InvalidRotation { rot } => format!("rotation {} is not valid", rot),
+            MoveError::PositionOccupied { pos } => {
+                format!("position ({}, {}) is not empty", pos.0, pos.1)
+            }
+            MoveError::NoAdjacentTile { .. } => "at least one adjacent tile must exist".to_string(),
+            MoveError::EdgeMismatch {
+                dir,
+                adjacent_side,
+                tile_side,
+            } => {
+                let opposite = match dir.as_str() {
+                    "right" => "left",
+                    "top" => "bottom",
+                    "left" => "right",
+                    _ => "top",
+                };
+                format!(
+                    "{} side is invalid: {} tile's {} is {:?}, but the current tile's {} is {:?}",
+                    dir, dir, opposite, adjacent_side, dir, tile_side
+                )
+            }
+            MoveError::RiverWithoutSpring => "the river must start from its spring".to_string(),
+            MoveError::RiverEnded => "the river has already ended".to_string(),
+            MoveError::RiverNotContinued => "a river tile must continue the river".to_string(),
+            MoveError::RiverUTurn => {
+                "the river must not turn to the same direction twice in a row".to_string()
+            }
+            MoveError::NoTileAtPosition { pos } => {
+                format!("there is no tile at ({}, {})", pos.0, pos.1)
+            }
+            MoveError::InvalidMeeplePosition { meeple_pos } => {
+                format!("meeple position {} doesn't exist on the tile", meeple_pos)
+            }
+            MoveError::UnknownMeeple { meeple_id } => {
+                format!("meeple {} doesn't belong to any player", meeple_id)
+            }
+            MoveError::MeepleOnBoard { meeple_id } => {
+                format!("meeple {} is already on the board", meeple_id)
+            }
+            MoveError::NoMeeplesLeft { player } => {
+                format!("player {} has no meeples left", player)
+            }
+            MoveError::FeatureOccupied { .. } => {
+                "meepling on this feature is not allowed".to_string()
+            }
+            MoveError::NotOwnMeeple { meeple_id } => {
+                format!("meeple {} belongs to another player", meeple_id)
+            }
+            MoveError::NotPlacedTile { tile_pos } => format!(
+                "the tile at ({}, {}) is not the one placed this turn",
+                tile_pos.0, tile_pos.1
+            ),
+            MoveError::TileNotCurrent { tile, current_tile } => {
+                format!("tile {:?} is not the current tile {:?}", tile, current_tile)
+            }
+            MoveError::TilePlaceable { tile } => {
+                format!("tile {:?} can't be discarded since it can be placed", tile)
+            }
+            MoveError::TileMoveAfterTileMove => {
+                "move before a tile move must not be a tile move".to_string()
+            }
+            MoveError::MeepleMoveWithoutTileMove => {
+                "move before a meeple move must be a tile move".to_string()
+            }
+            MoveError::GameEnded => "the game has already ended".to_string(),
+            MoveError::NotCurrentPlayer { player_id, .. } => {
+                format!("it's not player {}'s turn", player_id)
+            }
+            MoveError::WrongTurn { .. } => {
+                "player of the previous meeple move must not be the same player who is going to play"
+                    .to_string()
+            }
+            MoveError::MoveConflict { ord } => {
+                format!("move {} has already been made by another request", ord)
+            }
+        }
+    }
+}
+
+// reasons a BGA replay can't be decoded
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", tag = "code", rename_all = "snake_case")]
+pub enum DecodeError {
+    InvalidJson {
+        msg: String,
+    },
+    UnknownTileType {
+        bga_type: i32,
+    },
+    // a packet whose `arg` is missing or malformed, or which the decoder doesn't know (without `arg`)
+    UnsupportedPacket {
+        packet_type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        arg: Option<String>,
+    },
+    // a move which can't be made at that point of the game, e.g., a meeple placed before any tile
+    OutOfOrderMove {
+        ord: i32,
+        cause: String,
+    },
+    // tiles and meeples of expansions, which aren't supported
+    ExpansionContent {
+        packet_type: String,
+        content: String,
+    },
+    // the replay file couldn't be opened or read
+    Unreadable {
+        msg: String,
+    },
+}
+
+impl DecodeError {
+    pub fn msg(&self) -> String {
+        match self {
+            DecodeError::InvalidJson { msg } => format!("the replay is not valid JSON: {}", msg),
+            DecodeError::UnknownTileType { bga_type } => {
+                format!("tile type {} is unknown", bga_type)
+            }
+            DecodeError::UnsupportedPacket { packet_type, arg } => match arg {
+                Some(arg) => format!("{} packet has no valid `{}`", packet_type, arg),
+                None => format!("{} packet is not supported", packet_type),
+            },
+            DecodeError::OutOfOrderMove { ord, cause } => {
+                format!("move {} can't be made: {}", ord, cause)
+            }
+            DecodeError::ExpansionContent {
+                packet_type,
+                content,
+            } => format!(
+                "{} packet has {} of an expansion, which is not supported",
+                packet_type, content
+            ),
+            DecodeError::Unreadable { msg } => format!("the replay can't be read: {}", msg),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -13,12 +239,21 @@ pub struct Error {
     pub detail: Detail,
 }
 
+// lets `?` be used on diesel results, e.g. in transactions
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        internal_server_error(e.to_string())
+    }
+}
+
 pub fn not_found_error(name: String) -> Error {
     Error {
         status: Status::NotFound,
         detail: Detail {
             title: "not_found".to_string(),
             msg: format!("{} not found", name),
+            reason: None,
+            decode_reason: None,
         },
     }
 }
@@ -29,6 +264,8 @@ pub fn internal_server_error(msg: String) -> Error {
         detail: Detail {
             title: "internal".to_string(),
             msg,
+            reason: None,
+            decode_reason: None,
         },
     }
 }
@@ -39,16 +276,117 @@ pub fn bad_request_error(msg: String) -> Error {
         detail: Detail {
             title: "bad_request".to_string(),
             msg,
+            reason: None,
+            decode_reason: None,
+        },
+    }
+}
+
+pub fn unauthorized_error(msg: String) -> Error {
+    Error {
+        status: Status::Unauthorized,
+        detail: Detail {
+            title: "unauthorized".to_string(),
+            msg,
+            reason: None,
+            decode_reason: None,
         },
     }
 }
 
-pub fn moves_invalid_error(msg: String) -> Error {
+pub fn forbidden_error(msg: String) -> Error {
+    Error {
+        status: Status::Forbidden,
+        detail: Detail {
+            title: "forbidden".to_string(),
+            msg,
+            reason: None,
+            decode_reason: None,
+        },
+    }
+}
+
+pub fn conflict_error(msg: String) -> Error {
     Error {
         status: Status::Conflict,
         detail: Detail {
-            title: "moves_invalid".to_string(),
+            title: "conflict".to_string(),
             msg,
+            reason: None,
+            decode_reason: None,
+        },
+    }
+}
+
+pub fn move_error(reason: MoveError) -> Error {
+    // errors about whose turn it is are the client's fault rather than a conflict with the board
+    let (status, title) = match reason {
+        MoveError::TileMoveAfterTileMove
+        | MoveError::MeepleMoveWithoutTileMove
+        | MoveError::WrongTurn { .. }
+        | MoveError::NotCurrentPlayer { .. } => (Status::BadRequest, "bad_request"),
+        _ => (Status::Conflict, "moves_invalid"),
+    };
+    Error {
+        status,
+        detail: Detail {
+            title: title.to_string(),
+            msg: reason.msg(),
+            reason: Some(reason),
+            decode_reason: None,
         },
     }
 }
+
+pub fn decode_error(reason: DecodeError) -> Error {
+    Error {
+        status: Status::BadRequest,
+        detail: Detail {
+            title: "replay_invalid".to_string(),
+            msg: reason.msg(),
+            reason: None,
+            decode_reason: Some(Box::new(reason)),
+        },
+    }
+}
+
+#[test]
+fn move_error_detail_test() {
+    let e = move_error(MoveError::EdgeMismatch {
+        dir: "top".to_string(),
+        adjacent_side: Side::Field,
+        tile_side: Side::City,
+    });
+    assert_eq!(e.status, Status::Conflict);
+    assert_eq!(
+        rocket::serde::json::to_string(&e.detail).unwrap(),
+        r#"{"title":"moves_invalid","msg":"top side is invalid: top tile's bottom is Field, but the current tile's top is City","code":"edge_mismatch","dir":"top","adjacent_side":"field","tile_side":"city"}"#
+    );
+
+    let e = move_error(MoveError::WrongTurn { player_id: 3 });
+    assert_eq!(e.status, Status::BadRequest);
+    assert_eq!(
+        rocket::serde::json::to_string(&e.detail).unwrap(),
+        r#"{"title":"bad_request","msg":"player of the previous meeple move must not be the same player who is going to play","code":"wrong_turn","player_id":3}"#
+    );
+
+    let e = move_error(MoveError::MoveConflict { ord: 42 });
+    assert_eq!(e.status, Status::Conflict);
+    assert_eq!(
+        rocket::serde::json::to_string(&e.detail).unwrap(),
+        r#"{"title":"moves_invalid","msg":"move 42 has already been made by another request","code":"move_conflict","ord":42}"#
+    );
+
+    let e = bad_request_error("parameter `game_id` is required".to_string());
+    assert_eq!(
+        rocket::serde::json::to_string(&e.detail).unwrap(),
+        r#"{"title":"bad_request","msg":"parameter `game_id` is required"}"#
+    );
+
+    let e = decode_error(DecodeError::UnknownTileType { bga_type: 0 });
+    assert_eq!(e.status, Status::BadRequest);
+    assert_eq!(
+        rocket::serde::json::to_string(&e.detail).unwrap(),
+        r#"{"title":"replay_invalid","msg":"tile type 0 is unknown","code":"unknown_tile_type","bga_type":0}"#
+    );
+}
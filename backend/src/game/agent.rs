@@ -0,0 +1,437 @@
+use super::calculate_next_move::calculate_next_move;
+use super::game_state::GameState;
+use super::mcts::Mcts;
+use super::mov::{MeepleMove, Move, TileMove};
+use super::rule_set::RuleSet;
+use super::tile::{Expansion, Tile};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+pub const RANDOM: &str = "random";
+pub const GREEDY: &str = "greedy";
+// the agent the AI has played with from the start
+pub const HEURISTIC: &str = "heuristic";
+pub const MCTS: &str = "mcts";
+
+// the difficulty levels of the AI, the easiest first. each is rated on its own (see `ai_level`)
+pub const LEVELS: [&str; 4] = [RANDOM, GREEDY, HEURISTIC, MCTS];
+
+// what an agent knows about the game on its turn
+#[derive(Clone, Debug)]
+pub struct AgentState {
+    pub game_id: Option<i32>,
+    // in the order of seats
+    pub player_ids: Vec<i32>,
+    // the player the agent plays for
+    pub player_id: i32,
+    pub moves: Vec<Move>,
+    pub expansions: Vec<Expansion>,
+    pub rule_set: RuleSet,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decision {
+    Place(TileMove, MeepleMove),
+    // the tile can't be placed anywhere
+    Discard,
+}
+
+// an engine choosing the moves of a bot
+pub trait Agent {
+    fn choose(&self, state: &AgentState, tile: Tile) -> Decision;
+
+    // whether the agent can play games of `player_num` players. games are created only with agents which can
+    fn supports(&self, _player_num: usize) -> bool {
+        true
+    }
+}
+
+// any legal move, meeples included
+pub struct RandomMove;
+
+impl Agent for RandomMove {
+    fn choose(&self, state: &AgentState, tile: Tile) -> Decision {
+        let (mut game_state, seat) = match game_state_of(state) {
+            Some(s) => s,
+            None => return Decision::Discard,
+        };
+        let placements = placements(&mut game_state, seat, state.player_id, tile);
+        // seeded with the position, so that the same position is always played the same way
+        let seed = ((state.game_id.unwrap_or(0) as u64) << 32) | state.moves.len() as u64;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        match placements.choose(&mut rng) {
+            Some(p) => decision(state, tile, p, meeple_id(&game_state, seat)),
+            None => Decision::Discard,
+        }
+    }
+}
+
+// the move scoring the most points right away, placing a meeple when that scores no less
+pub struct Greedy;
+
+impl Agent for Greedy {
+    fn choose(&self, state: &AgentState, tile: Tile) -> Decision {
+        let (mut game_state, seat) = match game_state_of(state) {
+            Some(s) => s,
+            None => return Decision::Discard,
+        };
+        let meeple_id = meeple_id(&game_state, seat);
+        let mut best: Option<(Placement, (i32, bool))> = None;
+        for p in placements(&mut game_state, seat, state.player_id, tile) {
+            let before = game_state.status().player_points[seat];
+            let (tm, mm) = moves_of(state.player_id, tile, &p, meeple_id);
+            if let Err(e) = game_state
+                .apply_tile_move(&tm)
+                .and_then(|_| game_state.apply_meeple_move(&mm))
+            {
+                panic!("{:?}", e.detail.msg);
+            }
+            let key = (
+                game_state.status().player_points[seat] - before,
+                p.meeple_pos != -1,
+            );
+            game_state.undo(); // meeple move
+            game_state.undo(); // tile move
+            match best {
+                Some((_, k)) if k >= key => {}
+                _ => best = Some((p, key)),
+            }
+        }
+        match best {
+            Some((p, _)) => decision(state, tile, &p, meeple_id),
+            None => Decision::Discard,
+        }
+    }
+}
+
+// greedy over `evaluate`, solving the last two tiles exactly (see `calculate_next_move`)
+pub struct Heuristic;
+
+impl Agent for Heuristic {
+    fn choose(&self, state: &AgentState, tile: Tile) -> Decision {
+        if !self.supports(state.player_ids.len()) {
+            return first_legal_move(state, tile);
+        }
+        match calculate_next_move(
+            &state.moves,
+            state.game_id,
+            state.player_ids[0],
+            state.player_ids[1],
+            state.player_id,
+            tile,
+            &state.expansions,
+            &state.rule_set,
+        ) {
+            Some((tm, mm)) => Decision::Place(tm, mm),
+            None => Decision::Discard,
+        }
+    }
+
+    // `evaluate` only knows two-player games
+    fn supports(&self, player_num: usize) -> bool {
+        player_num == 2
+    }
+}
+
+// the agent named `name` (as stored in `player.agent` and `game.player_agents`)
+pub fn by_name(name: &str) -> Option<Box<dyn Agent>> {
+    match name {
+        RANDOM => Some(Box::new(RandomMove)),
+        GREEDY => Some(Box::new(Greedy)),
+        HEURISTIC => Some(Box::new(Heuristic)),
+        MCTS => Some(Box::new(Mcts::default())),
+        _ => None,
+    }
+}
+
+// the tile at the first tileable position without a meeple
+pub fn first_legal_move(state: &AgentState, tile: Tile) -> Decision {
+    let (game_state, _) = match game_state_of(state) {
+        Some(s) => s,
+        None => return Decision::Discard,
+    };
+    match game_state.tileable_positions(tile).first() {
+        Some(tp) => decision(
+            state,
+            tile,
+            &Placement {
+                pos: tp.pos,
+                rot: tp.rot,
+                meeple_pos: -1,
+            },
+            -1,
+        ),
+        None => Decision::Discard,
+    }
+}
+
+// where a tile goes, and where its meeple goes (-1 for none)
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    pos: (i32, i32),
+    rot: i32,
+    meeple_pos: i32,
+}
+
+// the board of `state`, and the seat of the player the agent plays for
+fn game_state_of(state: &AgentState) -> Option<(GameState, usize)> {
+    let seat = state
+        .player_ids
+        .iter()
+        .position(|p| *p == state.player_id)?;
+    match GameState::from_moves(
+        &state.moves,
+        state.player_ids.len(),
+        &state.expansions,
+        &state.rule_set,
+    ) {
+        Ok(s) => Some((s, seat)),
+        Err(_) => None,
+    }
+}
+
+// every way `seat` can place `tile`, with or without a meeple
+fn placements(
+    game_state: &mut GameState,
+    seat: usize,
+    player_id: i32,
+    tile: Tile,
+) -> Vec<Placement> {
+    let mut ps = vec![];
+    for tp in game_state.tileable_positions(tile) {
+        let p = Placement {
+            pos: tp.pos,
+            rot: tp.rot,
+            meeple_pos: -1,
+        };
+        let (tm, _) = moves_of(player_id, tile, &p, -1);
+        if let Err(e) = game_state.apply_tile_move(&tm) {
+            panic!("{:?}", e.detail.msg);
+        }
+        ps.push(p);
+        if !game_state.status().player_remaining_meeples[seat].is_empty() {
+            for meeple_pos in game_state.status().meepleable_positions.iter() {
+                ps.push(Placement {
+                    meeple_pos: *meeple_pos,
+                    ..p
+                });
+            }
+        }
+        game_state.undo();
+    }
+    ps
+}
+
+// the smallest id of the meeples `seat` has left
+fn meeple_id(game_state: &GameState, seat: usize) -> i32 {
+    match game_state.status().player_remaining_meeples[seat]
+        .iter()
+        .min()
+    {
+        Some(id) => *id,
+        None => -1,
+    }
+}
+
+// the tile move and the meeple move of `p`, with their ords to be filled
+fn moves_of(player_id: i32, tile: Tile, p: &Placement, meeple_id: i32) -> (TileMove, MeepleMove) {
+    (
+        TileMove {
+            id: -1,
+            ord: -1,
+            game_id: None,
+            player_id,
+            tile,
+            rot: p.rot,
+            pos: p.pos,
+        },
+        MeepleMove {
+            id: -1,
+            ord: -1,
+            game_id: None,
+            player_id,
+            meeple_id: if p.meeple_pos == -1 { -1 } else { meeple_id },
+            tile_pos: p.pos,
+            meeple_pos: p.meeple_pos,
+        },
+    )
+}
+
+fn decision(state: &AgentState, tile: Tile, p: &Placement, meeple_id: i32) -> Decision {
+    let ord = match state.moves.last() {
+        Some(mv) => mv.ord() + 1,
+        None => 0,
+    };
+    let (tm, mm) = moves_of(state.player_id, tile, p, meeple_id);
+    Decision::Place(
+        TileMove {
+            ord,
+            game_id: state.game_id,
+            ..tm
+        },
+        MeepleMove {
+            ord: ord + 1,
+            game_id: state.game_id,
+            ..mm
+        },
+    )
+}
+
+// plays a whole game between `agents`, seat i being played by `agents[i]` as player i,
+// with the deck shuffled with `seed`. returns the final points of the seats
+#[cfg(test)]
+pub fn play(
+    agents: &[&dyn Agent],
+    seed: u64,
+    first_seat: usize,
+    expansions: &[Expansion],
+    rule_set: &RuleSet,
+) -> Vec<i32> {
+    use super::calculate::calculate;
+    use super::mov::DiscardMove;
+    use super::tile;
+
+    let player_num = agents.len();
+    let player_ids: Vec<i32> = (0..player_num as i32).collect();
+    let deck = tile::deck(seed, expansions);
+    let starting_tile = tile::starting_tile(expansions);
+
+    // the player before the first player places the starting tile, as in `create_dealt_game`
+    let starting_seat = (first_seat + player_num - 1) % player_num;
+    let mut moves = vec![
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 0,
+            game_id: None,
+            player_id: starting_seat as i32,
+            tile: starting_tile,
+            rot: 0,
+            pos: (0, 0),
+        }),
+        Move::MMove(MeepleMove {
+            id: -1,
+            ord: 1,
+            game_id: None,
+            player_id: starting_seat as i32,
+            meeple_id: -1,
+            tile_pos: (0, 0),
+            meeple_pos: -1,
+        }),
+    ];
+    let mut out_tiles = vec![starting_tile];
+    let mut seat = first_seat;
+    loop {
+        let tile = tile::draw(&deck, &out_tiles, expansions);
+        if tile == Tile::Invalid {
+            break;
+        }
+        out_tiles.push(tile);
+        let state = AgentState {
+            game_id: None,
+            player_ids: player_ids.clone(),
+            player_id: seat as i32,
+            moves: moves.clone(),
+            expansions: expansions.to_vec(),
+            rule_set: rule_set.clone(),
+        };
+        let ord = moves.last().unwrap().ord() + 1;
+        match agents[seat].choose(&state, tile) {
+            Decision::Place(tm, mm) => {
+                moves.push(Move::TMove(TileMove { ord, ..tm }));
+                moves.push(Move::MMove(MeepleMove { ord: ord + 1, ..mm }));
+            }
+            Decision::Discard => {
+                moves.push(Move::DMove(DiscardMove {
+                    id: -1,
+                    ord,
+                    game_id: None,
+                    player_id: seat as i32,
+                    tile,
+                }));
+                // the player draws another tile
+                if rule_set.redraw_on_discard {
+                    continue;
+                }
+            }
+        }
+        seat = (seat + 1) % player_num;
+    }
+
+    match calculate(&moves, player_num, expansions, rule_set, true) {
+        Ok(s) => s.player_points,
+        Err(e) => panic!("{:?}", e.detail.msg),
+    }
+}
+
+#[test]
+fn agent_test() {
+    for level in LEVELS {
+        assert!(by_name(level).is_some());
+    }
+    assert!(by_name("unknown").is_none());
+    // bots searching with `evaluate` are only for two-player games
+    for (name, supported) in [
+        (RANDOM, true),
+        (GREEDY, true),
+        (HEURISTIC, false),
+        (MCTS, false),
+    ] {
+        let agent = by_name(name).unwrap();
+        assert!(agent.supports(2));
+        assert_eq!(agent.supports(3), supported, "{}", name);
+    }
+
+    let moves = vec![
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 0,
+            game_id: None,
+            player_id: 0,
+            tile: Tile::StartingTile,
+            rot: 0,
+            pos: (0, 0),
+        }),
+        Move::MMove(MeepleMove {
+            id: -1,
+            ord: 1,
+            game_id: None,
+            player_id: 0,
+            meeple_id: -1,
+            tile_pos: (0, 0),
+            meeple_pos: -1,
+        }),
+    ];
+    let mut state = AgentState {
+        game_id: None,
+        player_ids: vec![0, 1],
+        player_id: 1,
+        moves,
+        expansions: vec![],
+        rule_set: RuleSet::default(),
+    };
+    let agents: [&dyn Agent; 3] = [&RandomMove, &Greedy, &Heuristic];
+    for (player_ids, agent) in [vec![0, 1], vec![0, 1, 2]]
+        .iter()
+        .flat_map(|ids| agents.iter().map(move |a| (ids.clone(), a)))
+    {
+        state.player_ids = player_ids;
+        match agent.choose(&state, Tile::Straight) {
+            Decision::Place(tm, mm) => {
+                assert_eq!((tm.ord, tm.player_id, tm.tile), (2, 1, Tile::Straight));
+                assert_eq!((mm.ord, mm.player_id, mm.tile_pos), (3, 1, tm.pos));
+                let mut mvs = state.moves.clone();
+                mvs.push(Move::TMove(tm));
+                mvs.push(Move::MMove(mm));
+                assert!(GameState::from_moves(
+                    &mvs,
+                    state.player_ids.len(),
+                    &[],
+                    &RuleSet::default()
+                )
+                .is_ok());
+            }
+            Decision::Discard => panic!("the straight can be placed"),
+        }
+    }
+}
@@ -1,20 +1,119 @@
+use std::collections::HashMap;
+
 use rocket::serde::Serialize;
 
-#[derive(Serialize, Clone)]
+use super::calculate::TileItem;
+
+// a placed tile with its position, so that boards of any extent can be returned
+#[derive(Serialize, Clone, Debug, PartialEq)]
 #[serde(crate = "rocket::serde")]
 pub struct BoardTile {
+    pub y: i32,
+    pub x: i32,
     pub id: i32,
     pub rot: i32,
     pub meeple_id: i32,
     pub meeple_pos: i32,
 }
 
+// the smallest rectangle containing all the tiles
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct Bounds {
+    pub min_y: i32,
+    pub min_x: i32,
+    pub max_y: i32,
+    pub max_x: i32,
+}
+
 #[derive(Serialize, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct Board {
     pub player0_point: i32,
     pub player1_point: i32,
-    pub tiles: Vec<Vec<BoardTile>>,
+    pub player_points: Vec<i32>,
+    pub tiles: Vec<BoardTile>,
+    pub bounds: Bounds,
     pub meepleable_positions: Vec<i32>,
     pub complete_events: Vec<super::CompleteEvent>,
 }
+
+// the tiles sorted by their positions, and their bounds
+pub fn board_tiles(board: &HashMap<(i32, i32), TileItem>) -> (Vec<BoardTile>, Bounds) {
+    let mut tiles: Vec<BoardTile> = board
+        .iter()
+        .map(|((y, x), t)| BoardTile {
+            y: *y,
+            x: *x,
+            id: t.tile.to_id(),
+            rot: t.rot,
+            meeple_id: match t.meeple_id {
+                Some(mid) => mid,
+                None => -1,
+            },
+            meeple_pos: match t.meeple_pos {
+                Some(mpos) => mpos,
+                None => -1,
+            },
+        })
+        .collect();
+    tiles.sort_by_key(|t| (t.y, t.x));
+
+    let bounds = Bounds {
+        min_y: tiles.iter().map(|t| t.y).min().unwrap_or(0),
+        min_x: tiles.iter().map(|t| t.x).min().unwrap_or(0),
+        max_y: tiles.iter().map(|t| t.y).max().unwrap_or(0),
+        max_x: tiles.iter().map(|t| t.x).max().unwrap_or(0),
+    };
+    (tiles, bounds)
+}
+
+#[test]
+fn board_tiles_test() {
+    use super::tile::Tile;
+
+    let item = |tile: Tile, rot: i32, meeple: Option<(i32, i32)>| TileItem {
+        id: 0,
+        tile,
+        rot,
+        feature_starting_id: 0,
+        meeple_id: meeple.map(|m| m.0),
+        meeple_pos: meeple.map(|m| m.1),
+    };
+    // far beyond the 41x41 grid boards used to be returned in
+    let board = HashMap::from([
+        ((0, 0), item(Tile::StartingTile, 0, None)),
+        ((-100, 3), item(Tile::Straight, 1, Some((7, 1)))),
+        ((250, -40), item(Tile::Monastery, 0, None)),
+    ]);
+    let (tiles, bounds) = board_tiles(&board);
+    assert_eq!(
+        tiles.iter().map(|t| (t.y, t.x)).collect::<Vec<_>>(),
+        vec![(-100, 3), (0, 0), (250, -40)]
+    );
+    assert_eq!(
+        tiles[0],
+        BoardTile {
+            y: -100,
+            x: 3,
+            id: Tile::Straight.to_id(),
+            rot: 1,
+            meeple_id: 7,
+            meeple_pos: 1,
+        }
+    );
+    assert_eq!((tiles[1].meeple_id, tiles[1].meeple_pos), (-1, -1));
+    assert_eq!(
+        bounds,
+        Bounds {
+            min_y: -100,
+            min_x: -40,
+            max_y: 250,
+            max_x: 3,
+        }
+    );
+
+    let (tiles, bounds) = board_tiles(&HashMap::new());
+    assert!(tiles.is_empty());
+    assert_eq!((bounds.min_y, bounds.max_x), (0, 0));
+}
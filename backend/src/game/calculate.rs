@@ -3,16 +3,20 @@ use std::cmp::Ordering::*;
 #[allow(unused_imports)]
 use std::collections::{HashMap, HashSet};
 
+use rocket::serde::{Deserialize, Serialize};
+
 use self::Feature::*;
 use self::Side::*;
 #[allow(unused_imports)]
 use super::decoder;
+use super::game_state::GameState;
 use super::mergeable_feature::MergeableFeature;
 use super::mov::{MeepleMove, Move, TileMove};
-use super::tile::Tile;
-use crate::error::{moves_invalid_error, Error};
+use super::rule_set::RuleSet;
+use super::tile::{Expansion, Tile};
+use crate::error::{move_error, Error, MoveError};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct CompleteEvent {
     pub feature: Feature,
     pub meeple_ids: Vec<i32>,
@@ -55,20 +59,80 @@ impl PartialEq for CompleteEvent {
 }
 impl Eq for CompleteEvent {}
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Status {
     pub meepleable_positions: Vec<i32>,
     pub complete_events: Vec<CompleteEvent>,
-    pub player0_point: i32,
-    pub player1_point: i32,
+    pub player_points: Vec<i32>,
     pub board: HashMap<(i32, i32), TileItem>,
-    pub player0_remaining_meeples: HashSet<i32>,
-    pub player1_remaining_meeples: HashSet<i32>,
+    pub player_remaining_meeples: Vec<HashSet<i32>>,
     pub tile_id_to_pos: HashMap<i32, (i32, i32)>,
     pub mergeable_features: MergeableFeature,
     pub feature_num: i32,
 }
 
+// number of meeples each player has.
+// player i owns meeple ids from `i * MEEPLE_NUM` to `(i + 1) * MEEPLE_NUM - 1`,
+// so two-player games keep using 0..=6 for player0 and 7..=13 for player1
+pub const MEEPLE_NUM: i32 = 7;
+
+// with Inns & Cathedrals, each player also has a big meeple that counts as two meeples.
+// player i's big meeple id is `BIG_MEEPLE_ID_OFFSET + i`
+pub const BIG_MEEPLE_ID_OFFSET: i32 = 1000;
+
+pub fn meeple_owner(meeple_id: i32) -> usize {
+    if is_big_meeple(meeple_id) {
+        (meeple_id - BIG_MEEPLE_ID_OFFSET) as usize
+    } else {
+        (meeple_id / MEEPLE_NUM) as usize
+    }
+}
+
+pub fn is_big_meeple(meeple_id: i32) -> bool {
+    meeple_id >= BIG_MEEPLE_ID_OFFSET
+}
+
+pub fn big_meeple_id_of(player: usize) -> i32 {
+    BIG_MEEPLE_ID_OFFSET + player as i32
+}
+
+// how many meeples the meeple counts as when deciding the majority
+pub fn meeple_strength(meeple_id: i32) -> i32 {
+    if is_big_meeple(meeple_id) {
+        2
+    } else {
+        1
+    }
+}
+
+// all the meeples a player has at the beginning of a game with `expansions` and `rule_set`
+pub fn meeples_of(player: usize, expansions: &[Expansion], rule_set: &RuleSet) -> HashSet<i32> {
+    let first = player as i32 * MEEPLE_NUM;
+    let mut meeples: HashSet<i32> = (first..first + rule_set.meeples_per_player).collect();
+    if expansions.contains(&Expansion::InnsAndCathedrals) {
+        meeples.insert(big_meeple_id_of(player));
+    }
+    meeples
+}
+
+// players who have the most meeples on a feature (all of them score when tied)
+pub fn majority_players(meeple_ids: &Vec<i32>, player_num: usize) -> Vec<usize> {
+    let mut counts = vec![0; player_num];
+    for meeple_id in meeple_ids {
+        counts[meeple_owner(*meeple_id)] += meeple_strength(*meeple_id);
+    }
+    let max_count = match counts.iter().max() {
+        Some(c) => *c,
+        None => 0,
+    };
+    if max_count == 0 {
+        return vec![];
+    }
+    (0..player_num)
+        .filter(|p| counts[*p] == max_count)
+        .collect()
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct TileItem {
     pub id: i32,
@@ -79,20 +143,29 @@ pub struct TileItem {
     pub meeple_pos: Option<i32>,
 }
 
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
 pub enum Side {
     Field,
     Road,
     City,
+    Water,
     NoSide,
 }
 
-#[derive(Eq, PartialEq, PartialOrd, Clone, Debug)]
+#[derive(Eq, PartialEq, PartialOrd, Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
 pub enum Feature {
+    #[serde(rename = "road")]
     RoadFeature,
+    #[serde(rename = "city")]
     CityFeature,
+    #[serde(rename = "monastery")]
     MonasteryFeature,
+    #[serde(rename = "field")]
     FieldFeature,
+    #[serde(rename = "river")]
+    RiverFeature,
 }
 
 impl Feature {
@@ -102,6 +175,7 @@ impl Feature {
             RoadFeature => "road".to_string(),
             CityFeature => "city".to_string(),
             MonasteryFeature => "monastery".to_string(),
+            RiverFeature => "river".to_string(),
         }
     }
 }
@@ -120,32 +194,9 @@ pub struct TileablePosition {
 
 impl TileItem {
     fn sides(self) -> [Side; 4] {
-        match self.tile {
-            Tile::StartingTile => [Road, City, Road, Field],
-            Tile::Monastery => [Field, Field, Field, Field],
-            Tile::MonasteryWithRoad => [Field, Field, Field, Road],
-            Tile::CityCapWithCrossroad => [Road, City, Road, Road],
-            Tile::TriangleWithRoad => [Road, City, City, Road],
-            Tile::TriangleWithRoadWithCOA => [Road, City, City, Road],
-            Tile::Straight => [Field, Road, Field, Road],
-            Tile::CityCap => [Field, City, Field, Field],
-            Tile::Separator => [Field, City, City, Field],
-            Tile::TripleRoad => [Road, Field, Road, Road],
-            Tile::Curve => [Field, Field, Road, Road],
-            Tile::QuadrupleRoad => [Road, Road, Road, Road],
-            Tile::Connector => [City, Field, City, Field],
-            Tile::ConnectorWithCOA => [City, Field, City, Field],
-            Tile::Left => [Field, City, Road, Road],
-            Tile::Right => [Road, City, Field, Road],
-            Tile::TripleCity => [City, City, City, Field],
-            Tile::TripleCityWithCOA => [City, City, City, Field],
-            Tile::VerticalSeparator => [Field, City, Field, City],
-            Tile::TripleCityWithRoad => [City, City, City, Road],
-            Tile::TripleCityWithRoadWithCOA => [City, City, City, Road],
-            Tile::Triangle => [Field, City, City, Field],
-            Tile::TriangleWithCOA => [Field, City, City, Field],
-            Tile::QuadrupleCityWithCOA => [City, City, City, City],
-            Tile::Invalid => [Field, Field, Field, Field],
+        match self.tile.definition() {
+            Some(d) => d.sides,
+            None => [Field, Field, Field, Field],
         }
     }
     #[allow(dead_code)]
@@ -185,1374 +236,53 @@ impl TileItem {
     }
     // side_features defined so that they are not influenced by the effect of turn
     fn side_features(self) -> Vec<Vec<DistinctFeature>> {
-        match self.tile {
-            Tile::StartingTile => vec![
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                ], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::Monastery => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::MonasteryWithRoad => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::CityCapWithCrossroad => vec![
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 6,
-                        feature: FieldFeature,
-                    },
-                ], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 4,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 6,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 5,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 4,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::TriangleWithRoad => vec![
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                ], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::TriangleWithRoadWithCOA => vec![
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                ], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::Straight => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                }], // right
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: FieldFeature,
-                    },
-                ], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                }], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::CityCap => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::Separator => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::TripleRoad => vec![
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 5,
-                        feature: FieldFeature,
-                    },
-                ], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                }], // top
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                ], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 5,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 4,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::Curve => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                }], // top
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                ], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::QuadrupleRoad => vec![
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 4,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 7,
-                        feature: FieldFeature,
-                    },
-                ], // right
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: FieldFeature,
-                    },
-                ], // top
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 5,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 0,
-                        feature: FieldFeature,
-                    },
-                ], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 7,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 6,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 5,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::Connector => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::ConnectorWithCOA => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::Left => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::Right => vec![
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                ], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::TripleCity => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::TripleCityWithCOA => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::VerticalSeparator => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: CityFeature,
-                }], // bottom
-            ],
-            Tile::TripleCityWithRoad => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::TripleCityWithRoadWithCOA => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![
-                    DistinctFeature {
-                        id: self.feature_starting_id + 3,
-                        feature: FieldFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 2,
-                        feature: RoadFeature,
-                    },
-                    DistinctFeature {
-                        id: self.feature_starting_id + 1,
-                        feature: FieldFeature,
-                    },
-                ], // bottom
-            ],
-            Tile::Triangle => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::TriangleWithCOA => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                }], // bottom
-            ],
-            Tile::QuadrupleCityWithCOA => vec![
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // right
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // top
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // left
-                vec![DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                }], // bottom
-            ],
-            Tile::Invalid => vec![vec![]],
+        match self.tile.definition() {
+            Some(d) => d
+                .side_features
+                .iter()
+                .map(|fs| fs.iter().map(|f| self.distinct_feature(*f)).collect())
+                .collect(),
+            None => vec![vec![]],
         }
     }
     pub fn features(self) -> Vec<DistinctFeature> {
-        match self.tile {
-            Tile::StartingTile => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Monastery => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: MonasteryFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::MonasteryWithRoad => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: MonasteryFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-            ],
-            Tile::CityCapWithCrossroad => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 4,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 5,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 6,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::TriangleWithRoad => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::TriangleWithRoadWithCOA => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Straight => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::CityCap => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Separator => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::TripleRoad => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 4,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 5,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Curve => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::QuadrupleRoad => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 4,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 5,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 6,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 7,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Connector => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::ConnectorWithCOA => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Left => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Right => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::TripleCity => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::TripleCityWithCOA => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::VerticalSeparator => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: CityFeature,
-                },
-            ],
-            Tile::TripleCityWithRoad => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::TripleCityWithRoadWithCOA => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 2,
-                    feature: RoadFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 3,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::Triangle => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::TriangleWithCOA => vec![
-                DistinctFeature {
-                    id: self.feature_starting_id + 0,
-                    feature: CityFeature,
-                },
-                DistinctFeature {
-                    id: self.feature_starting_id + 1,
-                    feature: FieldFeature,
-                },
-            ],
-            Tile::QuadrupleCityWithCOA => vec![DistinctFeature {
-                id: self.feature_starting_id + 0,
-                feature: CityFeature,
-            }],
-            Tile::Invalid => vec![],
+        match self.tile.definition() {
+            Some(d) => (0..d.features.len())
+                .map(|f| self.distinct_feature(f))
+                .collect(),
+            None => vec![],
+        }
+    }
+    fn distinct_feature(self, f: usize) -> DistinctFeature {
+        DistinctFeature {
+            id: self.feature_starting_id + f as i32,
+            feature: self.tile.definition().unwrap().features[f].kind.clone(),
         }
     }
 }
 
-fn create_mergeable_features(mf: &mut MergeableFeature, t: &TileItem) {
-    match t.tile {
-        Tile::StartingTile => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 3, false);
-        }
-        Tile::Monastery => {
-            mf.new_feature(t.id, 9, false);
-            mf.new_feature(t.id, 4, false);
-        }
-        Tile::MonasteryWithRoad => {
-            mf.new_feature(t.id, 9, false);
-            mf.new_feature(t.id, 5, false);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::CityCapWithCrossroad => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::TriangleWithRoad => {
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::TriangleWithRoadWithCOA => {
-            mf.new_feature(t.id, 2, true);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::Straight => {
-            mf.new_feature(t.id, 3, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 3, false);
-        }
-        Tile::CityCap => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 3, false);
-        }
-        Tile::Separator => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::TripleRoad => {
-            mf.new_feature(t.id, 3, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::Curve => {
-            mf.new_feature(t.id, 4, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::QuadrupleRoad => {
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::Connector => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::ConnectorWithCOA => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, true);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::Left => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 3, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::Right => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 3, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::TripleCity => {
-            mf.new_feature(t.id, 3, false);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::TripleCityWithCOA => {
-            mf.new_feature(t.id, 3, true);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::VerticalSeparator => {
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::TripleCityWithRoad => {
-            mf.new_feature(t.id, 3, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::TripleCityWithRoadWithCOA => {
-            mf.new_feature(t.id, 3, true);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-            mf.new_feature(t.id, 1, false);
-        }
-        Tile::Triangle => {
-            mf.new_feature(t.id, 2, false);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::TriangleWithCOA => {
-            mf.new_feature(t.id, 2, true);
-            mf.new_feature(t.id, 2, false);
-        }
-        Tile::QuadrupleCityWithCOA => {
-            mf.new_feature(t.id, 4, true);
-        }
-        Tile::Invalid => {}
+pub fn create_mergeable_features(mf: &mut MergeableFeature, t: &TileItem) {
+    if let Some(d) = t.tile.definition() {
+        for f in &d.features {
+            mf.new_feature(t.id, f.open_sides, f.coa, f.inn, f.cathedral);
+        }
     }
 }
 
-fn set_cities_to_fields(mf: &mut MergeableFeature, t: &TileItem) {
-    match t.tile {
-        Tile::StartingTile => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::Monastery => {}
-        Tile::MonasteryWithRoad => {}
-        Tile::CityCapWithCrossroad => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::TriangleWithRoad => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::TriangleWithRoadWithCOA => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::Straight => {}
-        Tile::CityCap => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::Separator => {
-            mf.set_cities(
-                (t.feature_starting_id + 2) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-            mf.set_cities(
-                (t.feature_starting_id + 2) as usize,
-                (t.feature_starting_id + 1) as usize,
-            );
-        }
-        Tile::TripleRoad => {}
-        Tile::Curve => {}
-        Tile::QuadrupleRoad => {}
-        Tile::Connector => {
-            mf.set_cities(
-                (t.feature_starting_id + 0) as usize,
-                (t.feature_starting_id + 1) as usize,
-            );
-            mf.set_cities(
-                (t.feature_starting_id + 2) as usize,
-                (t.feature_starting_id + 1) as usize,
-            );
-        }
-        Tile::ConnectorWithCOA => {
-            mf.set_cities(
-                (t.feature_starting_id + 0) as usize,
-                (t.feature_starting_id + 1) as usize,
-            );
-            mf.set_cities(
-                (t.feature_starting_id + 2) as usize,
-                (t.feature_starting_id + 1) as usize,
-            );
-        }
-        Tile::Left => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::Right => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::TripleCity => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::TripleCityWithCOA => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::VerticalSeparator => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 2) as usize,
-            );
-        }
-        Tile::TripleCityWithRoad => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-            mf.set_cities(
-                (t.feature_starting_id + 3) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::TripleCityWithRoadWithCOA => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-            mf.set_cities(
-                (t.feature_starting_id + 3) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::Triangle => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
-        }
-        Tile::TriangleWithCOA => {
-            mf.set_cities(
-                (t.feature_starting_id + 1) as usize,
-                (t.feature_starting_id + 0) as usize,
-            );
+pub fn set_cities_to_fields(mf: &mut MergeableFeature, t: &TileItem) {
+    if let Some(d) = t.tile.definition() {
+        for (i, f) in d.features.iter().enumerate() {
+            for city in &f.cities {
+                mf.set_cities(
+                    t.feature_starting_id as usize + i,
+                    t.feature_starting_id as usize + city,
+                );
+            }
         }
-        Tile::QuadrupleCityWithCOA => {}
-        Tile::Invalid => {}
     }
 }
 
-fn merge_features(
+pub fn merge_features(
     mf: &mut MergeableFeature,
     feat0: Vec<DistinctFeature>,
     feat1: Vec<DistinctFeature>,
@@ -1566,592 +296,43 @@ fn merge_features(
     }
 }
 
-pub fn calculate(moves: &Vec<Move>, get_final_status: bool) -> Result<Status, Error> {
-    let mut mergeable_features = MergeableFeature::new();
-    let mut meepleable_positions = vec![];
-    let mut complete_events = vec![];
-    let mut player0_point = 0;
-    let mut player1_point = 0;
-    let mut current_feature_id = 0;
-    let mut current_tile_id = 0;
-    let mut board = HashMap::<(i32, i32), TileItem>::new();
-    let mut player0_remaining_meeples = HashSet::from([0, 1, 2, 3, 4, 5, 6]);
-    let mut player1_remaining_meeples = HashSet::from([7, 8, 9, 10, 11, 12, 13]);
-    let mut tile_id_to_pos = HashMap::<i32, (i32, i32)>::new();
-    let mut meeple_id_to_pos = HashMap::<i32, (i32, i32)>::new();
-
-    for mv in moves {
-        match mv {
-            Move::TMove(m) => {
-                let current_tile = TileItem {
-                    id: current_tile_id,
-                    tile: m.tile,
-                    rot: m.rot,
-                    feature_starting_id: current_feature_id,
-                    meeple_id: None,
-                    meeple_pos: None,
-                };
-
-                create_mergeable_features(&mut mergeable_features, &current_tile);
-                set_cities_to_fields(&mut mergeable_features, &current_tile);
-
-                let y = m.pos.0;
-                let x = m.pos.1;
-
-                tile_id_to_pos.insert(current_tile_id, (y, x));
-
-                current_feature_id += current_tile.features().len() as i32;
-                current_tile_id += 1;
-
-                // check if the placing position is empty
-                if board.contains_key(&(y, x)) {
-                    return Err(moves_invalid_error(format!(
-                        "position ({}, {}) is not empty",
-                        y, x
-                    )));
-                }
-
-                // check if there is at least one adjacent tile
-                if y != 0 || x != 0 {
-                    let top_exists = board.contains_key(&(y - 1, x));
-                    let bottom_exists = board.contains_key(&(y + 1, x));
-                    let left_exists = board.contains_key(&(y, x - 1));
-                    let right_exists = board.contains_key(&(y, x + 1));
-                    if !top_exists && !bottom_exists && !left_exists && !right_exists {
-                        return Err(moves_invalid_error(
-                            "at least one adjacent tile must exist".to_string(),
-                        ));
-                    }
-                }
-
-                // check if the sides are correct
-                match board.get(&(y - 1, x)) {
-                    Some(t) => {
-                        if t.bottom() != current_tile.top() {
-                            return Err(moves_invalid_error(format!("top side is invalid: top tile's bottom is {:?}, but the current tile's top is {:?}", t.bottom(), current_tile.top())));
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(y + 1, x)) {
-                    Some(t) => {
-                        if t.top() != current_tile.bottom() {
-                            return Err(moves_invalid_error(format!("bottom side is invalid: bottom tile's top is {:?}, but the current tile's bottom is {:?}", t.top(), current_tile.bottom())));
-                        }
-                    }
-                    None => {}
-                }
-                // FIXME use `if let`
-                match board.get(&(y, x - 1)) {
-                    Some(t) => {
-                        if t.right() != current_tile.left() {
-                            return Err(moves_invalid_error(format!("left side is invalid: left tile's right is {:?}, but the current tile's left is {:?}", t.right(), current_tile.left())));
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(y, x + 1)) {
-                    Some(t) => {
-                        if t.left() != current_tile.right() {
-                            return Err(moves_invalid_error(format!("right side is invalid: right tile's left is {:?}, but the current tile's right is {:?}", t.left(), current_tile.right())));
-                        }
-                    }
-                    None => {}
-                }
-
-                // place tile
-                board.insert((y, x), current_tile);
-
-                // merge cities first (so that the merging fields can see the merging cities as the same city)
-                let dy = [0, -1, 0, 1];
-                let dx = [1, 0, -1, 0];
-                for dir in 0..dy.len() {
-                    let ny = y + dy[dir];
-                    let nx = x + dx[dir];
-                    match board.get(&(ny, nx)) {
-                        Some(t) => {
-                            if current_tile.features_by_dir(dir)[0].feature == CityFeature {
-                                assert_eq!(
-                                    t.features_by_dir((dir + 2) % 4).len(),
-                                    current_tile.features_by_dir(dir).len()
-                                );
-                                merge_features(
-                                    &mut mergeable_features,
-                                    t.features_by_dir((dir + 2) % 4),
-                                    current_tile.features_by_dir(dir),
-                                );
-                            }
-                        }
-                        None => {}
-                    }
-                }
-                // merge roads and fields
-                for dir in 0..dy.len() {
-                    let ny = y + dy[dir];
-                    let nx = x + dx[dir];
-                    match board.get(&(ny, nx)) {
-                        Some(t) => {
-                            if current_tile.features_by_dir(dir)[0].feature != CityFeature {
-                                assert_eq!(
-                                    t.features_by_dir((dir + 2) % 4).len(),
-                                    current_tile.features_by_dir(dir).len()
-                                );
-                                merge_features(
-                                    &mut mergeable_features,
-                                    t.features_by_dir((dir + 2) % 4),
-                                    current_tile.features_by_dir(dir),
-                                );
-                            }
-                        }
-                        None => {}
-                    }
-                }
-
-                // update meepleable positions
-                match board.get(&(y, x)) {
-                    Some(t) => {
-                        meepleable_positions.clear();
-                        for f in &t.features() {
-                            if mergeable_features.get_meeples(f.id as usize).len() == 0 {
-                                assert!(f.id >= t.feature_starting_id);
-                                meepleable_positions.push(f.id - t.feature_starting_id);
-                            }
-                        }
-                    }
-                    None => {
-                        assert!(false);
-                    }
-                }
-
-                // update open side for monastery that was placed just now
-                match m.tile {
-                    Tile::Monastery | Tile::MonasteryWithRoad => {
-                        let mut filled_count = 0;
-                        for dy in -1..2 {
-                            for dx in -1..2 {
-                                filled_count += match board.get(&(y + dy, x + dx)) {
-                                    None => 0,
-                                    _ => 1,
-                                }
-                            }
-                        }
-                        assert!(filled_count <= 9);
-                        mergeable_features.reduce_open_sides(
-                            current_tile.feature_starting_id as usize,
-                            filled_count,
-                        );
-                    }
-                    _ => {}
-                }
-
-                // update open side for monastery that had been placed around the current tile
-                for dy in -1..2 {
-                    for dx in -1..2 {
-                        if dy == 0 && dx == 0 {
-                            continue;
-                        }
-                        let ny = y + dy;
-                        let nx = x + dx;
-                        match board.get(&(ny, nx)) {
-                            Some(t) => match t.tile {
-                                Tile::Monastery | Tile::MonasteryWithRoad => {
-                                    mergeable_features
-                                        .reduce_open_sides(t.feature_starting_id as usize, 1);
-                                }
-                                _ => {}
-                            },
-                            None => {}
-                        }
-                    }
-                }
-            }
-            Move::MMove(m) => {
-                complete_events.clear();
-                let y = m.tile_pos.0;
-                let x = m.tile_pos.1;
-                if m.meeple_id != -1 {
-                    if m.meeple_id < 7 {
-                        // FIXME
-                        if !player0_remaining_meeples.contains(&m.meeple_id) {
-                            return Err(moves_invalid_error(format!(
-                                "meeple {} is already on the board",
-                                m.meeple_id
-                            )));
-                        }
-                    } else {
-                        if !player1_remaining_meeples.contains(&m.meeple_id) {
-                            return Err(moves_invalid_error(format!(
-                                "meeple {} is already on the board",
-                                m.meeple_id
-                            )));
-                        }
-                    }
-                    match board.get(&(y, x)) {
-                        Some(t) => {
-                            let feature_id = t.feature_starting_id + m.meeple_pos;
-                            if mergeable_features.get_meeples(feature_id as usize).len() != 0 {
-                                return Err(moves_invalid_error(
-                                    "meepling on this feature is not allowed".to_string(),
-                                ));
-                            }
-                            mergeable_features.place_meeple(feature_id as usize, m.meeple_id);
-                            board.insert(
-                                (y, x),
-                                TileItem {
-                                    id: board[&(y, x)].id,
-                                    tile: board[&(y, x)].tile,
-                                    feature_starting_id: board[&(y, x)].feature_starting_id,
-                                    rot: board[&(y, x)].rot,
-                                    meeple_id: Some(m.meeple_id),
-                                    meeple_pos: Some(m.meeple_pos),
-                                },
-                            ); // is there a better way to do this?
-                            assert!(!meeple_id_to_pos.contains_key(&m.meeple_id));
-                            meeple_id_to_pos.insert(m.meeple_id, (y, x));
-                            if m.meeple_id < 7 {
-                                // FIXME
-                                player0_remaining_meeples.remove(&m.meeple_id);
-                            } else {
-                                player1_remaining_meeples.remove(&m.meeple_id);
-                            }
-                        }
-                        None => {
-                            assert!(false);
-                        }
-                    }
-                }
-                match board.get(&(y, x)) {
-                    Some(t) => {
-                        for f in &t.features() {
-                            if mergeable_features.is_done(f.id as usize) {
-                                continue;
-                            }
-                            if f.feature == FieldFeature {
-                                continue;
-                            }
-                            if mergeable_features.is_completed(f.id as usize) {
-                                let sz = mergeable_features.size(f.id as usize);
-                                let meeple_ids = mergeable_features.get_meeples(f.id as usize);
-                                if meeple_ids.len() == 0 {
-                                    continue;
-                                }
-                                let pts = match f.feature {
-                                    RoadFeature => (sz * 1) as i32,
-                                    CityFeature => (sz * 2) as i32,
-                                    MonasteryFeature => 9,
-                                    FieldFeature => 0,
-                                };
-                                let mut player0_meeples = 0;
-                                let mut player1_meeples = 0;
-                                for meeple_id in &meeple_ids {
-                                    if *meeple_id < 7 {
-                                        player0_meeples += 1;
-                                        player0_remaining_meeples.insert(*meeple_id);
-                                    } else {
-                                        player1_meeples += 1;
-                                        player1_remaining_meeples.insert(*meeple_id);
-                                    }
-                                    assert!(meeple_id_to_pos.contains_key(meeple_id));
-                                    let pos = meeple_id_to_pos.get(meeple_id).unwrap();
-                                    assert!(board.contains_key(&pos));
-                                    board.insert(
-                                        *pos,
-                                        TileItem {
-                                            id: board[pos].id,
-                                            tile: board[pos].tile,
-                                            feature_starting_id: board[pos].feature_starting_id,
-                                            rot: board[pos].rot,
-                                            meeple_id: None,
-                                            meeple_pos: None,
-                                        },
-                                    );
-                                    meeple_id_to_pos.remove(&meeple_id);
-                                }
-                                if player0_meeples >= player1_meeples {
-                                    player0_point += pts;
-                                }
-                                if player1_meeples >= player0_meeples {
-                                    player1_point += pts;
-                                }
-                                complete_events.push(CompleteEvent {
-                                    feature: f.feature.clone(),
-                                    meeple_ids,
-                                    point: pts,
-                                });
-                                mergeable_features.set_as_done(f.id as usize);
-                            }
-                        }
-                    }
-                    None => {
-                        assert!(false);
-                    }
-                }
-
-                // resolve meeples on adjacent monasteries
-                for dy in -1..2 {
-                    for dx in -1..2 {
-                        if dy == 0 && dx == 0 {
-                            continue;
-                        }
-                        let ny = y + dy;
-                        let nx = x + dx;
-                        match board.get(&(ny, nx)) {
-                            Some(t) => match t.tile {
-                                Tile::Monastery | Tile::MonasteryWithRoad => {
-                                    if mergeable_features
-                                        .is_completed(t.feature_starting_id as usize)
-                                    {
-                                        let meeple_ids = mergeable_features
-                                            .get_meeples(t.feature_starting_id as usize);
-                                        if meeple_ids.len() == 0 {
-                                            continue;
-                                        }
-                                        assert!(meeple_ids.len() == 1);
-                                        if meeple_ids[0] < 7 {
-                                            player0_point += 9;
-                                            player0_remaining_meeples.insert(meeple_ids[0]);
-                                        } else {
-                                            player1_point += 9;
-                                            player1_remaining_meeples.insert(meeple_ids[0]);
-                                        }
-                                        assert!(meeple_id_to_pos.contains_key(&meeple_ids[0]));
-                                        let pos = meeple_id_to_pos.get(&meeple_ids[0]).unwrap();
-                                        assert!(board.contains_key(&pos));
-                                        board.insert(
-                                            *pos,
-                                            TileItem {
-                                                id: board[pos].id,
-                                                tile: board[pos].tile,
-                                                feature_starting_id: board[pos].feature_starting_id,
-                                                rot: board[pos].rot,
-                                                meeple_id: None,
-                                                meeple_pos: None,
-                                            },
-                                        );
-                                        meeple_id_to_pos.remove(&meeple_ids[0]);
-                                        complete_events.push(CompleteEvent {
-                                            feature: MonasteryFeature,
-                                            meeple_ids,
-                                            point: 9,
-                                        });
-                                    }
-                                }
-                                _ => {}
-                            },
-                            None => {}
-                        }
-                    }
-                }
-            }
-            Move::DMove(_m) => {
-                // do nothing
-            }
-            Move::InvalidMove => {}
-        }
-    }
-
-    if !get_final_status {
-        return Ok(Status {
-            meepleable_positions,
-            complete_events,
-            player0_point,
-            player1_point,
-            board,
-            player0_remaining_meeples,
-            player1_remaining_meeples,
-            tile_id_to_pos,
-            mergeable_features,
-            feature_num: current_feature_id,
-        });
-    }
-
-    let mut complete_events = vec![];
-
-    for t in board.values() {
-        let fs = t.features();
-        for f in &fs {
-            let meeple_ids = mergeable_features.get_meeples(f.id as usize);
-            if meeple_ids.len() == 0 {
-                continue;
-            }
-
-            if f.feature != FieldFeature && mergeable_features.is_completed(f.id as usize) {
-                continue;
-            }
-            if mergeable_features.is_done(f.id as usize) {
-                continue;
-            }
-
-            let pts = match f.feature {
-                RoadFeature => {
-                    let sz = mergeable_features.size(f.id as usize);
-                    sz as i32
-                }
-                CityFeature => {
-                    let sz = mergeable_features.size(f.id as usize);
-                    sz as i32
-                }
-                MonasteryFeature => {
-                    let open_sides = mergeable_features.get_open_sides(f.id as usize);
-                    (9 - open_sides) as i32
-                }
-                FieldFeature => {
-                    let mut p = 0;
-                    let cities = mergeable_features.get_facing_cities(f.id as usize);
-                    for city in &cities {
-                        if mergeable_features.is_completed(*city) {
-                            p += 3;
-                        }
-                    }
-                    p
-                }
-            };
-            let mut player0_meeples = 0;
-            let mut player1_meeples = 0;
-            for meeple_id in &meeple_ids {
-                if *meeple_id < 7 {
-                    player0_meeples += 1;
-                    player0_remaining_meeples.insert(*meeple_id);
-                } else {
-                    player1_meeples += 1;
-                    player1_remaining_meeples.insert(*meeple_id);
-                }
-            }
-            if player0_meeples >= player1_meeples {
-                player0_point += pts;
-            }
-            if player1_meeples >= player0_meeples {
-                player1_point += pts;
-            }
-            complete_events.push(CompleteEvent {
-                feature: f.feature.clone(),
-                meeple_ids,
-                point: pts,
-            });
-
-            mergeable_features.set_as_done(f.id as usize);
-        }
-    }
-
-    for pos in meeple_id_to_pos.values() {
-        assert!(board.contains_key(pos));
-        board.insert(
-            *pos,
-            TileItem {
-                id: board[pos].id,
-                tile: board[pos].tile,
-                feature_starting_id: board[pos].feature_starting_id,
-                rot: board[pos].rot,
-                meeple_id: None,
-                meeple_pos: None,
-            },
-        );
-    }
-
-    assert_eq!(player0_remaining_meeples.len(), 7);
-    assert_eq!(player1_remaining_meeples.len(), 7);
-
-    Ok(Status {
-        meepleable_positions,
-        complete_events,
-        player0_point,
-        player1_point,
-        board,
-        player0_remaining_meeples,
-        player1_remaining_meeples,
-        tile_id_to_pos,
-        mergeable_features,
-        feature_num: current_feature_id,
-    })
+pub fn calculate(
+    moves: &Vec<Move>,
+    player_num: usize,
+    expansions: &[Expansion],
+    rule_set: &RuleSet,
+    get_final_status: bool,
+) -> Result<Status, Error> {
+    let mut state = GameState::from_moves(moves, player_num, expansions, rule_set)?;
+    if get_final_status {
+        state.apply_final_scoring();
+    }
+    Ok(state.into_status())
 }
 
-pub fn calculate_tileable_positions(moves: &Vec<Move>, t: Tile) -> Vec<TileablePosition> {
-    let board = match calculate(&moves, false) {
-        Ok(s) => s.board,
-        Err(e) => panic!("{:?}", e.detail.msg),
-    };
-
-    let mut tile = TileItem {
-        id: t.to_id(),
-        tile: t,
-        rot: 0,
-        feature_starting_id: 0,
-        meeple_id: None,
-        meeple_pos: None,
-    };
-
-    let mut checked: HashMap<(i32, i32), bool> = HashMap::new();
-    let mut tileable_positions = vec![];
-    for (y, x) in board.keys() {
-        match checked.get(&(*y, *x)) {
-            Some(_) => {
-                continue;
-            }
-            None => {}
-        }
-        checked.insert((*y, *x), true);
-
-        let dy = [0, -1, 0, 1];
-        let dx = [1, 0, -1, 0];
-        for di in 0..4 {
-            let ny = y + dy[di];
-            let nx = x + dx[di];
-            match board.get(&(ny, nx)) {
-                Some(_) => {
-                    continue;
-                }
-                None => {}
-            }
-            match checked.get(&(ny, nx)) {
-                Some(_) => {
-                    continue;
-                }
-                None => {}
-            }
-            checked.insert((ny, nx), true);
-
-            for rot in vec![1, 2, 3, 4] {
-                tile.rotate();
-
-                match board.get(&(ny - 1, nx)) {
-                    Some(t) => {
-                        if t.bottom() != tile.top() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(ny + 1, nx)) {
-                    Some(t) => {
-                        if t.top() != tile.bottom() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(ny, nx - 1)) {
-                    Some(t) => {
-                        if t.right() != tile.left() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(ny, nx + 1)) {
-                    Some(t) => {
-                        if t.left() != tile.right() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
-
-                tileable_positions.push(TileablePosition { pos: (ny, nx), rot })
-            }
-        }
-    }
+pub fn calculate_tileable_positions(
+    moves: &Vec<Move>,
+    player_num: usize,
+    expansions: &[Expansion],
+    rule_set: &RuleSet,
+    t: Tile,
+) -> Result<Vec<TileablePosition>, Error> {
+    let state = GameState::from_moves(moves, player_num, expansions, rule_set)?;
+    Ok(state.tileable_positions(t))
+}
 
-    tileable_positions
+// a tile can only be discarded when there is nowhere to place it
+pub fn validate_discard(
+    moves: &Vec<Move>,
+    player_num: usize,
+    expansions: &[Expansion],
+    rule_set: &RuleSet,
+    t: Tile,
+) -> Result<(), Error> {
+    if !calculate_tileable_positions(moves, player_num, expansions, rule_set, t)?.is_empty() {
+        return Err(move_error(MoveError::TilePlaceable { tile: t }));
+    }
+    Ok(())
 }
 
 #[test]
@@ -2189,7 +370,7 @@ fn calculate_test_for_road_and_city_completion() {
         rot: 2,
         pos: (-1, 0),
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.meepleable_positions, vec![0, 1, 2, 3]);
@@ -2208,12 +389,12 @@ fn calculate_test_for_road_and_city_completion() {
         tile_pos: (-1, 0),
         meeple_pos: 0,
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 0);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 0);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 0);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2229,7 +410,7 @@ fn calculate_test_for_road_and_city_completion() {
         rot: 3,
         pos: (0, -1),
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.meepleable_positions, vec![0, 1, 2, 3, 4, 5, 6]);
@@ -2248,12 +429,12 @@ fn calculate_test_for_road_and_city_completion() {
         tile_pos: (0, -1),
         meeple_pos: 0,
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 0);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 0);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 0);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2269,7 +450,7 @@ fn calculate_test_for_road_and_city_completion() {
         rot: 0,
         pos: (0, 1),
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.meepleable_positions, vec![0, 1, 2, 3, 4, 5, 6]);
@@ -2288,15 +469,15 @@ fn calculate_test_for_road_and_city_completion() {
         tile_pos: (0, 1),
         meeple_pos: 2,
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![1]);
             assert_eq!(res.complete_events[0].point, 3);
-            assert_eq!(res.player0_point, 3);
-            assert_eq!(res.player1_point, 0);
+            assert_eq!(res.player_points[0], 3);
+            assert_eq!(res.player_points[1], 0);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2312,7 +493,7 @@ fn calculate_test_for_road_and_city_completion() {
         rot: 1,
         pos: (0, -2),
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.meepleable_positions, vec![1, 2, 3]);
@@ -2331,15 +512,15 @@ fn calculate_test_for_road_and_city_completion() {
         tile_pos: (0, -2),
         meeple_pos: 2,
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![7]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 3);
-            assert_eq!(res.player1_point, 4);
+            assert_eq!(res.player_points[0], 3);
+            assert_eq!(res.player_points[1], 4);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2355,7 +536,7 @@ fn calculate_test_for_road_and_city_completion() {
         rot: 3,
         pos: (-1, 1),
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.meepleable_positions, vec![1, 2, 3]);
@@ -2374,22 +555,22 @@ fn calculate_test_for_road_and_city_completion() {
         tile_pos: (-1, 1),
         meeple_pos: 1,
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![0]);
             assert_eq!(res.complete_events[0].point, 10);
-            assert_eq!(res.player0_point, 13);
-            assert_eq!(res.player1_point, 4);
+            assert_eq!(res.player_points[0], 13);
+            assert_eq!(res.player_points[1], 4);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
         }
     }
 
-    let status = calculate(&mvs, true);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
     match status {
         Ok(res) => {
             let mut events = vec![];
@@ -2404,8 +585,8 @@ fn calculate_test_for_road_and_city_completion() {
             assert_eq!(events[1].feature, FieldFeature);
             assert_eq!(events[1].meeple_ids, vec![1]);
             assert_eq!(events[1].point, 3);
-            assert_eq!(res.player0_point, 16);
-            assert_eq!(res.player1_point, 5);
+            assert_eq!(res.player_points[0], 16);
+            assert_eq!(res.player_points[1], 5);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2448,7 +629,7 @@ fn calculate_test_for_monastery_completion() {
         rot: 0,
         pos: (1, 0),
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.meepleable_positions, vec![0, 1]);
@@ -2467,12 +648,12 @@ fn calculate_test_for_monastery_completion() {
         tile_pos: (1, 0),
         meeple_pos: 0,
     }));
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 0);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 0);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 0);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2669,7 +850,7 @@ fn calculate_test_for_monastery_completion() {
         meeple_pos: 0,
     }));
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             let mut events = vec![];
@@ -2684,8 +865,8 @@ fn calculate_test_for_monastery_completion() {
             assert_eq!(events[1].feature, MonasteryFeature);
             assert_eq!(events[1].meeple_ids, vec![7]);
             assert_eq!(events[1].point, 9);
-            assert_eq!(res.player0_point, 9);
-            assert_eq!(res.player1_point, 9);
+            assert_eq!(res.player_points[0], 9);
+            assert_eq!(res.player_points[1], 9);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2731,15 +912,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::Straight, 1, (0, 1), 0, 1);
     add_move(&mut mvs, Tile::CityCap, 2, (-1, 0), 7, 0);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![7]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 4);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 4);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2751,15 +932,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::TripleRoad, 0, (0, -1), 2, 4);
     add_move(&mut mvs, Tile::CityCap, 3, (1, 1), 8, 0);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![8]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 8);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 8);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2770,15 +951,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::TripleRoad, 0, (2, 1), 8, 1);
     add_move(&mut mvs, Tile::QuadrupleRoad, 0, (0, -2), 3, 4);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![3]);
             assert_eq!(res.complete_events[0].point, 2);
-            assert_eq!(res.player0_point, 2);
-            assert_eq!(res.player1_point, 8);
+            assert_eq!(res.player_points[0], 2);
+            assert_eq!(res.player_points[1], 8);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2787,15 +968,15 @@ fn calculate_test0() {
 
     add_move(&mut mvs, Tile::TripleRoad, 2, (3, 1), 9, 4);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![9]);
             assert_eq!(res.complete_events[0].point, 2);
-            assert_eq!(res.player0_point, 2);
-            assert_eq!(res.player1_point, 10);
+            assert_eq!(res.player_points[0], 2);
+            assert_eq!(res.player_points[1], 10);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2804,15 +985,15 @@ fn calculate_test0() {
 
     add_move(&mut mvs, Tile::CityCapWithCrossroad, 0, (-1, 2), 3, 0);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![0]);
             assert_eq!(res.complete_events[0].point, 5);
-            assert_eq!(res.player0_point, 7);
-            assert_eq!(res.player1_point, 10);
+            assert_eq!(res.player_points[0], 7);
+            assert_eq!(res.player_points[1], 10);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2825,15 +1006,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::Straight, 0, (2, -1), -1, -1);
     add_move(&mut mvs, Tile::Left, 1, (1, -2), 11, 2);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![7]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 7);
-            assert_eq!(res.player1_point, 14);
+            assert_eq!(res.player_points[0], 7);
+            assert_eq!(res.player_points[1], 14);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2842,15 +1023,15 @@ fn calculate_test0() {
 
     add_move(&mut mvs, Tile::CityCap, 2, (-3, 2), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![3]);
             assert_eq!(res.complete_events[0].point, 8);
-            assert_eq!(res.player0_point, 15);
-            assert_eq!(res.player1_point, 14);
+            assert_eq!(res.player_points[0], 15);
+            assert_eq!(res.player_points[1], 14);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2861,15 +1042,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::TripleCity, 2, (-5, 2), -1, -1);
     add_move(&mut mvs, Tile::TriangleWithRoad, 2, (3, 2), 12, 0);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![10]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 15);
-            assert_eq!(res.player1_point, 18);
+            assert_eq!(res.player_points[0], 15);
+            assert_eq!(res.player_points[1], 18);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2880,15 +1061,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::Curve, 3, (0, -3), -1, -1);
     add_move(&mut mvs, Tile::CityCapWithCrossroad, 2, (3, -1), 0, 0);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![2]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 19);
-            assert_eq!(res.player1_point, 18);
+            assert_eq!(res.player_points[0], 19);
+            assert_eq!(res.player_points[1], 18);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2898,15 +1079,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::TripleCity, 2, (-5, 3), -1, -1);
     add_move(&mut mvs, Tile::StartingTile, 0, (4, -1), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![0]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 23);
-            assert_eq!(res.player1_point, 18);
+            assert_eq!(res.player_points[0], 23);
+            assert_eq!(res.player_points[1], 18);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2916,15 +1097,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::VerticalSeparator, 1, (-5, 1), 10, 2);
     add_move(&mut mvs, Tile::Right, 0, (2, 0), 0, 1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![1]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 27);
-            assert_eq!(res.player1_point, 18);
+            assert_eq!(res.player_points[0], 27);
+            assert_eq!(res.player_points[1], 18);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2935,15 +1116,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::VerticalSeparator, 0, (-4, 5), 1, 2);
     add_move(&mut mvs, Tile::Left, 2, (-5, 5), 13, 0);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![13]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 27);
-            assert_eq!(res.player1_point, 22);
+            assert_eq!(res.player_points[0], 27);
+            assert_eq!(res.player_points[1], 22);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2955,15 +1136,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::MonasteryWithRoad, 3, (-1, 1), 2, 0);
     add_move(&mut mvs, Tile::TripleRoad, 2, (3, 0), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![8]);
             assert_eq!(res.complete_events[0].point, 3);
-            assert_eq!(res.player0_point, 27);
-            assert_eq!(res.player1_point, 25);
+            assert_eq!(res.player_points[0], 27);
+            assert_eq!(res.player_points[1], 25);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -2975,15 +1156,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::Straight, 0, (0, -4), -1, -1);
     add_move(&mut mvs, Tile::TriangleWithRoad, 3, (1, -3), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![11]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 27);
-            assert_eq!(res.player1_point, 29);
+            assert_eq!(res.player_points[0], 27);
+            assert_eq!(res.player_points[1], 29);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3005,15 +1186,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::Straight, 0, (1, 3), -1, -1);
     add_move(&mut mvs, Tile::MonasteryWithRoad, 2, (-2, 0), 6, 0);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, MonasteryFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![2]);
             assert_eq!(res.complete_events[0].point, 9);
-            assert_eq!(res.player0_point, 36);
-            assert_eq!(res.player1_point, 29);
+            assert_eq!(res.player_points[0], 36);
+            assert_eq!(res.player_points[1], 29);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3024,15 +1205,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::Straight, 1, (3, -2), -1, -1);
     add_move(&mut mvs, Tile::Curve, 3, (0, 3), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, MonasteryFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![13]);
             assert_eq!(res.complete_events[0].point, 9);
-            assert_eq!(res.player0_point, 36);
-            assert_eq!(res.player1_point, 38);
+            assert_eq!(res.player_points[0], 36);
+            assert_eq!(res.player_points[1], 38);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3046,15 +1227,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::StartingTile, 3, (5, 2), -1, -1);
     add_move(&mut mvs, Tile::Curve, 0, (0, 4), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, RoadFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![13]);
             assert_eq!(res.complete_events[0].point, 6);
-            assert_eq!(res.player0_point, 36);
-            assert_eq!(res.player1_point, 44);
+            assert_eq!(res.player_points[0], 36);
+            assert_eq!(res.player_points[1], 44);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3065,15 +1246,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::TriangleWithRoad, 1, (5, 1), -1, -1);
     add_move(&mut mvs, Tile::Left, 2, (-7, 5), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![3]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 40);
-            assert_eq!(res.player1_point, 44);
+            assert_eq!(res.player_points[0], 40);
+            assert_eq!(res.player_points[1], 44);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3084,15 +1265,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::TriangleWithCOA, 1, (3, 4), -1, -1);
     add_move(&mut mvs, Tile::CityCap, 1, (-5, -1), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![10]);
             assert_eq!(res.complete_events[0].point, 6);
-            assert_eq!(res.player0_point, 40);
-            assert_eq!(res.player1_point, 50);
+            assert_eq!(res.player_points[0], 40);
+            assert_eq!(res.player_points[1], 50);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3103,7 +1284,7 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::ConnectorWithCOA, 0, (3, 5), 10, 1);
     add_move(&mut mvs, Tile::CityCapWithCrossroad, 3, (-3, 0), 3, 2);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 2);
@@ -3113,8 +1294,8 @@ fn calculate_test0() {
             assert_eq!(res.complete_events[1].feature, MonasteryFeature);
             assert_eq!(res.complete_events[1].meeple_ids, vec![4]);
             assert_eq!(res.complete_events[1].point, 9);
-            assert_eq!(res.player0_point, 51);
-            assert_eq!(res.player1_point, 50);
+            assert_eq!(res.player_points[0], 51);
+            assert_eq!(res.player_points[1], 50);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3123,15 +1304,15 @@ fn calculate_test0() {
 
     add_move(&mut mvs, Tile::VerticalSeparator, 1, (3, 6), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![10]);
             assert_eq!(res.complete_events[0].point, 12);
-            assert_eq!(res.player0_point, 51);
-            assert_eq!(res.player1_point, 62);
+            assert_eq!(res.player_points[0], 51);
+            assert_eq!(res.player_points[1], 62);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3144,15 +1325,15 @@ fn calculate_test0() {
     add_move(&mut mvs, Tile::Curve, 1, (-1, 4), -1, -1);
     add_move(&mut mvs, Tile::Separator, 2, (-3, -1), 4, 1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![4]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 55);
-            assert_eq!(res.player1_point, 62);
+            assert_eq!(res.player_points[0], 55);
+            assert_eq!(res.player_points[1], 62);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3161,15 +1342,15 @@ fn calculate_test0() {
 
     add_move(&mut mvs, Tile::Triangle, 0, (4, 3), -1, -1);
 
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events.len(), 1);
             assert_eq!(res.complete_events[0].feature, CityFeature);
             assert_eq!(res.complete_events[0].meeple_ids, vec![12]);
             assert_eq!(res.complete_events[0].point, 10);
-            assert_eq!(res.player0_point, 55);
-            assert_eq!(res.player1_point, 72);
+            assert_eq!(res.player_points[0], 55);
+            assert_eq!(res.player_points[1], 72);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3178,11 +1359,11 @@ fn calculate_test0() {
 
     add_move(&mut mvs, Tile::TripleCityWithRoadWithCOA, 1, (-6, 6), 4, 1);
 
-    let status = calculate(&mvs, true);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
     match status {
         Ok(res) => {
-            assert_eq!(res.player0_point, 113);
-            assert_eq!(res.player1_point, 116);
+            assert_eq!(res.player_points[0], 113);
+            assert_eq!(res.player_points[1], 116);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3199,13 +1380,13 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::TriangleWithRoad, 3, (-1, 0), 7, 0);
     add_move(&mut mvs, Tile::TripleRoad, 1, (-1, 1), 1, 4);
     add_move(&mut mvs, Tile::TripleCityWithRoad, 0, (-2, 1), 8, 2);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![8]);
             assert_eq!(res.complete_events[0].point, 2);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 2);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 2);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3217,13 +1398,13 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::TripleRoad, 3, (-2, 3), 8, 2);
     add_move(&mut mvs, Tile::ConnectorWithCOA, 0, (0, 3), -1, -1);
     add_move(&mut mvs, Tile::TripleCityWithRoadWithCOA, 0, (-3, 3), 9, 0);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![8]);
             assert_eq!(res.complete_events[0].point, 2);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 4);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 4);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3231,26 +1412,26 @@ fn calculate_test1() {
     }
     add_move(&mut mvs, Tile::Curve, 3, (0, -1), -1, -1);
     add_move(&mut mvs, Tile::CityCapWithCrossroad, 0, (-2, 4), 8, 2);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![8]);
             assert_eq!(res.complete_events[0].point, 2);
-            assert_eq!(res.player0_point, 0);
-            assert_eq!(res.player1_point, 6);
+            assert_eq!(res.player_points[0], 0);
+            assert_eq!(res.player_points[1], 6);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
         }
     }
     add_move(&mut mvs, Tile::Right, 3, (0, 4), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![3]);
             assert_eq!(res.complete_events[0].point, 8);
-            assert_eq!(res.player0_point, 8);
-            assert_eq!(res.player1_point, 6);
+            assert_eq!(res.player_points[0], 8);
+            assert_eq!(res.player_points[1], 6);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3269,13 +1450,13 @@ fn calculate_test1() {
         -1,
         -1,
     );
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![1]);
             assert_eq!(res.complete_events[0].point, 3);
-            assert_eq!(res.player0_point, 11);
-            assert_eq!(res.player1_point, 6);
+            assert_eq!(res.player_points[0], 11);
+            assert_eq!(res.player_points[1], 6);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3286,26 +1467,26 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::TriangleWithCOA, 2, (-3, 2), -1, -1);
     add_move(&mut mvs, Tile::TriangleWithCOA, 1, (-4, 2), 4, 0);
     add_move(&mut mvs, Tile::Curve, 2, (1, -1), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![0, 8]);
             assert_eq!(res.complete_events[0].point, 6);
-            assert_eq!(res.player0_point, 17);
-            assert_eq!(res.player1_point, 12);
+            assert_eq!(res.player_points[0], 17);
+            assert_eq!(res.player_points[1], 12);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
         }
     }
     add_move(&mut mvs, Tile::StartingTile, 0, (2, -2), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![1]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 21);
-            assert_eq!(res.player1_point, 12);
+            assert_eq!(res.player_points[0], 21);
+            assert_eq!(res.player_points[1], 12);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3313,15 +1494,15 @@ fn calculate_test1() {
     }
     add_move(&mut mvs, Tile::Monastery, 0, (-1, 2), 8, 0);
     add_move(&mut mvs, Tile::MonasteryWithRoad, 2, (-1, 3), 0, 0);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![0]);
             assert_eq!(res.complete_events[0].point, 9);
             assert_eq!(res.complete_events[1].meeple_ids, vec![8]);
             assert_eq!(res.complete_events[1].point, 9);
-            assert_eq!(res.player0_point, 30);
-            assert_eq!(res.player1_point, 21);
+            assert_eq!(res.player_points[0], 30);
+            assert_eq!(res.player_points[1], 21);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3338,26 +1519,26 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::TriangleWithRoadWithCOA, 1, (-5, 1), 11, 0);
     add_move(&mut mvs, Tile::MonasteryWithRoad, 2, (-5, 3), 5, 0);
     add_move(&mut mvs, Tile::TripleRoad, 1, (0, 5), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![10]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 30);
-            assert_eq!(res.player1_point, 25);
+            assert_eq!(res.player_points[0], 30);
+            assert_eq!(res.player_points[1], 25);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
         }
     }
     add_move(&mut mvs, Tile::Separator, 1, (4, -2), 1, 0);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![0]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 34);
-            assert_eq!(res.player1_point, 25);
+            assert_eq!(res.player_points[0], 34);
+            assert_eq!(res.player_points[1], 25);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3365,13 +1546,13 @@ fn calculate_test1() {
     }
     add_move(&mut mvs, Tile::Right, 3, (2, -3), 12, 0);
     add_move(&mut mvs, Tile::Left, 3, (4, -1), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![1]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 38);
-            assert_eq!(res.player1_point, 25);
+            assert_eq!(res.player_points[0], 38);
+            assert_eq!(res.player_points[1], 25);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3384,13 +1565,13 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::Straight, 1, (-5, 6), -1, -1);
     add_move(&mut mvs, Tile::Curve, 1, (-6, 4), -1, -1);
     add_move(&mut mvs, Tile::QuadrupleRoad, 1, (-5, 7), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![8]);
             assert_eq!(res.complete_events[0].point, 9);
-            assert_eq!(res.player0_point, 38);
-            assert_eq!(res.player1_point, 34);
+            assert_eq!(res.player_points[0], 38);
+            assert_eq!(res.player_points[1], 34);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3401,13 +1582,13 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::CityCapWithCrossroad, 2, (1, 5), 6, 0);
     add_move(&mut mvs, Tile::Curve, 2, (-7, 2), -1, -1);
     add_move(&mut mvs, Tile::CityCap, 0, (2, 5), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![6]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 42);
-            assert_eq!(res.player1_point, 34);
+            assert_eq!(res.player_points[0], 42);
+            assert_eq!(res.player_points[1], 34);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3419,13 +1600,13 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::Curve, 0, (-7, 1), -1, -1);
     add_move(&mut mvs, Tile::ConnectorWithCOA, 0, (2, -4), -1, -1);
     add_move(&mut mvs, Tile::StartingTile, 2, (-9, 2), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![6]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 46);
-            assert_eq!(res.player1_point, 34);
+            assert_eq!(res.player_points[0], 46);
+            assert_eq!(res.player_points[1], 34);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3436,13 +1617,13 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::Straight, 1, (-8, 5), -1, -1);
     add_move(&mut mvs, Tile::VerticalSeparator, 1, (-3, -2), 6, 2);
     add_move(&mut mvs, Tile::StartingTile, 1, (2, -5), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![12]);
             assert_eq!(res.complete_events[0].point, 8);
-            assert_eq!(res.player0_point, 46);
-            assert_eq!(res.player1_point, 42);
+            assert_eq!(res.player_points[0], 46);
+            assert_eq!(res.player_points[1], 42);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3451,13 +1632,13 @@ fn calculate_test1() {
     add_move(&mut mvs, Tile::Straight, 1, (4, -4), -1, -1);
     add_move(&mut mvs, Tile::TripleCity, 3, (-3, 0), -1, -1);
     add_move(&mut mvs, Tile::CityCap, 1, (-3, -3), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![6]);
             assert_eq!(res.complete_events[0].point, 4);
-            assert_eq!(res.player0_point, 50);
-            assert_eq!(res.player1_point, 42);
+            assert_eq!(res.player_points[0], 50);
+            assert_eq!(res.player_points[1], 42);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3465,13 +1646,13 @@ fn calculate_test1() {
     }
     add_move(&mut mvs, Tile::Left, 3, (5, -2), 12, 1);
     add_move(&mut mvs, Tile::Right, 2, (-7, 3), 6, 1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![1]);
             assert_eq!(res.complete_events[0].point, 6);
-            assert_eq!(res.player0_point, 56);
-            assert_eq!(res.player1_point, 42);
+            assert_eq!(res.player_points[0], 56);
+            assert_eq!(res.player_points[1], 42);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3479,19 +1660,19 @@ fn calculate_test1() {
     }
     add_move(&mut mvs, Tile::Separator, 2, (1, 3), 13, 2);
     add_move(&mut mvs, Tile::Monastery, 0, (3, -4), 1, 0);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(res) => {
             assert_eq!(res.complete_events[0].meeple_ids, vec![0]);
             assert_eq!(res.complete_events[0].point, 9);
-            assert_eq!(res.player0_point, 65);
-            assert_eq!(res.player1_point, 42);
+            assert_eq!(res.player_points[0], 65);
+            assert_eq!(res.player_points[1], 42);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
         }
     }
-    let status = calculate(&mvs, true);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
     match status {
         Ok(res) => {
             let mut events = vec![];
@@ -3524,8 +1705,8 @@ fn calculate_test1() {
             assert_eq!(events[7].feature, FieldFeature);
             assert_eq!(events[7].meeple_ids, [12]);
             assert_eq!(events[7].point, 9);
-            assert_eq!(res.player0_point, 110);
-            assert_eq!(res.player1_point, 89);
+            assert_eq!(res.player_points[0], 110);
+            assert_eq!(res.player_points[1], 89);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3534,7 +1715,7 @@ fn calculate_test1() {
 
     // check if calculate works fairly fast (probably not fast enough yet)
     for _i in 0..1000 {
-        match calculate(&mut mvs, false) {
+        match calculate(&mut mvs, 2, &[], &RuleSet::default(), false) {
             Ok(_) => {}
             Err(_) => {}
         }
@@ -3574,7 +1755,7 @@ fn calculate_test_for_field0() {
     add_move(&mut mvs, Tile::CityCap, 3, (-2, 6), 11, 0);
     add_move(&mut mvs, Tile::Triangle, 3, (-2, 4), -1, -1);
 
-    let status = calculate(&mvs, true);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
     match status {
         Ok(mut res) => {
             res.complete_events.sort();
@@ -3582,8 +1763,8 @@ fn calculate_test_for_field0() {
             assert_eq!(res.complete_events[6].feature, FieldFeature);
             assert_eq!(res.complete_events[6].meeple_ids, vec![9]);
             assert_eq!(res.complete_events[6].point, 12);
-            assert_eq!(res.player0_point, 31);
-            assert_eq!(res.player1_point, 35);
+            assert_eq!(res.player_points[0], 31);
+            assert_eq!(res.player_points[1], 35);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3598,7 +1779,7 @@ fn calculate_test_for_field0() {
     add_move(&mut mvs, Tile::ConnectorWithCOA, 0, (-5, 4), 2, 1);
     add_move(&mut mvs, Tile::TripleRoad, 1, (-3, 3), 10, 4);
 
-    let status = calculate(&mvs, true);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
     match status {
         Ok(mut res) => {
             res.complete_events.sort();
@@ -3609,8 +1790,8 @@ fn calculate_test_for_field0() {
             assert_eq!(res.complete_events[7].feature, FieldFeature);
             assert_eq!(res.complete_events[7].meeple_ids, vec![9]);
             assert_eq!(res.complete_events[7].point, 12);
-            assert_eq!(res.player0_point, 37);
-            assert_eq!(res.player1_point, 48);
+            assert_eq!(res.player_points[0], 37);
+            assert_eq!(res.player_points[1], 48);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3620,20 +1801,20 @@ fn calculate_test_for_field0() {
 
 #[test]
 fn calculate_test_for_field1() {
-    let mut mvs = decoder::decode_from_file_path("src/data/388947581.json".to_string());
+    let mut mvs = decoder::decode_from_file_path("src/data/388947581.json".to_string()).unwrap();
     for _ in 0..48 {
         mvs.pop();
     }
 
-    let status = calculate(&mvs, true);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
     match status {
         Ok(mut res) => {
             res.complete_events.sort();
             assert_eq!(res.complete_events.len(), 10);
             assert_eq!(res.complete_events[8].feature, FieldFeature);
             assert_eq!(res.complete_events[8].point, 3);
-            assert_eq!(res.player0_point, 48);
-            assert_eq!(res.player1_point, 42);
+            assert_eq!(res.player_points[0], 48);
+            assert_eq!(res.player_points[1], 42);
         }
         Err(e) => {
             panic!("Error: {:?}", e.detail);
@@ -3650,7 +1831,7 @@ fn falculate_test_closed_field() {
     add_move(&mut mvs, Tile::Curve, 2, (0, -1), -1, -1);
     add_move(&mut mvs, Tile::Curve, 0, (-1, 1), -1, -1);
     add_move(&mut mvs, Tile::Curve, 1, (0, 1), -1, -1);
-    let status = calculate(&mvs, false);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
     match status {
         Ok(mut res) => {
             res.complete_events.sort();
@@ -3663,7 +1844,7 @@ fn falculate_test_closed_field() {
             panic!("Error: {:?}", e.detail);
         }
     }
-    let status = calculate(&mvs, true);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
     match status {
         Ok(mut res) => {
             res.complete_events.sort();
@@ -3696,12 +1877,12 @@ fn calculate_test_with_bga_json_data() {
         let exp_player0_point = d.1;
         let exp_player1_point = d.2;
 
-        let mvs = decoder::decode_from_file_path(file_path.to_string());
-        let status = calculate(&mvs, true);
+        let mvs = decoder::decode_from_file_path(file_path.to_string()).unwrap();
+        let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
         match status {
             Ok(res) => {
-                assert_eq!(res.player0_point, exp_player0_point);
-                assert_eq!(res.player1_point, exp_player1_point);
+                assert_eq!(res.player_points[0], exp_player0_point);
+                assert_eq!(res.player_points[1], exp_player1_point);
             }
             Err(e) => {
                 panic!("Error: {:?}", e.detail);
@@ -3709,3 +1890,452 @@ fn calculate_test_with_bga_json_data() {
         }
     }
 }
+
+#[test]
+fn calculate_test_for_three_players() {
+    // same moves as `calculate_test_for_field0`, but player1's meeples are owned by player2 instead
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::TripleRoad, 1, (0, 1), 0, 4);
+    add_move(&mut mvs, Tile::Straight, 0, (-1, 1), 14, 1);
+    add_move(&mut mvs, Tile::CityCap, 2, (-1, 0), 1, 0);
+    add_move(&mut mvs, Tile::Triangle, 0, (-1, -1), 15, 0);
+    add_move(&mut mvs, Tile::Separator, 1, (-1, 2), 1, 0);
+    add_move(&mut mvs, Tile::TripleRoad, 3, (-2, 1), -1, -1);
+    add_move(&mut mvs, Tile::Separator, 0, (-1, 3), 2, 0);
+    add_move(&mut mvs, Tile::Monastery, 0, (0, 2), 14, 0);
+    add_move(&mut mvs, Tile::StartingTile, 2, (-2, 2), 1, 0);
+    add_move(&mut mvs, Tile::Straight, 0, (1, 1), 16, 2);
+    add_move(&mut mvs, Tile::VerticalSeparator, 0, (-1, 4), 1, 0);
+    add_move(&mut mvs, Tile::CityCapWithCrossroad, 0, (0, 4), 17, 0);
+    add_move(&mut mvs, Tile::TriangleWithRoad, 2, (-2, 3), 3, 2);
+    add_move(&mut mvs, Tile::TripleCity, 3, (-2, -1), -1, -1);
+    add_move(&mut mvs, Tile::Left, 3, (0, -1), 4, 0);
+    add_move(&mut mvs, Tile::Curve, 3, (0, 3), -1, -1);
+    add_move(&mut mvs, Tile::Left, 1, (0, -2), -1, -1);
+    add_move(&mut mvs, Tile::Curve, 2, (-1, 5), -1, -1);
+    add_move(&mut mvs, Tile::CityCap, 2, (1, -2), 4, 0);
+    add_move(&mut mvs, Tile::StartingTile, 1, (-2, 5), -1, -1);
+    add_move(&mut mvs, Tile::Straight, 1, (-3, 2), -1, -1);
+    add_move(&mut mvs, Tile::Left, 0, (-3, 5), 17, 0);
+    add_move(&mut mvs, Tile::Curve, 2, (-3, 4), -1, -1);
+    add_move(&mut mvs, Tile::TriangleWithRoadWithCOA, 1, (-1, -2), -1, -1);
+    add_move(&mut mvs, Tile::Straight, 1, (-1, -3), -1, -1);
+    add_move(&mut mvs, Tile::CityCap, 3, (-2, 6), 18, 0);
+    add_move(&mut mvs, Tile::Triangle, 3, (-2, 4), -1, -1);
+
+    let status = calculate(&mvs, 3, &[], &RuleSet::default(), true);
+    match status {
+        Ok(res) => {
+            assert_eq!(res.player_points, vec![31, 0, 35]);
+            assert_eq!(
+                res.player_remaining_meeples[2],
+                meeples_of(2, &[], &RuleSet::default())
+            );
+        }
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+
+    // player2's meeples don't exist in a two-player game
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
+    match status {
+        Ok(_) => {
+            panic!("meeple 14 must be invalid in a two-player game");
+        }
+        Err(e) => {
+            assert_eq!(e.detail.msg, "meeple 14 doesn't belong to any player");
+        }
+    }
+}
+
+#[test]
+fn calculate_test_for_inns_and_cathedrals() {
+    let expansions = vec![Expansion::InnsAndCathedrals];
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::Cathedral, 0, (-1, 0), 0, 0);
+    add_move(&mut mvs, Tile::StraightWithInn, 1, (0, 1), 1001, 1);
+    add_move(&mut mvs, Tile::MonasteryWithRoad, 3, (0, -1), -1, -1);
+    add_move(&mut mvs, Tile::MonasteryWithRoad, 1, (0, 2), -1, -1);
+
+    // the road with an inn is worth 2 points per tile
+    let status = calculate(&mvs, 2, &expansions, &RuleSet::default(), false);
+    match status {
+        Ok(res) => {
+            assert_eq!(res.player_points, vec![0, 8]);
+            assert_eq!(
+                res.complete_events,
+                vec![CompleteEvent {
+                    feature: RoadFeature,
+                    meeple_ids: vec![1001],
+                    point: 8,
+                }]
+            );
+            assert!(res.player_remaining_meeples[1].contains(&1001));
+        }
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+
+    // the incomplete city with a cathedral is worth nothing
+    let status = calculate(&mvs, 2, &expansions, &RuleSet::default(), true);
+    match status {
+        Ok(res) => {
+            assert_eq!(res.player_points, vec![0, 8]);
+        }
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+
+    // neither the tiles nor the big meeples can be used without the expansion
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
+    match status {
+        Ok(_) => {
+            panic!("Cathedral must be invalid without the expansion");
+        }
+        Err(e) => {
+            assert_eq!(e.detail.msg, "tile Cathedral is not used in this game");
+        }
+    }
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::CityCap, 2, (1, 0), 1000, 1);
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), false);
+    match status {
+        Ok(_) => {
+            panic!("meeple 1000 must be invalid without the expansion");
+        }
+        Err(e) => {
+            assert_eq!(e.detail.msg, "meeple 1000 doesn't belong to any player");
+        }
+    }
+}
+
+#[test]
+fn calculate_test_for_river() {
+    let expansions = vec![Expansion::River];
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::RiverSpring, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::RiverStraight, 0, (1, 0), -1, -1);
+
+    // the river can only be continued from its open end, without turning to the same direction twice in a row
+    let tileable_positions =
+        calculate_tileable_positions(&mvs, 2, &expansions, &RuleSet::default(), Tile::RiverCurve)
+            .unwrap();
+    let rots: Vec<i32> = tileable_positions
+        .iter()
+        .map(|p| {
+            assert_eq!(p.pos, (2, 0));
+            p.rot
+        })
+        .collect();
+    assert_eq!(rots, vec![1, 2]);
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::RiverStraight, 0, (1, 1), -1, -1);
+    match calculate(&invalid_mvs, 2, &expansions, &RuleSet::default(), false) {
+        Ok(_) => panic!("the river must be continued from its end"),
+        Err(e) => assert_eq!(e.detail.msg, "a river tile must continue the river"),
+    }
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::RiverWithMonastery, 0, (2, 0), 0, 2);
+    match calculate(&invalid_mvs, 2, &expansions, &RuleSet::default(), false) {
+        Ok(_) => panic!("meeples can't be placed on the river"),
+        Err(e) => assert_eq!(e.detail.msg, "meepling on this feature is not allowed"),
+    }
+
+    add_move(&mut mvs, Tile::RiverCurve, 1, (2, 0), -1, -1);
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::RiverCurve, 2, (2, -1), -1, -1);
+    match calculate(&invalid_mvs, 2, &expansions, &RuleSet::default(), false) {
+        Ok(_) => panic!("the river must not make a U-turn"),
+        Err(e) => assert_eq!(
+            e.detail.msg,
+            "the river must not turn to the same direction twice in a row"
+        ),
+    }
+
+    add_move(&mut mvs, Tile::RiverCurve, 3, (2, -1), 0, 0);
+    add_move(&mut mvs, Tile::RiverLake, 2, (3, -1), -1, -1);
+    match calculate(&mvs, 2, &expansions, &RuleSet::default(), false) {
+        Ok(res) => {
+            assert_eq!(res.player_points, vec![0, 0]);
+            // the field around the lake is connected to the meepled field
+            assert_eq!(res.meepleable_positions, Vec::<i32>::new());
+        }
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::RiverStraight, 0, (3, -2), -1, -1);
+    match calculate(&invalid_mvs, 2, &expansions, &RuleSet::default(), false) {
+        Ok(_) => panic!("the river can't be continued after the lake"),
+        Err(e) => assert_eq!(e.detail.msg, "the river has already ended"),
+    }
+
+    // the other tiles can be placed along the river
+    add_move(&mut mvs, Tile::CityCap, 0, (0, 1), -1, -1);
+    match calculate(&mvs, 2, &expansions, &RuleSet::default(), false) {
+        Ok(_) => {}
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+}
+
+#[test]
+fn calculate_test_for_rule_set() {
+    use super::rule_set::FieldEdition;
+
+    // two fields of player0 face the same completed city
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::CityCap, 2, (-1, 0), 0, 1);
+    add_move(&mut mvs, Tile::Straight, 1, (0, 1), 1, 0);
+
+    let status = calculate(&mvs, 2, &[], &RuleSet::default(), true);
+    match status {
+        Ok(res) => {
+            assert_eq!(res.player_points, vec![6, 0]);
+        }
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+
+    // with the 1st edition rules, the city is scored only once
+    let rule_set = RuleSet {
+        field_edition: FieldEdition::First,
+        ..Default::default()
+    };
+    let status = calculate(&mvs, 2, &[], &rule_set, true);
+    match status {
+        Ok(res) => {
+            assert_eq!(res.player_points, vec![4, 0]);
+            assert_eq!(
+                res.complete_events,
+                vec![CompleteEvent {
+                    feature: FieldFeature,
+                    meeple_ids: vec![0, 1],
+                    point: 4,
+                }]
+            );
+        }
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+
+    // only the first `meeples_per_player` meeples can be used
+    let rule_set = RuleSet {
+        meeples_per_player: 1,
+        ..Default::default()
+    };
+    let status = calculate(&mvs, 2, &[], &rule_set, false);
+    match status {
+        Ok(_) => {
+            panic!("meeple 1 must be invalid with one meeple per player");
+        }
+        Err(e) => {
+            assert_eq!(e.detail.msg, "meeple 1 doesn't belong to any player");
+        }
+    }
+    let status = calculate(&mvs[0..4].to_vec(), 2, &[], &rule_set, true);
+    match status {
+        Ok(res) => {
+            assert_eq!(res.player_points, vec![3, 0]);
+            assert_eq!(res.player_remaining_meeples[0], HashSet::from([0]));
+            assert_eq!(res.player_remaining_meeples[1], HashSet::from([7]));
+        }
+        Err(e) => {
+            panic!("Error: {:?}", e.detail);
+        }
+    }
+}
+
+#[test]
+fn calculate_test_for_extreme_layout() {
+    // every base game tile whose top and bottom are fields, placed in a column below the starting tile,
+    // so that the board extends 25 tiles in one direction
+    let mut column = vec![];
+    column.append(&mut vec![(Tile::Straight, 1); 8]);
+    column.append(&mut vec![(Tile::CityCap, 1); 5]);
+    column.append(&mut vec![(Tile::Monastery, 0); 4]);
+    column.append(&mut vec![(Tile::MonasteryWithRoad, 1); 2]);
+    column.append(&mut vec![(Tile::Connector, 0); 1]);
+    column.append(&mut vec![(Tile::ConnectorWithCOA, 0); 2]);
+    column.append(&mut vec![(Tile::VerticalSeparator, 1); 3]);
+
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    for (i, (tile, rot)) in column.iter().enumerate() {
+        add_move(&mut mvs, *tile, *rot, (i as i32 + 1, 0), -1, -1);
+    }
+    // a meeple on the road of the first straight, which stays open
+    if let Move::MMove(m) = &mut mvs[3] {
+        m.meeple_id = 0;
+        m.meeple_pos = 1;
+    }
+
+    let res = calculate(&mvs, 2, &[], &RuleSet::default(), true).unwrap();
+    assert_eq!(res.board.len(), 26);
+    assert_eq!(res.board[&(25, 0)].tile, Tile::VerticalSeparator);
+    assert_eq!(res.player_points[0], 1);
+
+    // tiles can still be placed beyond the end of the column
+    let state = GameState::from_moves(&mvs, 2, &[], &RuleSet::default()).unwrap();
+    assert!(state
+        .tileable_positions(Tile::Monastery)
+        .iter()
+        .any(|p| p.pos == (26, 0)));
+}
+
+#[test]
+fn calculate_test_for_move_errors() {
+    let reason_of =
+        |mvs: &Vec<Move>, rule_set: &RuleSet| match calculate(mvs, 2, &[], rule_set, false) {
+            Ok(_) => panic!("the moves must be invalid"),
+            Err(e) => e.detail.reason,
+        };
+
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::Monastery, 0, (0, 0), -1, -1);
+    assert_eq!(
+        reason_of(&invalid_mvs, &RuleSet::default()),
+        Some(MoveError::PositionOccupied { pos: (0, 0) })
+    );
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::Monastery, -1, (1, 0), -1, -1);
+    assert_eq!(
+        reason_of(&invalid_mvs, &RuleSet::default()),
+        Some(MoveError::InvalidRotation { rot: -1 })
+    );
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::Monastery, 4, (1, 0), -1, -1);
+    assert_eq!(
+        reason_of(&invalid_mvs, &RuleSet::default()),
+        Some(MoveError::InvalidRotation { rot: 4 })
+    );
+    // the rotations of the tileable positions are all valid
+    let state = GameState::from_moves(&mvs, 2, &[], &RuleSet::default()).unwrap();
+    for p in state.tileable_positions(Tile::Curve) {
+        let mut valid_mvs = mvs.clone();
+        add_move(&mut valid_mvs, Tile::Curve, p.rot, p.pos, -1, -1);
+        assert!(calculate(&valid_mvs, 2, &[], &RuleSet::default(), false).is_ok());
+    }
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::Monastery, 0, (5, 5), -1, -1);
+    assert_eq!(
+        reason_of(&invalid_mvs, &RuleSet::default()),
+        Some(MoveError::NoAdjacentTile { pos: (5, 5) })
+    );
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::CityCap, 0, (1, 0), -1, -1);
+    assert_eq!(
+        reason_of(&invalid_mvs, &RuleSet::default()),
+        Some(MoveError::EdgeMismatch {
+            dir: "top".to_string(),
+            adjacent_side: Field,
+            tile_side: City,
+        })
+    );
+
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::Monastery, 0, (1, 0), 0, 2);
+    assert_eq!(
+        reason_of(&invalid_mvs, &RuleSet::default()),
+        Some(MoveError::InvalidMeeplePosition { meeple_pos: 2 })
+    );
+
+    // the meeple goes on the starting tile instead of the monastery placed this turn
+    let mut invalid_mvs = mvs.clone();
+    add_move(&mut invalid_mvs, Tile::Monastery, 0, (1, 0), 0, 0);
+    if let Some(Move::MMove(m)) = invalid_mvs.last_mut() {
+        m.tile_pos = (0, 0);
+    }
+    assert_eq!(
+        reason_of(&invalid_mvs, &RuleSet::default()),
+        Some(MoveError::NotPlacedTile { tile_pos: (0, 0) })
+    );
+
+    let rule_set = RuleSet {
+        meeples_per_player: 1,
+        ..Default::default()
+    };
+    add_move(&mut mvs, Tile::Monastery, 0, (1, 0), 0, 0);
+    add_move(&mut mvs, Tile::Monastery, 0, (2, 0), 0, 0);
+    assert_eq!(
+        reason_of(&mvs, &rule_set),
+        Some(MoveError::NoMeeplesLeft { player: 0 })
+    );
+}
+
+#[test]
+fn validate_discard_test() {
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    match validate_discard(&mvs, 2, &[], &RuleSet::default(), Tile::Monastery) {
+        Ok(_) => panic!("a placeable tile must not be discarded"),
+        Err(e) => assert_eq!(
+            e.detail.reason,
+            Some(MoveError::TilePlaceable {
+                tile: Tile::Monastery
+            })
+        ),
+    }
+
+    // the third player's meeples are only known in games of three or more
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::Straight, 1, (0, 1), 14, 1);
+    assert!(validate_discard(&mvs, 3, &[], &RuleSet::default(), Tile::Monastery).is_err());
+    assert!(
+        calculate_tileable_positions(&mvs, 3, &[], &RuleSet::default(), Tile::Monastery)
+            .is_ok_and(|tps| !tps.is_empty())
+    );
+    assert!(
+        calculate_tileable_positions(&mvs, 2, &[], &RuleSet::default(), Tile::Monastery).is_err()
+    );
+
+    // no river tile can be placed after the lake
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::RiverSpring, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::RiverLake, 2, (1, 0), -1, -1);
+    assert!(validate_discard(
+        &mvs,
+        2,
+        &[Expansion::River],
+        &RuleSet::default(),
+        Tile::RiverStraight
+    )
+    .is_ok());
+}
+
+#[test]
+fn majority_players_test() {
+    assert_eq!(majority_players(&vec![], 3), Vec::<usize>::new());
+    assert_eq!(majority_players(&vec![0, 14, 15], 3), vec![2]);
+    assert_eq!(majority_players(&vec![0, 14], 3), vec![0, 2]);
+    assert_eq!(
+        majority_players(&vec![1, 8, 15, 22, 29], 5),
+        vec![0, 1, 2, 3, 4]
+    );
+    assert_eq!(majority_players(&vec![7, 8, 28], 5), vec![1]);
+    // a big meeple counts as two meeples
+    assert_eq!(majority_players(&vec![0, 1001], 2), vec![1]);
+    assert_eq!(majority_players(&vec![0, 1, 1001], 2), vec![0, 1]);
+}
@@ -1,12 +1,20 @@
-use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
 
-use super::calculate::calculate;
-use super::calculate::TileItem;
 use super::evaluate::evaluate;
+use super::expectimax::{best_candidate, solve_endgame};
+use super::game_state::GameState;
 use super::mov::{MeepleMove, Move, TileMove};
-use super::solver::solve;
-use super::solver::SolveResult;
-use super::tile::Tile;
+use super::rule_set::RuleSet;
+use super::tile;
+use super::tile::{Expansion, Tile};
+
+// the end of the game is solved exactly (see `expectimax`) from this many tiles left. 2 tiles take a few
+// seconds on one core, and 3 don't finish in a minute
+const ENDGAME_TILES: usize = 2;
+// the solver gives up after this, and the move is then calculated as usual
+const ENDGAME_TIME_LIMIT: Duration = Duration::from_secs(10);
 
 pub fn calculate_next_move(
     moves: &Vec<Move>,
@@ -15,46 +23,33 @@ pub fn calculate_next_move(
     player1_id: i32,
     player_id: i32,
     next_tile: Tile,
+    expansions: &[Expansion],
+    rule_set: &RuleSet,
 ) -> Option<(TileMove, MeepleMove)> {
-    let mut mvs = moves.clone();
+    let mvs = moves.clone();
+    let player_ids = [player0_id, player1_id];
 
-    let mut tile_count = 0;
-    for mv in &mvs {
-        match mv {
-            Move::TMove(_) | Move::DMove(_) => {
-                tile_count += 1;
-            }
-            _ => {}
-        }
-    }
-    if tile_count >= 72 - 2 {
-        let ((tm, mm), solve_result) = solve(
-            &mvs, game_id, player0_id, player1_id, next_tile, true, false,
-        );
-        if solve_result != SolveResult::AlwaysLose {
-            return Some((tm, mm));
-        }
-        // if the above doesn't return results, then lose 100% (or `solve` failed to calculate the results fast enough), so just play as usual
+    // when the end isn't solved in time, play as usual
+    if let Some(mv) = endgame_move(
+        &mvs,
+        game_id,
+        &player_ids,
+        player_id,
+        next_tile,
+        expansions,
+        rule_set,
+    ) {
+        return Some(mv);
     }
 
-    let mut tile = TileItem {
-        id: next_tile.to_id(),
-        tile: next_tile,
-        rot: 0,
-        feature_starting_id: 0,
-        meeple_id: None,
-        meeple_pos: None,
-    };
-
     let tile_move_ord = mvs.last().unwrap().ord() + 1;
     let meeple_move_ord = tile_move_ord + 1;
 
-    let board = match calculate(&mvs, false) {
-        Ok(s) => s.board,
-        Err(_) => HashMap::new(),
+    let state = match GameState::from_moves(&mvs, player_ids.len(), expansions, rule_set) {
+        Ok(s) => s,
+        Err(_) => return None,
     };
 
-    let mut checked = HashMap::new();
     let mut max_val = -100000;
     let mut tile_move = TileMove {
         id: -1,
@@ -75,181 +70,181 @@ pub fn calculate_next_move(
         meeple_pos: -1,
     };
 
-    // let mut test = vec![];
-
-    let mut updated = false;
-    for pos in board.keys() {
-        match checked.get(pos) {
-            Some(_) => {
-                continue;
-            }
-            None => {}
-        }
-        checked.insert(pos.clone(), true);
-
-        let y = pos.0;
-        let x = pos.1;
-        let dy = [0, -1, 0, 1];
-        let dx = [1, 0, -1, 0];
-        for i in 0..4 {
-            let ny = y + dy[i];
-            let nx = x + dx[i];
-            match board.get(&(ny, nx)) {
-                Some(_) => {
-                    continue;
-                }
-                None => {}
-            }
-            match checked.get(&(ny, nx)) {
-                Some(_) => {
-                    continue;
-                }
-                None => {}
-            }
-            checked.insert((ny, nx), true);
-
-            for rot in vec![1, 2, 3, 4] {
-                tile.rotate();
-
-                match board.get(&(ny - 1, nx)) {
-                    Some(t) => {
-                        if t.bottom() != tile.top() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(ny + 1, nx)) {
-                    Some(t) => {
-                        if t.top() != tile.bottom() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(ny, nx - 1)) {
-                    Some(t) => {
-                        if t.right() != tile.left() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
-                match board.get(&(ny, nx + 1)) {
-                    Some(t) => {
-                        if t.left() != tile.right() {
-                            continue;
-                        }
-                    }
-                    None => {}
-                }
+    // each position is evaluated on a copy of the state of its own thread. the results keep the order of
+    // `tileable_positions`, so the first of the best moves is chosen as when they were evaluated one by one
+    let candidates: Vec<Option<(i32, TileMove, MeepleMove)>> = state
+        .tileable_positions(next_tile)
+        .par_iter()
+        .map_init(
+            || state.clone(),
+            |state, tileable_position| {
                 let tmove = TileMove {
                     id: -1,
                     ord: tile_move_ord,
                     game_id,
                     player_id,
                     tile: next_tile,
-                    rot: rot % 4,
-                    pos: (ny, nx),
+                    rot: tileable_position.rot,
+                    pos: tileable_position.pos,
                 };
+                best_move_at(state, tmove, meeple_move_ord, player0_id)
+            },
+        )
+        .collect();
 
-                mvs.push(Move::TMove(tmove.clone()));
+    let mut updated = false;
+    for (val, tmove, mmove) in candidates.into_iter().flatten() {
+        if val > max_val {
+            max_val = val;
+            tile_move = tmove;
+            meeple_move = mmove;
+            updated = true;
+        }
+    }
 
-                let s = match calculate(&mvs, false) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        panic!("{:?}", e.detail.msg);
-                    }
-                };
+    if !updated {
+        return None;
+    }
 
-                let remaining_meeples = if player_id == player0_id {
-                    s.player0_remaining_meeples
-                } else {
-                    s.player1_remaining_meeples
-                };
-                let mut meepleable_positions = s.meepleable_positions;
-                meepleable_positions.push(-1);
-                for mpos in &meepleable_positions {
-                    let mut meeple_id = -1;
-                    if *mpos != -1 {
-                        if remaining_meeples.len() == 0 {
-                            continue;
-                        }
-                        meeple_id = remaining_meeples.iter().next().unwrap().clone();
-                    }
-
-                    let mmove = MeepleMove {
-                        id: -1,
-                        ord: meeple_move_ord,
-                        game_id,
-                        player_id,
-                        meeple_id,
-                        tile_pos: (ny, nx),
-                        meeple_pos: *mpos,
-                    };
-                    mvs.push(Move::MMove(mmove.clone()));
-
-                    // let debug = tmove.pos == (0, 3) && tmove.rot == 3 && mmove.meeple_pos == 2;
-                    // let debug = tmove.pos == (0, 4) && tmove.rot == 1 && mmove.meeple_pos == 4;
-                    let debug = false;
-
-                    let (res0, res1) = evaluate(&mvs, debug);
-
-                    let val = if player_id == player0_id {
-                        res0 - res1
-                    } else {
-                        res1 - res0
-                    };
-
-                    if val > max_val {
-                        max_val = val;
-                        tile_move = tmove.clone();
-                        meeple_move = mmove.clone();
-                        updated = true;
-                    }
-
-                    mvs.pop();
-                }
-                mvs.pop();
-            }
+    Some((tile_move, meeple_move))
+}
+
+// the best move found by solving the end of the game exactly, when few enough tiles are left and it's
+// solved in time
+pub fn endgame_move(
+    moves: &Vec<Move>,
+    game_id: Option<i32>,
+    player_ids: &[i32],
+    player_id: i32,
+    next_tile: Tile,
+    expansions: &[Expansion],
+    rule_set: &RuleSet,
+) -> Option<(TileMove, MeepleMove)> {
+    // the tiles still to be placed, the drawn one included
+    let mut out_tiles = vec![next_tile];
+    for mv in moves {
+        match mv {
+            Move::TMove(tm) => out_tiles.push(tm.tile),
+            Move::DMove(dm) => out_tiles.push(dm.tile),
+            _ => {}
         }
     }
+    let tiles_left = tile::remaining_tiles(out_tiles, expansions).len() + 1;
+    if tiles_left > ENDGAME_TILES {
+        return None;
+    }
+    let candidates = solve_endgame(
+        moves,
+        game_id,
+        player_ids,
+        player_id,
+        next_tile,
+        expansions,
+        rule_set,
+        Some(Instant::now() + ENDGAME_TIME_LIMIT),
+    )?;
+    best_candidate(&candidates).map(|c| (c.tile_move.clone(), c.meeple_move.clone()))
+}
 
-    if !updated {
+// the best meeple move after `tmove` and its value, the first of them on a tie.
+// `None` when the tile move or every meeple move fails to apply
+fn best_move_at(
+    state: &mut GameState,
+    tmove: TileMove,
+    meeple_move_ord: i32,
+    player0_id: i32,
+) -> Option<(i32, TileMove, MeepleMove)> {
+    let player_id = tmove.player_id;
+    if state.apply_tile_move(&tmove).is_err() {
         return None;
     }
 
-    /*
-    test.sort_by(|a, b| a.4.cmp(&b.4));
-    for t in test {
-        println!(
-            "m = {:?}, {:?}, res0 = {:?}, res1 = {:?}, val = {:?}",
-            t.0, t.1, t.2, t.3, t.4
-        );
+    let remaining_meeples = if player_id == player0_id {
+        state.status().player_remaining_meeples[0].clone()
+    } else {
+        state.status().player_remaining_meeples[1].clone()
+    };
+    let mut meepleable_positions = state.status().meepleable_positions.clone();
+    meepleable_positions.push(-1);
+    let mut best: Option<(i32, TileMove, MeepleMove)> = None;
+    for mpos in &meepleable_positions {
+        let mut meeple_id = -1;
+        if *mpos != -1 {
+            // the smallest id, so that the same move is made on every run
+            match remaining_meeples.iter().min() {
+                Some(id) => meeple_id = *id,
+                None => continue,
+            }
+        }
+
+        let mmove = MeepleMove {
+            id: -1,
+            ord: meeple_move_ord,
+            game_id: tmove.game_id,
+            player_id,
+            meeple_id,
+            tile_pos: tmove.pos,
+            meeple_pos: *mpos,
+        };
+        if state.apply_meeple_move(&mmove).is_err() {
+            continue;
+        }
+
+        let (res0, res1) = evaluate(state, false);
+
+        let val = if player_id == player0_id {
+            res0 - res1
+        } else {
+            res1 - res0
+        };
+
+        match &best {
+            Some((v, _, _)) if *v >= val => {}
+            _ => best = Some((val, tmove.clone(), mmove)),
+        }
+
+        state.undo(); // undo meeple move
     }
-    println!("");
-    */
+    state.undo(); // undo tile move
 
-    Some((tile_move, meeple_move))
+    best
 }
 
 #[test]
 fn calculate_next_move_invade_test0() {
     let src_mvs =
-        super::decoder::decode_from_file_path("src/data/365601037/moves.json".to_string());
+        super::decoder::decode_from_file_path("src/data/365601037/moves.json".to_string()).unwrap();
     let mvs = src_mvs[0..10].to_vec();
 
     // 365601037/0.png
-    let (tile_move, meeple_move) =
-        calculate_next_move(&mvs, None, 0, 1, 1, Tile::Triangle).unwrap();
+    let (tile_move, meeple_move) = calculate_next_move(
+        &mvs,
+        None,
+        0,
+        1,
+        1,
+        Tile::Triangle,
+        &[],
+        &RuleSet::default(),
+    )
+    .unwrap();
     assert_eq!(tile_move.pos, (-1, 1));
     assert_eq!(tile_move.rot, 1);
     assert_eq!(meeple_move.meeple_pos, 0);
 
     // 365601037/1.png
     let mvs = src_mvs[0..22].to_vec();
-    let (tile_move, meeple_move) =
-        calculate_next_move(&mvs, None, 0, 1, 1, Tile::TriangleWithCOA).unwrap();
+    let (tile_move, meeple_move) = calculate_next_move(
+        &mvs,
+        None,
+        0,
+        1,
+        1,
+        Tile::TriangleWithCOA,
+        &[],
+        &RuleSet::default(),
+    )
+    .unwrap();
     assert_eq!(tile_move.pos, (-2, 2));
     assert_eq!(tile_move.rot, 3);
     assert_eq!(meeple_move.meeple_pos, -1);
@@ -259,13 +254,89 @@ fn calculate_next_move_invade_test0() {
     // 365601037/2.png
     let mvs = src_mvs[0..34].to_vec();
     let (tile_move, meeple_move) =
-        calculate_next_move(&mvs, -1, 0, 1, 1, Tile::ConnectorWithCOA).unwrap();
+        calculate_next_move(&mvs, -1, 0, 1, 1, Tile::ConnectorWithCOA, &[], &RuleSet::default()).unwrap();
     assert_eq!(tile_move.pos, (-3, 2));
     assert_eq!(tile_move.rot % 2, 0);
     assert_eq!(meeple_move.meeple_pos, -1);
     */
 }
 
+#[test]
+fn calculate_next_move_parallel_test() {
+    let mut mvs = vec![
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 0,
+            game_id: None,
+            player_id: 0,
+            tile: Tile::StartingTile,
+            rot: 0,
+            pos: (0, 0),
+        }),
+        Move::MMove(MeepleMove {
+            id: -1,
+            ord: 1,
+            game_id: None,
+            player_id: 0,
+            meeple_id: -1,
+            tile_pos: (0, 0),
+            meeple_pos: -1,
+        }),
+    ];
+    for (player_id, tile) in [(1, Tile::CityCapWithCrossroad), (0, Tile::Triangle)] {
+        let (tm, mm) =
+            calculate_next_move(&mvs, None, 0, 1, player_id, tile, &[], &RuleSet::default())
+                .unwrap();
+
+        // the first of the best moves, evaluating the positions one by one
+        let mut state = GameState::from_moves(&mvs, 2, &[], &RuleSet::default()).unwrap();
+        let mut best: Option<(i32, TileMove, MeepleMove)> = None;
+        for tp in state.tileable_positions(tile) {
+            let tmove = TileMove {
+                id: -1,
+                ord: tm.ord,
+                game_id: None,
+                player_id,
+                tile,
+                rot: tp.rot,
+                pos: tp.pos,
+            };
+            if let Some(c) = best_move_at(&mut state, tmove, mm.ord, 0) {
+                if best.is_none() || c.0 > best.as_ref().unwrap().0 {
+                    best = Some(c);
+                }
+            }
+        }
+        let (_, best_tm, best_mm) = best.unwrap();
+        assert_eq!((tm.pos, tm.rot), (best_tm.pos, best_tm.rot));
+        assert_eq!(mm, best_mm);
+
+        mvs.push(Move::TMove(tm));
+        mvs.push(Move::MMove(mm));
+    }
+}
+
+// slow in debug builds, so run it with `cargo test --release -- --ignored endgame_time_limit_test`
+#[test]
+#[ignore]
+fn endgame_time_limit_test() {
+    for seed in 0..4 {
+        let (mvs, t) = super::expectimax::late_game(seed, ENDGAME_TILES);
+        let player_id = (mvs.len() as i32 / 2 + 1) % 2;
+        assert!(solve_endgame(
+            &mvs,
+            None,
+            &[0, 1],
+            player_id,
+            t,
+            &[],
+            &RuleSet::default(),
+            Some(Instant::now() + ENDGAME_TIME_LIMIT),
+        )
+        .is_some());
+    }
+}
+
 /*
 #[test]
 fn calculate_next_move_test() {
@@ -289,7 +360,7 @@ fn calculate_next_move_test() {
     let mut mvs = super::database::list_moves(&db, game_id, None).unwrap();
     mvs = mvs[0..98].to_vec();
 
-    let (tile_move, meeple_move) = calculate_next_move(&mvs, None, 0, 1, 1, Tile::Curve).unwrap();
+    let (tile_move, meeple_move) = calculate_next_move(&mvs, None, 0, 1, 1, Tile::Curve, &[], &RuleSet::default()).unwrap();
 
     println!("tile_move = {:?}", tile_move);
     println!("meeple_move = {:?}", meeple_move);
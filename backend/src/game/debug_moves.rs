@@ -1,142 +1,80 @@
-use std::collections::HashMap;
-
 use crate::game::evaluate::evaluate;
 
 use super::{
-    calculate::{calculate, TileItem},
+    game_state::GameState,
     mov::{MeepleMove, Move, TileMove},
+    rule_set::RuleSet,
     tile::Tile,
 };
 
 #[allow(dead_code)]
 pub fn list_evaluate_results(moves: &Vec<Move>, next_tile: Tile) {
-    let mut mvs = moves.clone();
-
-    let mut tile = TileItem {
-        id: next_tile.to_id(),
-        tile: next_tile,
-        rot: 0,
-        feature_starting_id: 0,
-        meeple_id: None,
-        meeple_pos: None,
-    };
-
-    let tile_move_ord = mvs.last().unwrap().ord() + 1;
+    let tile_move_ord = moves.last().unwrap().ord() + 1;
     let meeple_move_ord = tile_move_ord + 1;
 
-    let board = match calculate(&mvs, false) {
-        Ok(s) => s.board,
-        Err(_) => HashMap::new(),
+    let mut state = match GameState::from_moves(moves, 2, &[], &RuleSet::default()) {
+        Ok(s) => s,
+        Err(e) => {
+            panic!("{:?}", e.detail.msg);
+        }
     };
 
-    let mut checked = HashMap::new();
-
     let mut results = vec![];
 
-    for pos in board.keys() {
-        match checked.get(pos) {
-            Some(_) => {
-                continue;
-            }
-            None => {}
-        }
-        checked.insert(pos.clone(), true);
+    for tileable_position in state.tileable_positions(next_tile) {
+        let tmove = TileMove {
+            id: -1,
+            ord: tile_move_ord,
+            game_id: None,
+            player_id: -1,
+            tile: next_tile,
+            rot: tileable_position.rot,
+            pos: tileable_position.pos,
+        };
 
-        let y = pos.0;
-        let x = pos.1;
-        let dy = [0, -1, 0, 1];
-        let dx = [1, 0, -1, 0];
-        for dir0 in 0..4 {
-            let ny = y + dy[dir0];
-            let nx = x + dx[dir0];
-            match board.get(&(ny, nx)) {
-                Some(_) => {
-                    continue;
-                }
-                None => {}
+        match state.apply_tile_move(&tmove) {
+            Ok(_) => {}
+            Err(e) => {
+                panic!("{:?}", e.detail.msg);
             }
-            match checked.get(&(ny, nx)) {
-                Some(_) => {
+        };
+
+        let remaining_meeples = state.status().player_remaining_meeples[1].clone();
+        let mut meepleable_positions = state.status().meepleable_positions.clone();
+        meepleable_positions.push(-1);
+        for mpos in &meepleable_positions {
+            let mut meeple_id = -1;
+            if *mpos != -1 {
+                if remaining_meeples.len() == 0 {
                     continue;
                 }
-                None => {}
+                meeple_id = remaining_meeples.iter().next().unwrap().clone();
             }
-            checked.insert((ny, nx), true);
-
-            for rot in vec![1, 2, 3, 4] {
-                tile.rotate();
-                let mut fit = true;
-                for dir1 in 0..dy.len() {
-                    let nny = ny + dy[dir1];
-                    let nnx = nx + dx[dir1];
-                    let op_dir = (dir1 + 2) % 4;
-                    match board.get(&(nny, nnx)) {
-                        Some(t) => {
-                            if t.side_by_dir(op_dir) != tile.side_by_dir(dir1) {
-                                fit = false;
-                                break;
-                            }
-                        }
-                        None => {}
-                    }
-                }
 
-                if !fit {
-                    continue;
+            let mmove = MeepleMove {
+                id: -1,
+                ord: meeple_move_ord,
+                game_id: None,
+                player_id: -1,
+                meeple_id,
+                tile_pos: tmove.pos,
+                meeple_pos: *mpos,
+            };
+            match state.apply_meeple_move(&mmove) {
+                Ok(_) => {}
+                Err(e) => {
+                    panic!("{:?}", e.detail.msg);
                 }
+            };
 
-                let tmove = TileMove {
-                    id: -1,
-                    ord: tile_move_ord,
-                    game_id: None,
-                    player_id: -1,
-                    tile: next_tile,
-                    rot: rot % 4,
-                    pos: (ny, nx),
-                };
-
-                mvs.push(Move::TMove(tmove.clone()));
-
-                let s = match calculate(&mvs, false) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        panic!("{:?}", e.detail.msg);
-                    }
-                };
-
-                let remaining_meeples = s.player1_remaining_meeples;
-                let mut meepleable_positions = s.meepleable_positions;
-                meepleable_positions.push(-1);
-                for mpos in &meepleable_positions {
-                    let mut meeple_id = -1;
-                    if *mpos != -1 {
-                        if remaining_meeples.len() == 0 {
-                            continue;
-                        }
-                        meeple_id = remaining_meeples.iter().next().unwrap().clone();
-                    }
+            let debug = false; /* tmove.pos == (-5, 2) && tmove.rot == 2 && mmove.meeple_pos == 1; */
+            let (res0, res1) = evaluate(&mut state, debug);
 
-                    let mmove = MeepleMove {
-                        id: -1,
-                        ord: meeple_move_ord,
-                        game_id: None,
-                        player_id: -1,
-                        meeple_id,
-                        tile_pos: (ny, nx),
-                        meeple_pos: *mpos,
-                    };
-                    mvs.push(Move::MMove(mmove.clone()));
+            results.push((tmove.clone(), mmove, res0, res1, res1 - res0));
 
-                    let debug = false; /* tmove.pos == (-5, 2) && tmove.rot == 2 && mmove.meeple_pos == 1; */
-                    let (res0, res1) = evaluate(&mvs, debug);
-
-                    results.push((tmove.clone(), mmove, res0, res1, res1 - res0));
-
-                    mvs.pop();
-                }
-                mvs.pop();
-            }
+            state.undo();
         }
+        state.undo();
     }
 
     println!("Scores for each move");
@@ -159,17 +97,15 @@ struct CompareMove {
 
 #[allow(dead_code)]
 fn compare_evaluate_results(moves: &Vec<Move>, next_tile: Tile, compare_moves: &Vec<CompareMove>) {
-    let mut mvs = moves.clone();
-
-    let tile_move_ord = mvs.last().unwrap().ord() + 1;
+    let tile_move_ord = moves.last().unwrap().ord() + 1;
     let meeple_move_ord = tile_move_ord + 1;
-    let s = match calculate(&mvs, false) {
+    let mut state = match GameState::from_moves(moves, 2, &[], &RuleSet::default()) {
         Ok(s) => s,
         Err(e) => {
             panic!("{:?}", e.detail.msg);
         }
     };
-    let remaining_meeples = s.player1_remaining_meeples;
+    let remaining_meeples = state.status().player_remaining_meeples[1].clone();
 
     for compare_move in compare_moves {
         println!("move = {:?}", compare_move);
@@ -179,7 +115,7 @@ fn compare_evaluate_results(moves: &Vec<Move>, next_tile: Tile, compare_moves: &
             game_id: None,
             player_id: -1,
             tile: next_tile,
-            rot: compare_move.rot % 4,
+            rot: compare_move.rot,
             pos: compare_move.pos,
         };
 
@@ -200,13 +136,23 @@ fn compare_evaluate_results(moves: &Vec<Move>, next_tile: Tile, compare_moves: &
             tile_pos: compare_move.pos,
         };
 
-        mvs.push(Move::TMove(tmove.clone()));
-        mvs.push(Move::MMove(mmove.clone()));
+        match state.apply(&Move::TMove(tmove.clone())) {
+            Ok(_) => {}
+            Err(e) => {
+                panic!("{:?}", e.detail.msg);
+            }
+        };
+        match state.apply(&Move::MMove(mmove.clone())) {
+            Ok(_) => {}
+            Err(e) => {
+                panic!("{:?}", e.detail.msg);
+            }
+        };
 
-        let (_, _) = evaluate(&mvs, true);
+        let (_, _) = evaluate(&mut state, true);
 
-        mvs.pop();
-        mvs.pop();
+        state.undo();
+        state.undo();
     }
 }
 
@@ -1,26 +1,50 @@
 use serde_json::Value;
-use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
 
-use super::calculate::calculate;
+use super::game_state::GameState;
 use super::mov::{DiscardMove, MeepleMove, Move, TileMove};
-use super::tile::to_tile;
+use super::rule_set::RuleSet;
 use super::tile::Tile;
+use super::tile::{catalogue, to_tile};
+use crate::error::{DecodeError, MoveError};
 
-pub fn decode(data: String) -> Vec<Move> {
-    let v: Value = serde_json::from_str(&data).unwrap();
+// packets of BGA's framework and of scores, which don't change the board. any other packet which isn't a move is
+// rejected, since it may be a move of an expansion which would otherwise be left out of the replay
+const SKIPPED_PACKETS: &[&str] = &[
+    "gameStateChange",
+    "gameStateChangePrivateArg",
+    "gameStateMultipleActiveUpdate",
+    "newActivePlayer",
+    "newScores",
+    "playerstatus",
+    "simpleNode",
+    "simpleNote",
+    "tableInfosChanged",
+    "undoRestorePoint",
+    "updateReflexionTime",
+    "wakeupPlayers",
+    "yourturnack",
+];
+
+pub fn decode(data: String) -> Result<Vec<Move>, DecodeError> {
+    let v: Value = match serde_json::from_str(&data) {
+        Ok(v) => v,
+        Err(e) => return Err(DecodeError::InvalidJson { msg: e.to_string() }),
+    };
 
     let mut ord = 0;
     let mut player_id = 0;
     let mut tile_id = -1;
 
-    let mut remaining_meeples = vec![
-        HashSet::from([0, 1, 2, 3, 4, 5, 6]),     // player0
-        HashSet::from([7, 8, 9, 10, 11, 12, 13]), // player1
-    ];
+    // games on BGA are always two-player games.
+    // moves are applied as they are decoded, which tells which meeples are left (which can't be known easily from the data)
+    let mut state = GameState::new(2, &[], &RuleSet::default());
+    let mut moves = vec![];
 
-    let mut moves = vec![
+    push(
+        &mut state,
+        &mut moves,
         Move::TMove(TileMove {
             id: -1,
             ord,
@@ -30,6 +54,10 @@ pub fn decode(data: String) -> Vec<Move> {
             rot: 0,
             pos: (0, 0),
         }),
+    )?;
+    push(
+        &mut state,
+        &mut moves,
         Move::MMove(MeepleMove {
             id: -1,
             ord: ord + 1,
@@ -39,7 +67,7 @@ pub fn decode(data: String) -> Vec<Move> {
             tile_pos: (0, 0),
             meeple_pos: -1,
         }),
-    ];
+    )?;
     ord += 2;
     player_id = 1 - player_id;
 
@@ -48,55 +76,49 @@ pub fn decode(data: String) -> Vec<Move> {
             if let Some(ds) = packet["data"].as_array() {
                 for d in ds {
                     let args = &d["args"];
-                    match d["type"].as_str() {
-                        Some("playTile") | Some("cantPlay") => {
+                    let packet_type = match d["type"].as_str() {
+                        Some(t) => t,
+                        None => continue,
+                    };
+                    match packet_type {
+                        "playTile" | "cantPlay" => {
                             // create empty meeple move if there is no playPartisan packet before
                             let prev_move = moves.last().unwrap().clone();
                             match prev_move {
                                 Move::TMove(t) => {
-                                    moves.push(Move::MMove(MeepleMove {
-                                        id: -1,
-                                        ord,
-                                        game_id: None,
-                                        player_id,
-                                        meeple_id: -1,
-                                        tile_pos: t.pos,
-                                        meeple_pos: -1,
-                                    }));
+                                    push(
+                                        &mut state,
+                                        &mut moves,
+                                        Move::MMove(MeepleMove {
+                                            id: -1,
+                                            ord,
+                                            game_id: None,
+                                            player_id,
+                                            meeple_id: -1,
+                                            tile_pos: t.pos,
+                                            meeple_pos: -1,
+                                        }),
+                                    )?;
                                     ord += 1;
                                     player_id = 1 - player_id;
                                 }
                                 _ => {}
                             }
 
-                            // check which meeples are retrieved (which can't be known easily from the data)
-                            let status = calculate(&moves, false);
-                            match status {
-                                Ok(res) => {
-                                    for e in &res.complete_events {
-                                        for meeple_id in &e.meeple_ids {
-                                            if *meeple_id < 7 {
-                                                remaining_meeples[0].insert(*meeple_id);
-                                            } else {
-                                                remaining_meeples[1].insert(*meeple_id);
-                                            }
-                                        }
-                                    }
+                            if packet_type == "playTile" {
+                                let tile_type = parse_arg(args, packet_type, "type")?;
+                                let y = parse_arg(args, packet_type, "y")?;
+                                let x = parse_arg(args, packet_type, "x")?;
+                                let rot = parse_arg(args, packet_type, "ori")? - 1;
+                                tile_id = convert_from_bga_tile_type_to_tile_id(tile_type);
+                                if tile_id == -1 {
+                                    return Err(unknown_tile_type(packet_type, tile_type));
                                 }
-                                Err(e) => {
-                                    panic!("Error: {:?}", e.detail);
-                                }
-                            }
 
-                            match d["type"].as_str() {
-                                Some("playTile") => {
-                                    let tile_type = parse_number(&args["type"]);
-                                    let y = parse_number(&args["y"]);
-                                    let x = parse_number(&args["x"]);
-                                    let rot = parse_number(&args["ori"]) - 1;
-                                    tile_id = convert_from_bga_tile_type_to_tile_id(tile_type);
-
-                                    moves.push(Move::TMove(TileMove {
+                                push(
+                                    &mut state,
+                                    &mut moves,
+                                    Move::TMove(TileMove {
                                         id: -1,
                                         ord,
                                         game_id: None,
@@ -104,58 +126,100 @@ pub fn decode(data: String) -> Vec<Move> {
                                         tile: to_tile(tile_id),
                                         rot,
                                         pos: (y, x),
-                                    }));
-
-                                    ord += 1;
+                                    }),
+                                )?;
+                            } else {
+                                let bga_tile_id = parse_arg(args, packet_type, "tile_id")?;
+                                if bga_tile_id < 1 {
+                                    return Err(DecodeError::UnsupportedPacket {
+                                        packet_type: packet_type.to_string(),
+                                        arg: Some("tile_id".to_string()),
+                                    });
+                                }
+                                let tile_type =
+                                    convert_from_bga_tile_id_to_bga_tile_type(bga_tile_id);
+                                if tile_type == -1 {
+                                    return Err(DecodeError::ExpansionContent {
+                                        packet_type: packet_type.to_string(),
+                                        content: format!("tile {}", bga_tile_id),
+                                    });
                                 }
-                                Some("cantPlay") => {
-                                    let bga_tile_id = parse_number(&args["tile_id"]);
-                                    let tile_id = convert_from_bga_tile_type_to_tile_id(
-                                        convert_from_bga_tile_id_to_bga_tile_type(bga_tile_id),
-                                    );
+                                let tile_id = convert_from_bga_tile_type_to_tile_id(tile_type);
 
-                                    moves.push(Move::DMove(DiscardMove {
+                                push(
+                                    &mut state,
+                                    &mut moves,
+                                    Move::DMove(DiscardMove {
                                         id: -1,
                                         ord,
                                         tile: to_tile(tile_id),
                                         game_id: None,
                                         player_id,
-                                    }));
-
-                                    ord += 1;
-                                }
-                                _ => {}
+                                    }),
+                                )?;
                             }
+
+                            ord += 1;
                         }
-                        Some("playPartisan") => {
-                            let y = parse_number(&args["y"]);
-                            let x = parse_number(&args["x"]);
-                            let pos = parse_number(&args["pos"]);
+                        "playPartisan" => {
+                            let y = parse_arg(args, packet_type, "y")?;
+                            let x = parse_arg(args, packet_type, "x")?;
+                            let pos = parse_arg(args, packet_type, "pos")?;
 
-                            if remaining_meeples[player_id as usize].len() == 0 {
-                                panic!("decode failed: no meeple is available");
+                            if !matches!(moves.last(), Some(Move::TMove(_))) {
+                                return Err(DecodeError::OutOfOrderMove {
+                                    ord,
+                                    cause: MoveError::MeepleMoveWithoutTileMove.msg(),
+                                });
                             }
-                            let meeple_id = remaining_meeples[player_id as usize]
+                            let meeple_pos = convert_pos(tile_id, pos);
+                            if meeple_pos == -1 {
+                                return Err(DecodeError::ExpansionContent {
+                                    packet_type: packet_type.to_string(),
+                                    content: format!("meeple position {}", pos),
+                                });
+                            }
+                            let meeple_id = match state.status().player_remaining_meeples
+                                [player_id as usize]
                                 .iter()
-                                .next()
-                                .unwrap()
-                                .clone();
-                            remaining_meeples[player_id as usize].remove(&meeple_id);
+                                .min()
+                            {
+                                Some(m) => *m,
+                                None => {
+                                    return Err(DecodeError::OutOfOrderMove {
+                                        ord,
+                                        cause: MoveError::NoMeeplesLeft {
+                                            player: player_id as usize,
+                                        }
+                                        .msg(),
+                                    })
+                                }
+                            };
 
-                            moves.push(Move::MMove(MeepleMove {
-                                id: -1,
-                                ord,
-                                game_id: None,
-                                player_id,
-                                meeple_id,
-                                tile_pos: (y, x),
-                                meeple_pos: convert_pos(tile_id, pos),
-                            }));
+                            push(
+                                &mut state,
+                                &mut moves,
+                                Move::MMove(MeepleMove {
+                                    id: -1,
+                                    ord,
+                                    game_id: None,
+                                    player_id,
+                                    meeple_id,
+                                    tile_pos: (y, x),
+                                    meeple_pos,
+                                }),
+                            )?;
 
                             ord += 1;
                             player_id = 1 - player_id;
                         }
-                        _ => {}
+                        t if SKIPPED_PACKETS.contains(&t) => {}
+                        _ => {
+                            return Err(DecodeError::UnsupportedPacket {
+                                packet_type: packet_type.to_string(),
+                                arg: None,
+                            })
+                        }
                     }
                 }
             }
@@ -163,269 +227,235 @@ pub fn decode(data: String) -> Vec<Move> {
     }
 
     // add empty meeple move if there is no playPartisan packet in the end
-    match moves.last().unwrap() {
+    match moves.last().unwrap().clone() {
         Move::TMove(t) => {
-            moves.push(Move::MMove(MeepleMove {
-                id: -1,
-                ord,
-                game_id: None,
-                player_id,
-                meeple_id: -1,
-                tile_pos: t.pos,
-                meeple_pos: -1,
-            }));
+            push(
+                &mut state,
+                &mut moves,
+                Move::MMove(MeepleMove {
+                    id: -1,
+                    ord,
+                    game_id: None,
+                    player_id,
+                    meeple_id: -1,
+                    tile_pos: t.pos,
+                    meeple_pos: -1,
+                }),
+            )?;
         }
         _ => {}
     }
 
-    moves
+    Ok(moves)
+}
+
+// applies `mv` to `state` so that a move which can't be made is reported where it is in the replay
+fn push(state: &mut GameState, moves: &mut Vec<Move>, mv: Move) -> Result<(), DecodeError> {
+    if let Err(e) = state.apply(&mv) {
+        return Err(DecodeError::OutOfOrderMove {
+            ord: mv.ord(),
+            cause: e.detail.msg,
+        });
+    }
+    moves.push(mv);
+    Ok(())
+}
+
+// tile types after the base game's are of expansions
+fn unknown_tile_type(packet_type: &str, bga_type: i32) -> DecodeError {
+    let max_base_type = catalogue()
+        .iter()
+        .filter_map(|d| d.bga_type)
+        .max()
+        .unwrap_or(0);
+    if bga_type > max_base_type {
+        DecodeError::ExpansionContent {
+            packet_type: packet_type.to_string(),
+            content: format!("tile type {}", bga_type),
+        }
+    } else {
+        DecodeError::UnknownTileType { bga_type }
+    }
 }
 
 #[allow(dead_code)]
-pub fn decode_from_file_path(file_path: String) -> Vec<Move> {
-    let mut file = File::open(file_path).unwrap();
+pub fn decode_from_file_path(file_path: String) -> Result<Vec<Move>, DecodeError> {
+    let unreadable = |e: std::io::Error| DecodeError::Unreadable { msg: e.to_string() };
+    let mut file = File::open(file_path).map_err(unreadable)?;
     let mut data = String::new();
-    file.read_to_string(&mut data).unwrap();
+    file.read_to_string(&mut data).map_err(unreadable)?;
 
     decode(data)
 }
 
 fn convert_from_bga_tile_type_to_tile_id(typ: i32) -> i32 {
-    match typ {
-        15 => 0,
-        20 => 1,
-        21 => 2,
-        14 => 3,
-        5 => 4,
-        6 => 5,
-        16 => 6,
-        11 => 7,
-        9 => 8,
-        18 => 9,
-        17 => 10,
-        19 => 11,
-        7 => 12,
-        8 => 13,
-        12 => 14,
-        13 => 15,
-        23 => 16,
-        24 => 17,
-        10 => 18,
-        1 => 19,
-        2 => 20,
-        3 => 21,
-        4 => 22,
-        22 => 23,
-        _ => -1,
+    match catalogue().iter().find(|d| d.bga_type == Some(typ)) {
+        Some(d) => d.id,
+        None => -1,
     }
 }
 
+// BGA numbers the tiles from 1 consecutively in the order of tile types
 fn convert_from_bga_tile_id_to_bga_tile_type(tid: i32) -> i32 {
-    match tid {
-        1 => 1,
-        2 => 2,
-        3 => 2,
-        4 => 3,
-        5 => 3,
-        6 => 3,
-        7 => 4,
-        8 => 4,
-        9 => 5,
-        10 => 5,
-        11 => 5,
-        12 => 6,
-        13 => 6,
-        14 => 7,
-        15 => 8,
-        16 => 8,
-        17 => 9,
-        18 => 9,
-        19 => 10,
-        20 => 10,
-        21 => 10,
-        22 => 11,
-        23 => 11,
-        24 => 11,
-        25 => 11,
-        26 => 11,
-        27 => 12,
-        28 => 12,
-        29 => 12,
-        30 => 13,
-        31 => 13,
-        32 => 13,
-        33 => 14,
-        34 => 14,
-        35 => 14,
-        36 => 15,
-        37 => 15,
-        38 => 15,
-        39 => 15,
-        40 => 16,
-        41 => 16,
-        42 => 16,
-        43 => 16,
-        44 => 16,
-        45 => 16,
-        46 => 16,
-        47 => 16,
-        48 => 17,
-        49 => 17,
-        50 => 17,
-        51 => 17,
-        52 => 17,
-        53 => 17,
-        54 => 17,
-        55 => 17,
-        56 => 17,
-        57 => 18,
-        58 => 18,
-        59 => 18,
-        60 => 18,
-        61 => 19,
-        62 => 20,
-        63 => 20,
-        64 => 20,
-        65 => 20,
-        66 => 21,
-        67 => 21,
-        68 => 22,
-        69 => 23,
-        70 => 23,
-        71 => 23,
-        72 => 24,
-        _ => -1,
+    if tid < 1 {
+        return -1;
+    }
+    let mut defs: Vec<_> = catalogue()
+        .iter()
+        .filter(|d| d.bga_type.is_some())
+        .collect();
+    defs.sort_by_key(|d| d.bga_type);
+    let mut last_tid = 0;
+    for d in defs {
+        last_tid += d.count;
+        if tid <= last_tid {
+            return d.bga_type.unwrap();
+        }
     }
+    -1
 }
 
 fn convert_pos(tile_id: i32, pos: i32) -> i32 {
-    match tile_id {
-        0 => match pos {
-            2 => 0,
-            3 => 1,
-            1 => 2,
-            4 => 3,
-            _ => -1,
+    match to_tile(tile_id).definition() {
+        Some(d) => match d.features.iter().position(|f| f.bga_position == Some(pos)) {
+            Some(f) => f as i32,
+            None => -1,
         },
-        1 => match pos {
-            1 => 0,
-            2 => 1,
-            _ => -1,
-        },
-        2 => match pos {
-            1 => 0,
-            3 => 1,
-            2 => 2,
-            _ => -1,
-        },
-        3 => match pos {
-            4 => 0,
-            5 => 1,
-            1 => 2,
-            2 => 3,
-            6 => 4,
-            3 => 5,
-            7 => 6,
-            _ => -1,
-        },
-        4 | 5 => match pos {
-            2 => 0,
-            3 => 1,
-            1 => 2,
-            4 => 3,
-            _ => -1,
-        },
-        6 => match pos {
-            3 => 0,
-            1 => 1,
-            4 => 2,
-            _ => -1,
-        },
-        7 => match pos {
-            1 => 0,
-            2 => 1,
-            _ => -1,
-        },
-        8 => match pos {
-            1 => 0,
-            2 => 1,
-            3 => 2,
-            _ => -1,
-        },
-        9 => match pos {
-            4 => 0,
-            1 => 1,
-            2 => 2,
-            5 => 3,
-            3 => 4,
-            6 => 5,
-            _ => -1,
-        },
-        10 => match pos {
-            2 => 0,
-            1 => 1,
-            3 => 2,
-            _ => -1,
-        },
-        11 => match pos {
-            5 => 0,
-            4 => 1,
-            6 => 2,
-            1 => 3,
-            2 => 4,
-            8 => 5,
-            3 => 6,
-            7 => 7,
-            _ => -1,
-        },
-        12 | 13 => match pos {
-            2 => 0,
-            1 => 1,
-            3 => 2,
-            _ => -1,
-        },
-        14 | 15 => match pos {
-            2 => 0,
-            3 => 1,
-            1 => 2,
-            4 => 3,
-            _ => -1,
-        },
-        16 | 17 => match pos {
-            1 => 0,
-            2 => 1,
-            _ => -1,
-        },
-        18 => match pos {
-            1 => 0,
-            3 => 1,
-            2 => 2,
-            _ => -1,
-        },
-        19 | 20 => match pos {
-            2 => 0,
-            3 => 1,
-            1 => 2,
-            4 => 3,
-            _ => -1,
-        },
-        21 | 22 => match pos {
-            1 => 0,
-            2 => 1,
-            _ => -1,
-        },
-        23 => match pos {
-            1 => 0,
-            _ => -1,
-        },
-        _ => -1,
+        None => -1,
     }
 }
 
-fn parse_number(v: &Value) -> i32 {
-    match v {
-        Value::Number(n) => n.as_i64().unwrap() as i32,
-        Value::String(s) => s.parse().unwrap(),
-        _ => panic!("parse failed for {:?}", v),
+// BGA sends numbers either as numbers or as strings
+fn parse_arg(args: &Value, packet_type: &str, arg: &str) -> Result<i32, DecodeError> {
+    let n = match &args[arg] {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    match n {
+        Some(n) => Ok(n as i32),
+        None => Err(DecodeError::UnsupportedPacket {
+            packet_type: packet_type.to_string(),
+            arg: Some(arg.to_string()),
+        }),
     }
 }
+
+#[test]
+fn convert_bga_ids_test() {
+    assert_eq!(convert_from_bga_tile_id_to_bga_tile_type(1), 1);
+    assert_eq!(convert_from_bga_tile_id_to_bga_tile_type(26), 11);
+    assert_eq!(convert_from_bga_tile_id_to_bga_tile_type(36), 15);
+    assert_eq!(convert_from_bga_tile_id_to_bga_tile_type(72), 24);
+    assert_eq!(convert_from_bga_tile_id_to_bga_tile_type(73), -1);
+    assert_eq!(convert_from_bga_tile_type_to_tile_id(15), 0);
+    assert_eq!(convert_from_bga_tile_type_to_tile_id(22), 23);
+    assert_eq!(convert_pos(3, 7), 6);
+    assert_eq!(convert_pos(11, 8), 5);
+    assert_eq!(convert_pos(23, 2), -1);
+}
+
+#[test]
+fn decode_test() {
+    let replay = |packets: &str| format!(r#"{{"data": {{"data": [{{"data": [{}]}}]}}}}"#, packets);
+    let play_tile =
+        r#"{"type": "playTile", "args": {"type": "20", "x": "0", "y": "1", "ori": "1"}}"#;
+
+    let mvs = decode(replay(&format!(
+        r#"{}, {{"type": "playPartisan", "args": {{"x": 0, "y": 1, "pos": 2}}}}"#,
+        play_tile
+    )))
+    .unwrap();
+    assert_eq!(mvs.len(), 4);
+    assert_eq!(
+        mvs[2],
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 2,
+            game_id: None,
+            player_id: 1,
+            tile: Tile::Monastery,
+            rot: 0,
+            pos: (1, 0),
+        })
+    );
+    assert_eq!(
+        mvs[3],
+        Move::MMove(MeepleMove {
+            id: -1,
+            ord: 3,
+            game_id: None,
+            player_id: 1,
+            meeple_id: 7,
+            tile_pos: (1, 0),
+            meeple_pos: 1,
+        })
+    );
+
+    // an empty meeple move is added after a tile without a meeple
+    let mvs = decode(replay(play_tile)).unwrap();
+    assert_eq!(mvs.len(), 4);
+    assert!(matches!(&mvs[3], Move::MMove(m) if m.meeple_id == -1));
+
+    assert!(matches!(
+        decode("not json".to_string()),
+        Err(DecodeError::InvalidJson { .. })
+    ));
+    assert_eq!(
+        decode(replay(
+            r#"{"type": "playTile", "args": {"type": "99", "x": "0", "y": "1", "ori": "1"}}"#
+        )),
+        Err(DecodeError::ExpansionContent {
+            packet_type: "playTile".to_string(),
+            content: "tile type 99".to_string(),
+        })
+    );
+    assert_eq!(
+        decode(replay(
+            r#"{"type": "playTile", "args": {"type": "20", "y": "1", "ori": "1"}}"#
+        )),
+        Err(DecodeError::UnsupportedPacket {
+            packet_type: "playTile".to_string(),
+            arg: Some("x".to_string()),
+        })
+    );
+    assert_eq!(
+        decode(replay(r#"{"type": "cantPlay", "args": {"tile_id": "80"}}"#)),
+        Err(DecodeError::ExpansionContent {
+            packet_type: "cantPlay".to_string(),
+            content: "tile 80".to_string(),
+        })
+    );
+    // a meeple without a tile
+    assert!(matches!(
+        decode(replay(
+            r#"{"type": "playPartisan", "args": {"x": 0, "y": 0, "pos": 2}}"#
+        )),
+        Err(DecodeError::OutOfOrderMove { ord: 2, .. })
+    ));
+    // a tile which doesn't fit
+    assert!(matches!(
+        decode(replay(
+            r#"{"type": "playTile", "args": {"type": "20", "x": "5", "y": "5", "ori": "1"}}"#
+        )),
+        Err(DecodeError::OutOfOrderMove { ord: 2, .. })
+    ));
+    // packets which don't change the board are skipped, and unknown ones are rejected
+    let mvs = decode(replay(&format!(
+        r#"{{"type": "updateReflexionTime", "args": {{}}}}, {}"#,
+        play_tile
+    )))
+    .unwrap();
+    assert_eq!(mvs.len(), 4);
+    assert_eq!(
+        decode(replay(r#"{"type": "playAbbot", "args": {}}"#)),
+        Err(DecodeError::UnsupportedPacket {
+            packet_type: "playAbbot".to_string(),
+            arg: None,
+        })
+    );
+    assert!(matches!(
+        decode_from_file_path("no/such/replay.json".to_string()),
+        Err(DecodeError::Unreadable { .. })
+    ));
+}
@@ -1,13 +1,11 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
 
-use super::calculate::calculate;
 use super::calculate::Feature::*;
 use super::calculate::Side::*;
-use super::calculate::TileItem;
+use super::calculate::{meeple_owner, meeple_strength, TileItem};
+use super::game_state::GameState;
 use super::mergeable_feature::MergeableFeature;
-use super::mov;
-use super::mov::Move;
 use super::{tile, tile::Tile};
 
 #[derive(Debug, Clone)]
@@ -132,6 +130,17 @@ pub fn count_fitting_tiles(
     }
 }
 
+// tiles with a road that ends on the tile
+fn has_roadend(t: Tile) -> bool {
+    match t.definition() {
+        Some(d) => d
+            .features
+            .iter()
+            .any(|f| f.kind == RoadFeature && f.open_sides == 1),
+        None => false,
+    }
+}
+
 pub fn count_fitting_roadends(
     board: &HashMap<(i32, i32), TileItem>,
     remaining_tiles: &Vec<Tile>,
@@ -140,16 +149,8 @@ pub fn count_fitting_roadends(
 ) -> Option<i32> {
     let mut roadends = vec![];
     for t in remaining_tiles {
-        match t {
-            Tile::MonasteryWithRoad
-            | Tile::TripleRoad
-            | Tile::QuadrupleRoad
-            | Tile::CityCapWithCrossroad
-            | Tile::TripleCityWithRoad
-            | Tile::TripleCityWithRoadWithCOA => {
-                roadends.push(t.clone());
-            }
-            _ => {}
+        if has_roadend(*t) {
+            roadends.push(t.clone());
         }
     }
     count_fitting_tiles(board, &roadends, Tile::Invalid, y, x)
@@ -485,10 +486,10 @@ fn search(
     let mut player0_meeples = 0;
     let mut player1_meeples = 0;
     for meeple in meeples {
-        if meeple < 7 {
-            player0_meeples += 1;
+        if meeple_owner(meeple) == 0 {
+            player0_meeples += meeple_strength(meeple);
         } else {
-            player1_meeples += 1;
+            player1_meeples += meeple_strength(meeple);
         }
     }
 
@@ -616,6 +617,14 @@ fn search(
     });
 }
 
+// the tiles of `board` in the order of their positions. visiting features in the order of the hash map
+// would round the probabilities, which are integers, differently from one run to another
+fn sorted_tiles(board: &HashMap<(i32, i32), TileItem>) -> Vec<&TileItem> {
+    let mut positions: Vec<&(i32, i32)> = board.keys().collect();
+    positions.sort();
+    positions.into_iter().map(|p| &board[p]).collect()
+}
+
 fn search_cities(
     board: &HashMap<(i32, i32), TileItem>,
     mf: &mut MergeableFeature,
@@ -623,7 +632,7 @@ fn search_cities(
     remaining_tiles: &Vec<Tile>,
 ) -> Vec<City> {
     let mut cities = vec![];
-    for t in board.values() {
+    for t in sorted_tiles(board) {
         let fs = t.features();
         for f in &fs {
             if f.feature != CityFeature {
@@ -659,45 +668,31 @@ fn search_cities(
     cities
 }
 
+// tiles with a city that connects two or more sides
 fn can_be_used_to_expand(t: Tile) -> bool {
-    match t {
-        Tile::QuadrupleCityWithCOA
-        | Tile::TripleCity
-        | Tile::TripleCityWithCOA
-        | Tile::TripleCityWithRoad
-        | Tile::TripleCityWithRoadWithCOA
-        | Tile::Triangle
-        | Tile::TriangleWithCOA
-        | Tile::TriangleWithRoad
-        | Tile::TriangleWithRoadWithCOA
-        | Tile::Connector
-        | Tile::ConnectorWithCOA => true,
-        _ => false,
+    match t.definition() {
+        Some(d) => d
+            .features
+            .iter()
+            .any(|f| f.kind == CityFeature && f.open_sides >= 2),
+        None => false,
     }
 }
 
-pub fn evaluate(moves: &Vec<Move>, debug: bool) -> (i32, i32) {
+pub fn evaluate(state: &mut GameState, debug: bool) -> (i32, i32) {
     let dy = [0, -1, 0, 1];
     let dx = [1, 0, -1, 0];
 
-    let s = match calculate(&moves, false) {
-        Ok(s) => s,
-        Err(e) => panic!("{:?}", e.detail.msg),
-    };
+    let out_tiles = state.placed_tiles().clone();
+    let remaining_tiles = tile::remaining_tiles(out_tiles.clone(), state.expansions());
 
-    let mut mf = s.mergeable_features;
-    let mut board = s.board.clone();
-    let tile_id_to_pos = s.tile_id_to_pos;
+    let s = state.status_mut();
 
-    let mut out_tiles = vec![];
-    for mv in moves {
-        match mv {
-            mov::Move::TMove(tm) => {
-                out_tiles.push(tm.tile.clone());
-            }
-            _ => {}
-        }
-    }
+    // features are marked as done while evaluating, which is rolled back before returning
+    let mut mf = std::mem::replace(&mut s.mergeable_features, MergeableFeature::new());
+    let checkpoint = mf.checkpoint();
+    let mut board = s.board.clone();
+    let tile_id_to_pos = &s.tile_id_to_pos;
 
     let mut roads = vec![vec![]; 2];
     let mut monasteries = vec![vec![]; 2];
@@ -707,8 +702,6 @@ pub fn evaluate(moves: &Vec<Move>, debug: bool) -> (i32, i32) {
 
     let meeple_value = 30;
 
-    let remaining_tiles = tile::remaining_tiles(out_tiles.clone());
-
     let cities = search_cities(&board, &mut mf, &tile_id_to_pos, &remaining_tiles);
 
     for city in &cities {
@@ -942,7 +935,7 @@ pub fn evaluate(moves: &Vec<Move>, debug: bool) -> (i32, i32) {
         results[1] += result1;
     }
 
-    for t in board.values() {
+    for t in sorted_tiles(&board) {
         let fs = t.features();
         for f in &fs {
             let meeple_ids = mf.get_meeples(f.id as usize);
@@ -957,10 +950,10 @@ pub fn evaluate(moves: &Vec<Move>, debug: bool) -> (i32, i32) {
             let mut player0_meeples = 0;
             let mut player1_meeples = 0;
             for meeple_id in &meeple_ids {
-                if *meeple_id < 7 {
-                    player0_meeples += 1;
+                if meeple_owner(*meeple_id) == 0 {
+                    player0_meeples += meeple_strength(*meeple_id);
                 } else {
-                    player1_meeples += 1;
+                    player1_meeples += meeple_strength(*meeple_id);
                 }
             }
             let feat = Ft {
@@ -999,6 +992,7 @@ pub fn evaluate(moves: &Vec<Move>, debug: bool) -> (i32, i32) {
                 FieldFeature => {
                     fields[player].push(feat);
                 }
+                RiverFeature => {}
             }
         }
     }
@@ -1243,29 +1237,29 @@ pub fn evaluate(moves: &Vec<Move>, debug: bool) -> (i32, i32) {
         }
     }
 
-    results[0] += s.player0_point * 12;
-    results[1] += s.player1_point * 12;
+    results[0] += s.player_points[0] * 12;
+    results[1] += s.player_points[1] * 12;
 
     if debug {
         println!(
-            "s.player0_point, s.player1_point = {:?}, {:?}",
-            s.player0_point, s.player1_point
+            "s.player_points[0], s.player_points[1] = {:?}, {:?}",
+            s.player_points[0], s.player_points[1]
         );
         println!(
             "result0, result1 = {:?}, {:?}",
-            s.player0_point * 12,
-            s.player1_point * 12
+            s.player_points[0] * 12,
+            s.player_points[1] * 12
         );
     }
 
-    results[0] += remaining_meeple_values(s.player0_remaining_meeples.len());
-    results[1] += remaining_meeple_values(s.player1_remaining_meeples.len());
+    results[0] += remaining_meeple_values(s.player_remaining_meeples[0].len());
+    results[1] += remaining_meeple_values(s.player_remaining_meeples[1].len());
 
     if debug {
         println!(
             "remainig_meeple_values result0, result1 = {:?}, {:?}",
-            remaining_meeple_values(s.player0_remaining_meeples.len()),
-            remaining_meeple_values(s.player1_remaining_meeples.len()),
+            remaining_meeple_values(s.player_remaining_meeples[0].len()),
+            remaining_meeple_values(s.player_remaining_meeples[1].len()),
         );
     }
     if debug {
@@ -1280,6 +1274,9 @@ pub fn evaluate(moves: &Vec<Move>, debug: bool) -> (i32, i32) {
         println!();
     }
 
+    mf.rollback(checkpoint);
+    s.mergeable_features = mf;
+
     return (results[0], results[1]);
 }
 
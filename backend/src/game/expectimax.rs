@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use super::game_state::GameState;
+use super::mov::{DiscardMove, MeepleMove, Move, TileMove};
+use super::rule_set::RuleSet;
+use super::tile;
+use super::tile::{Expansion, Tile};
+
+// an exact solver of the end of two-player games.
+// the players choose their moves (decision nodes) and the tiles are drawn from what remains in the deck
+// (chance nodes, whose outcomes are the distinct tiles weighted by their counts). both players are assumed to
+// play for the win first and for the points next. positions reached by placing the same tiles in another
+// order are solved once
+
+// how a move turns out for the player the solver is for
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub tile_move: TileMove,
+    pub meeple_move: MeepleMove,
+    // 0.5 for a draw
+    pub win_probability: f64,
+    // the points of the player minus the points of the opponent at the end
+    pub expected_point_diff: f64,
+}
+
+// values closer than this are taken as equal, so that the rounding of sums doesn't break ties
+const EPSILON: f64 = 1e-9;
+
+// the time limit is checked every this many moves
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Place {
+        pos: (i32, i32),
+        rot: i32,
+        meeple_pos: i32,
+    },
+    Discard,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Outcome {
+    win: f64,
+    diff: f64,
+}
+
+impl Outcome {
+    fn is_better_than(&self, other: &Outcome) -> bool {
+        if (self.win - other.win).abs() > EPSILON {
+            return self.win > other.win;
+        }
+        self.diff > other.diff + EPSILON
+    }
+}
+
+// a move made since the root: the tile id, the position, the rotation, the meeple id and the meeple's position
+type Made = (i32, (i32, i32), i32, i32, i32);
+
+// the moves made since the root, the points and the meeples left, and who draws next.
+// they tell everything about a position below the root, whatever order the moves were made in
+type Key = (Vec<Made>, Vec<i32>, Vec<usize>, usize);
+
+#[derive(Clone)]
+struct Solver {
+    state: GameState,
+    player_ids: Vec<i32>,
+    // the seat the solver is for
+    seat: usize,
+    // the seat which wins when the points are tied
+    tie_winner_seat: Option<usize>,
+    redraw_on_discard: bool,
+    remaining: Vec<(Tile, usize)>,
+    made: Vec<Made>,
+    cache: HashMap<Key, Outcome>,
+    deadline: Option<Instant>,
+    nodes: usize,
+    timed_out: bool,
+}
+
+// every move `player_id` can make with `next_tile`, with how it turns out when both players play on perfectly.
+// None when it isn't a two-player game or the search doesn't end by `deadline`
+pub fn solve_endgame(
+    moves: &Vec<Move>,
+    game_id: Option<i32>,
+    player_ids: &[i32],
+    player_id: i32,
+    next_tile: Tile,
+    expansions: &[Expansion],
+    rule_set: &RuleSet,
+    deadline: Option<Instant>,
+) -> Option<Vec<Candidate>> {
+    if player_ids.len() != 2 || moves.is_empty() {
+        return None;
+    }
+    let seat = player_ids.iter().position(|p| *p == player_id)?;
+    let state = match GameState::from_moves(moves, 2, expansions, rule_set) {
+        Ok(s) => s,
+        Err(_) => return None,
+    };
+
+    let mut out_tiles = vec![next_tile];
+    out_tiles.extend(state.placed_tiles());
+    out_tiles.extend(state.discarded_tiles());
+    let mut remaining: Vec<(Tile, usize)> = vec![];
+    for t in tile::remaining_tiles(out_tiles, expansions) {
+        match remaining.iter_mut().find(|(rt, _)| *rt == t) {
+            Some((_, count)) => *count += 1,
+            None => remaining.push((t, 1)),
+        }
+    }
+
+    // the player who placed the starting tile plays last, and wins ties (see `decide_winner`)
+    let tie_winner_seat = if rule_set.tie_is_draw {
+        None
+    } else {
+        player_ids.iter().position(|p| *p == moves[0].player_id())
+    };
+    let mut solver = Solver {
+        state,
+        player_ids: player_ids.to_vec(),
+        seat,
+        tie_winner_seat,
+        redraw_on_discard: rule_set.redraw_on_discard,
+        remaining,
+        made: vec![],
+        cache: HashMap::new(),
+        deadline,
+        nodes: 0,
+        timed_out: false,
+    };
+
+    // each move is solved on a copy of the solver of its own thread, with its own cache
+    let actions = solver.actions(seat, next_tile);
+    let outcomes: Vec<Option<Outcome>> = actions
+        .par_iter()
+        .map_init(
+            || solver.clone(),
+            |s, action| {
+                let outcome = s.outcome_of(seat, next_tile, action);
+                if s.timed_out {
+                    None
+                } else {
+                    Some(outcome)
+                }
+            },
+        )
+        .collect();
+
+    let ord = moves.last().unwrap().ord() + 1;
+    let meeple_id = solver.meeple_id(seat);
+    let mut candidates = vec![];
+    for (action, outcome) in actions.iter().zip(outcomes) {
+        let outcome = outcome?;
+        let (pos, rot, meeple_pos) = match action {
+            Action::Place {
+                pos,
+                rot,
+                meeple_pos,
+            } => (*pos, *rot, *meeple_pos),
+            // the tile can't be placed, so there's no move to choose
+            Action::Discard => return Some(vec![]),
+        };
+        candidates.push(Candidate {
+            tile_move: TileMove {
+                id: -1,
+                ord,
+                game_id,
+                player_id,
+                tile: next_tile,
+                rot,
+                pos,
+            },
+            meeple_move: MeepleMove {
+                id: -1,
+                ord: ord + 1,
+                game_id,
+                player_id,
+                meeple_id: if meeple_pos == -1 { -1 } else { meeple_id },
+                tile_pos: pos,
+                meeple_pos,
+            },
+            win_probability: outcome.win,
+            expected_point_diff: outcome.diff,
+        });
+    }
+    Some(candidates)
+}
+
+// the candidate most likely to win, and scoring the most of them. the first of them on a tie
+pub fn best_candidate(candidates: &[Candidate]) -> Option<&Candidate> {
+    let mut best: Option<&Candidate> = None;
+    for c in candidates {
+        let better = match best {
+            Some(b) => Outcome {
+                win: c.win_probability,
+                diff: c.expected_point_diff,
+            }
+            .is_better_than(&Outcome {
+                win: b.win_probability,
+                diff: b.expected_point_diff,
+            }),
+            None => true,
+        };
+        if better {
+            best = Some(c);
+        }
+    }
+    best
+}
+
+impl Solver {
+    // every way `seat` can place `tile`, or discarding it when there's none
+    fn actions(&mut self, seat: usize, tile: Tile) -> Vec<Action> {
+        let mut actions = vec![];
+        for tp in self.state.tileable_positions(tile) {
+            // rotations that look the same lead to the same positions
+            if tp.rot >= tile.rotations() {
+                continue;
+            }
+            let tm = self.tile_move(seat, tile, tp.pos, tp.rot);
+            if let Err(e) = self.state.apply_tile_move(&tm) {
+                panic!("{:?}", e.detail.msg);
+            }
+            let mut meeple_positions = vec![-1];
+            if !self.state.status().player_remaining_meeples[seat].is_empty() {
+                meeple_positions.extend(self.state.status().meepleable_positions.iter());
+            }
+            self.state.undo();
+            for meeple_pos in meeple_positions {
+                actions.push(Action::Place {
+                    pos: tp.pos,
+                    rot: tp.rot,
+                    meeple_pos,
+                });
+            }
+        }
+        if actions.is_empty() {
+            actions.push(Action::Discard);
+        }
+        actions
+    }
+
+    // how it turns out when `seat` makes `action` with `tile`
+    fn outcome_of(&mut self, seat: usize, tile: Tile, action: &Action) -> Outcome {
+        if self.nodes.is_multiple_of(DEADLINE_CHECK_INTERVAL) {
+            if let Some(d) = self.deadline {
+                if Instant::now() > d {
+                    self.timed_out = true;
+                }
+            }
+        }
+        self.nodes += 1;
+        if self.timed_out {
+            return Outcome::default();
+        }
+
+        self.apply(seat, tile, action);
+        let next_seat = match action {
+            Action::Discard if self.redraw_on_discard => seat,
+            _ => 1 - seat,
+        };
+        let outcome = if self.remaining.iter().all(|(_, count)| *count == 0) {
+            self.final_outcome()
+        } else {
+            self.draw(next_seat)
+        };
+        self.undo(action);
+        outcome
+    }
+
+    // `seat` draws one of the remaining tiles
+    fn draw(&mut self, seat: usize) -> Outcome {
+        let key = self.key(seat);
+        if let Some(o) = self.cache.get(&key) {
+            return *o;
+        }
+
+        let total: usize = self.remaining.iter().map(|(_, count)| count).sum();
+        let mut outcome = Outcome::default();
+        for k in 0..self.remaining.len() {
+            let (tile, count) = self.remaining[k];
+            if count == 0 {
+                continue;
+            }
+            self.remaining[k].1 -= 1;
+            let o = self.decide(seat, tile);
+            self.remaining[k].1 += 1;
+            let p = count as f64 / total as f64;
+            outcome.win += o.win * p;
+            outcome.diff += o.diff * p;
+        }
+
+        self.cache.insert(key, outcome);
+        outcome
+    }
+
+    // `seat` places `tile` where it's best for them
+    fn decide(&mut self, seat: usize, tile: Tile) -> Outcome {
+        let mut best: Option<Outcome> = None;
+        for action in self.actions(seat, tile) {
+            let o = self.outcome_of(seat, tile, &action);
+            let better = match &best {
+                // the opponent plays for the worst outcome of `self.seat`
+                Some(b) if seat == self.seat => o.is_better_than(b),
+                Some(b) => b.is_better_than(&o),
+                None => true,
+            };
+            if better {
+                best = Some(o);
+            }
+        }
+        best.unwrap()
+    }
+
+    fn final_outcome(&mut self) -> Outcome {
+        self.state.apply_final_scoring();
+        let points = &self.state.status().player_points;
+        let diff = points[self.seat] - points[1 - self.seat];
+        let win = if diff > 0 {
+            1.0
+        } else if diff < 0 {
+            0.0
+        } else {
+            match self.tie_winner_seat {
+                Some(s) if s == self.seat => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            }
+        };
+        self.state.undo();
+        Outcome {
+            win,
+            diff: diff as f64,
+        }
+    }
+
+    fn key(&self, seat: usize) -> Key {
+        let mut made = self.made.clone();
+        made.sort();
+        let status = self.state.status();
+        (
+            made,
+            status.player_points.clone(),
+            status
+                .player_remaining_meeples
+                .iter()
+                .map(|m| m.len())
+                .collect(),
+            seat,
+        )
+    }
+
+    fn apply(&mut self, seat: usize, tile: Tile, action: &Action) {
+        let res = match action {
+            Action::Place {
+                pos,
+                rot,
+                meeple_pos,
+            } => {
+                let tm = self.tile_move(seat, tile, *pos, *rot);
+                let meeple_id = if *meeple_pos == -1 {
+                    -1
+                } else {
+                    self.meeple_id(seat)
+                };
+                self.made
+                    .push((tile.to_id(), *pos, *rot, meeple_id, *meeple_pos));
+                let mm = MeepleMove {
+                    id: -1,
+                    ord: -1,
+                    game_id: None,
+                    player_id: self.player_ids[seat],
+                    meeple_id,
+                    tile_pos: *pos,
+                    meeple_pos: *meeple_pos,
+                };
+                self.state
+                    .apply_tile_move(&tm)
+                    .and_then(|_| self.state.apply_meeple_move(&mm))
+            }
+            Action::Discard => {
+                // the seat is kept, since who discarded tells who draws next
+                self.made
+                    .push((tile.to_id(), (i32::MIN, seat as i32), 0, -1, -1));
+                self.state.apply_discard_move(&DiscardMove {
+                    id: -1,
+                    ord: -1,
+                    game_id: None,
+                    player_id: self.player_ids[seat],
+                    tile,
+                })
+            }
+        };
+        if let Err(e) = res {
+            panic!("{:?}", e.detail.msg);
+        }
+    }
+
+    fn undo(&mut self, action: &Action) {
+        self.made.pop();
+        match action {
+            Action::Place { .. } => {
+                self.state.undo(); // meeple move
+                self.state.undo(); // tile move
+            }
+            Action::Discard => self.state.undo(),
+        }
+    }
+
+    fn tile_move(&self, seat: usize, tile: Tile, pos: (i32, i32), rot: i32) -> TileMove {
+        TileMove {
+            id: -1,
+            ord: -1,
+            game_id: None,
+            player_id: self.player_ids[seat],
+            tile,
+            rot,
+            pos,
+        }
+    }
+
+    // the smallest id of the meeples `seat` has left
+    fn meeple_id(&self, seat: usize) -> i32 {
+        match self.state.status().player_remaining_meeples[seat]
+            .iter()
+            .min()
+        {
+            Some(id) => *id,
+            None => -1,
+        }
+    }
+}
+
+// the moves of a game played with the first legal moves until `left` tiles (with the one drawn) are left,
+// and the tile drawn
+#[cfg(test)]
+pub(super) fn late_game(seed: u64, left: usize) -> (Vec<Move>, Tile) {
+    use super::agent::{first_legal_move, AgentState, Decision};
+
+    let deck = tile::deck(seed, &[]);
+    let mut moves = vec![
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 0,
+            game_id: None,
+            player_id: 1,
+            tile: Tile::StartingTile,
+            rot: 0,
+            pos: (0, 0),
+        }),
+        Move::MMove(MeepleMove {
+            id: -1,
+            ord: 1,
+            game_id: None,
+            player_id: 1,
+            meeple_id: -1,
+            tile_pos: (0, 0),
+            meeple_pos: -1,
+        }),
+    ];
+    let mut out_tiles = vec![Tile::StartingTile];
+    let mut player_id = 0;
+    loop {
+        let t = tile::draw(&deck, &out_tiles, &[]);
+        out_tiles.push(t);
+        if tile::remaining_tiles(out_tiles.clone(), &[]).len() + 1 == left {
+            return (moves, t);
+        }
+        let state = AgentState {
+            game_id: None,
+            player_ids: vec![0, 1],
+            player_id,
+            moves: moves.clone(),
+            expansions: vec![],
+            rule_set: RuleSet::default(),
+        };
+        let ord = moves.last().unwrap().ord() + 1;
+        match first_legal_move(&state, t) {
+            Decision::Place(tm, mm) => {
+                moves.push(Move::TMove(tm));
+                moves.push(Move::MMove(mm));
+            }
+            Decision::Discard => {
+                moves.push(Move::DMove(DiscardMove {
+                    id: -1,
+                    ord,
+                    game_id: None,
+                    player_id,
+                    tile: t,
+                }));
+            }
+        }
+        player_id = 1 - player_id;
+    }
+}
+
+#[test]
+fn solve_endgame_last_tile_test() {
+    use super::calculate::calculate;
+
+    let (moves, t) = late_game(0, 1);
+    let candidates =
+        solve_endgame(&moves, None, &[0, 1], 0, t, &[], &RuleSet::default(), None).unwrap();
+    assert!(!candidates.is_empty());
+    // with no tile to draw, every candidate is the final score
+    for c in &candidates {
+        let mut mvs = moves.clone();
+        mvs.push(Move::TMove(c.tile_move.clone()));
+        mvs.push(Move::MMove(c.meeple_move.clone()));
+        let s = calculate(&mvs, 2, &[], &RuleSet::default(), true).unwrap();
+        let diff = s.player_points[0] - s.player_points[1];
+        assert_eq!(c.expected_point_diff, diff as f64);
+        // player 1 placed the starting tile, so a tie goes to them
+        assert_eq!(c.win_probability, if diff > 0 { 1.0 } else { 0.0 });
+    }
+}
+
+#[test]
+fn solve_endgame_test() {
+    let (moves, t) = late_game(1, 2);
+    let candidates =
+        solve_endgame(&moves, None, &[0, 1], 0, t, &[], &RuleSet::default(), None).unwrap();
+    assert!(!candidates.is_empty());
+    for c in &candidates {
+        assert!((0.0..=1.0).contains(&c.win_probability));
+        assert_eq!(
+            (c.tile_move.ord, c.meeple_move.ord),
+            (moves.len() as i32, moves.len() as i32 + 1)
+        );
+    }
+    let best = best_candidate(&candidates).unwrap();
+    assert!(candidates
+        .iter()
+        .all(|c| c.win_probability <= best.win_probability));
+
+    // it gives up when it runs out of time
+    let (moves, t) = late_game(0, 1);
+    assert!(solve_endgame(
+        &moves,
+        None,
+        &[0, 1],
+        0,
+        t,
+        &[],
+        &RuleSet::default(),
+        Some(Instant::now())
+    )
+    .is_none());
+}
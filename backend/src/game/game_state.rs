@@ -0,0 +1,970 @@
+use std::collections::{HashMap, HashSet};
+
+use super::calculate::Feature::*;
+use super::calculate::{
+    create_mergeable_features, is_big_meeple, majority_players, meeple_owner, meeples_of,
+    merge_features, set_cities_to_fields, CompleteEvent, Side, Status, TileItem, TileablePosition,
+    MEEPLE_NUM,
+};
+use super::mergeable_feature::MergeableFeature;
+use super::mov::{DiscardMove, MeepleMove, Move, TileMove};
+use super::rule_set::{FieldEdition, RuleSet};
+use super::tile::{Expansion, Tile};
+use crate::error::{move_error, Error, MoveError};
+
+// the position of a river tile and the direction of its water side that isn't connected yet
+type RiverEnd = ((i32, i32), usize);
+
+// everything needed to take back one applied move
+#[derive(Clone, Debug)]
+struct Undo {
+    features_checkpoint: usize,
+    board: Vec<((i32, i32), Option<TileItem>)>,
+    placed_tile_id: Option<i32>,
+    placed: bool,
+    discarded: bool,
+    feature_num: i32,
+    // `None` when the move didn't change them
+    meepleable_positions: Option<Vec<i32>>,
+    complete_events: Option<Vec<CompleteEvent>>,
+    player_points: Vec<i32>,
+    // the meeples moved in the order they were moved, with the position each was at before
+    // (`None` for a meeple that was in its owner's hand)
+    meeples: Vec<(i32, Option<(i32, i32)>)>,
+    river_end: Option<RiverEnd>,
+    river_turn: Option<i32>,
+}
+
+// `GameState` is a `Status` that can be moved forward and backward one move at a time,
+// so that the AI and the solver don't have to replay all the moves for every candidate.
+// a clone can be moved on its own, e.g. by another thread
+#[derive(Clone, Debug)]
+pub struct GameState {
+    status: Status,
+    expansions: Vec<Expansion>,
+    rule_set: RuleSet,
+    tile_count: i32,
+    meeple_id_to_pos: HashMap<i32, (i32, i32)>,
+    placed_tiles: Vec<Tile>,
+    discarded_tiles: Vec<Tile>,
+    // the river tile whose water side is still open and the direction of that side
+    river_end: Option<RiverEnd>,
+    // the direction (1 for left, -1 for right) the river turned to at its last curve
+    river_turn: Option<i32>,
+    history: Vec<Undo>,
+}
+
+impl GameState {
+    pub fn new(player_num: usize, expansions: &[Expansion], rule_set: &RuleSet) -> Self {
+        GameState {
+            status: Status {
+                meepleable_positions: vec![],
+                complete_events: vec![],
+                player_points: vec![0; player_num],
+                board: HashMap::new(),
+                player_remaining_meeples: (0..player_num)
+                    .map(|p| meeples_of(p, expansions, rule_set))
+                    .collect(),
+                tile_id_to_pos: HashMap::new(),
+                mergeable_features: MergeableFeature::new(),
+                feature_num: 0,
+            },
+            expansions: expansions.to_vec(),
+            rule_set: rule_set.clone(),
+            tile_count: 0,
+            meeple_id_to_pos: HashMap::new(),
+            placed_tiles: vec![],
+            discarded_tiles: vec![],
+            river_end: None,
+            river_turn: None,
+            history: vec![],
+        }
+    }
+
+    pub fn from_moves(
+        moves: &Vec<Move>,
+        player_num: usize,
+        expansions: &[Expansion],
+        rule_set: &RuleSet,
+    ) -> Result<Self, Error> {
+        let mut state = GameState::new(player_num, expansions, rule_set);
+        for mv in moves {
+            state.apply(mv)?;
+        }
+        Ok(state)
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    pub fn status_mut(&mut self) -> &mut Status {
+        &mut self.status
+    }
+
+    pub fn into_status(self) -> Status {
+        self.status
+    }
+
+    pub fn expansions(&self) -> &[Expansion] {
+        &self.expansions
+    }
+
+    // tiles placed on the board, in the order they were placed
+    pub fn placed_tiles(&self) -> &Vec<Tile> {
+        &self.placed_tiles
+    }
+
+    pub fn discarded_tiles(&self) -> &Vec<Tile> {
+        &self.discarded_tiles
+    }
+
+    #[allow(unused)]
+    // the number of moves that can be taken back with `undo`
+    pub fn depth(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn apply(&mut self, mv: &Move) -> Result<(), Error> {
+        match mv {
+            Move::TMove(m) => self.apply_tile_move(m),
+            Move::MMove(m) => self.apply_meeple_move(m),
+            Move::DMove(m) => self.apply_discard_move(m),
+            Move::InvalidMove => {
+                let undo = self.start_undo();
+                self.history.push(undo);
+                Ok(())
+            }
+        }
+    }
+
+    // takes back the last move (or the final scoring) applied to this state
+    pub fn undo(&mut self) {
+        let undo = match self.history.pop() {
+            Some(u) => u,
+            None => panic!("there is no move to undo"),
+        };
+        self.status
+            .mergeable_features
+            .rollback(undo.features_checkpoint);
+        for (pos, t) in undo.board.into_iter().rev() {
+            match t {
+                Some(t) => {
+                    self.status.board.insert(pos, t);
+                }
+                None => {
+                    self.status.board.remove(&pos);
+                }
+            }
+        }
+        if let Some(tile_id) = undo.placed_tile_id {
+            self.status.tile_id_to_pos.remove(&tile_id);
+            self.tile_count -= 1;
+        }
+        if undo.placed {
+            self.placed_tiles.pop();
+        }
+        if undo.discarded {
+            self.discarded_tiles.pop();
+        }
+        self.status.feature_num = undo.feature_num;
+        if let Some(meepleable_positions) = undo.meepleable_positions {
+            self.status.meepleable_positions = meepleable_positions;
+        }
+        if let Some(complete_events) = undo.complete_events {
+            self.status.complete_events = complete_events;
+        }
+        self.status.player_points = undo.player_points;
+        for (meeple_id, prev) in undo.meeples.into_iter().rev() {
+            let remaining_meeples =
+                &mut self.status.player_remaining_meeples[meeple_owner(meeple_id)];
+            match prev {
+                Some(pos) => {
+                    self.meeple_id_to_pos.insert(meeple_id, pos);
+                    remaining_meeples.remove(&meeple_id);
+                }
+                None => {
+                    self.meeple_id_to_pos.remove(&meeple_id);
+                    remaining_meeples.insert(meeple_id);
+                }
+            }
+        }
+        self.river_end = undo.river_end;
+        self.river_turn = undo.river_turn;
+    }
+
+    fn start_undo(&self) -> Undo {
+        Undo {
+            features_checkpoint: self.status.mergeable_features.checkpoint(),
+            board: vec![],
+            placed_tile_id: None,
+            placed: false,
+            discarded: false,
+            feature_num: self.status.feature_num,
+            meepleable_positions: None,
+            complete_events: None,
+            player_points: self.status.player_points.clone(),
+            meeples: vec![],
+            river_end: self.river_end,
+            river_turn: self.river_turn,
+        }
+    }
+
+    // gives `pts` to every player who has the most meeples on the feature, and returns the meeples to their owners
+    fn score(&mut self, meeple_ids: &Vec<i32>, pts: i32) {
+        for player in majority_players(meeple_ids, self.status.player_points.len()) {
+            self.status.player_points[player] += pts;
+        }
+        for meeple_id in meeple_ids {
+            self.status.player_remaining_meeples[meeple_owner(*meeple_id)].insert(*meeple_id);
+        }
+    }
+
+    fn set_board(&mut self, undo: &mut Undo, pos: (i32, i32), t: TileItem) {
+        let prev = self.status.board.insert(pos, t);
+        undo.board.push((pos, prev));
+    }
+
+    fn remove_meeple_from_board(&mut self, undo: &mut Undo, meeple_id: i32) {
+        assert!(self.meeple_id_to_pos.contains_key(&meeple_id));
+        let pos = *self.meeple_id_to_pos.get(&meeple_id).unwrap();
+        assert!(self.status.board.contains_key(&pos));
+        let t = self.status.board[&pos];
+        self.set_board(
+            undo,
+            pos,
+            TileItem {
+                meeple_id: None,
+                meeple_pos: None,
+                ..t
+            },
+        );
+        self.meeple_id_to_pos.remove(&meeple_id);
+        undo.meeples.push((meeple_id, Some(pos)));
+    }
+
+    // the river has to be continued from its open end, and must not turn to the same direction twice in a row
+    // (otherwise it could make a U-turn). returns the open end and the last turn after `t` is placed at `pos`
+    fn next_river(
+        &self,
+        t: &TileItem,
+        pos: (i32, i32),
+    ) -> Result<(Option<RiverEnd>, Option<i32>), Error> {
+        let dy = [0, -1, 0, 1];
+        let dx = [1, 0, -1, 0];
+        let water_dirs: Vec<usize> = (0..4)
+            .filter(|dir| t.side_by_dir(*dir) == Side::Water)
+            .collect();
+
+        if self.status.board.is_empty() {
+            if water_dirs.len() != 1 {
+                return Err(move_error(MoveError::RiverWithoutSpring));
+            }
+            return Ok((Some((pos, water_dirs[0])), None));
+        }
+
+        let (end_pos, end_dir) = match self.river_end {
+            Some(e) => e,
+            None => {
+                return Err(move_error(MoveError::RiverEnded));
+            }
+        };
+        if pos != (end_pos.0 + dy[end_dir], end_pos.1 + dx[end_dir]) {
+            return Err(move_error(MoveError::RiverNotContinued));
+        }
+
+        let exits: Vec<usize> = water_dirs
+            .into_iter()
+            .filter(|dir| *dir != (end_dir + 2) % 4)
+            .collect();
+        if exits.is_empty() {
+            // the river flows into the lake
+            return Ok((None, self.river_turn));
+        }
+        let exit = exits[0];
+        if exit == end_dir {
+            return Ok((Some((pos, exit)), self.river_turn));
+        }
+        let turn = if exit == (end_dir + 1) % 4 { 1 } else { -1 };
+        if self.river_turn == Some(turn) {
+            return Err(move_error(MoveError::RiverUTurn));
+        }
+        Ok((Some((pos, exit)), Some(turn)))
+    }
+
+    pub fn apply_tile_move(&mut self, m: &TileMove) -> Result<(), Error> {
+        let board = &self.status.board;
+        let current_tile = TileItem {
+            id: self.tile_count,
+            tile: m.tile,
+            rot: m.rot,
+            feature_starting_id: self.status.feature_num,
+            meeple_id: None,
+            meeple_pos: None,
+        };
+
+        let y = m.pos.0;
+        let x = m.pos.1;
+
+        if !m.tile.is_available(&self.expansions) {
+            return Err(move_error(MoveError::TileNotInGame { tile: m.tile }));
+        }
+
+        // the sides of the tile are looked up by `rot`, so it must be checked before anything else
+        if !(0..4).contains(&m.rot) {
+            return Err(move_error(MoveError::InvalidRotation { rot: m.rot }));
+        }
+
+        // check if the placing position is empty
+        if board.contains_key(&(y, x)) {
+            return Err(move_error(MoveError::PositionOccupied { pos: (y, x) }));
+        }
+
+        // check if there is at least one adjacent tile
+        if y != 0 || x != 0 {
+            let top_exists = board.contains_key(&(y - 1, x));
+            let bottom_exists = board.contains_key(&(y + 1, x));
+            let left_exists = board.contains_key(&(y, x - 1));
+            let right_exists = board.contains_key(&(y, x + 1));
+            if !top_exists && !bottom_exists && !left_exists && !right_exists {
+                return Err(move_error(MoveError::NoAdjacentTile { pos: (y, x) }));
+            }
+        }
+
+        // check if the sides are correct
+        if let Some(t) = board.get(&(y - 1, x)) {
+            if t.bottom() != current_tile.top() {
+                return Err(move_error(MoveError::EdgeMismatch {
+                    dir: "top".to_string(),
+                    adjacent_side: t.bottom(),
+                    tile_side: current_tile.top(),
+                }));
+            }
+        }
+        if let Some(t) = board.get(&(y + 1, x)) {
+            if t.top() != current_tile.bottom() {
+                return Err(move_error(MoveError::EdgeMismatch {
+                    dir: "bottom".to_string(),
+                    adjacent_side: t.top(),
+                    tile_side: current_tile.bottom(),
+                }));
+            }
+        }
+        if let Some(t) = board.get(&(y, x - 1)) {
+            if t.right() != current_tile.left() {
+                return Err(move_error(MoveError::EdgeMismatch {
+                    dir: "left".to_string(),
+                    adjacent_side: t.right(),
+                    tile_side: current_tile.left(),
+                }));
+            }
+        }
+        if let Some(t) = board.get(&(y, x + 1)) {
+            if t.left() != current_tile.right() {
+                return Err(move_error(MoveError::EdgeMismatch {
+                    dir: "right".to_string(),
+                    adjacent_side: t.left(),
+                    tile_side: current_tile.right(),
+                }));
+            }
+        }
+
+        let river = if m.tile.is_river() {
+            Some(self.next_river(&current_tile, (y, x))?)
+        } else {
+            None
+        };
+
+        let mut undo = self.start_undo();
+        if let Some((river_end, river_turn)) = river {
+            self.river_end = river_end;
+            self.river_turn = river_turn;
+        }
+        let mf = &mut self.status.mergeable_features;
+
+        create_mergeable_features(mf, &current_tile);
+        set_cities_to_fields(mf, &current_tile);
+
+        self.status.tile_id_to_pos.insert(current_tile.id, (y, x));
+        undo.placed_tile_id = Some(current_tile.id);
+        self.status.feature_num += current_tile.features().len() as i32;
+        self.tile_count += 1;
+        self.placed_tiles.push(m.tile);
+        undo.placed = true;
+
+        // place tile
+        self.set_board(&mut undo, (y, x), current_tile);
+
+        let board = &self.status.board;
+        let mf = &mut self.status.mergeable_features;
+
+        // merge cities first (so that the merging fields can see the merging cities as the same city)
+        let dy = [0, -1, 0, 1];
+        let dx = [1, 0, -1, 0];
+        for dir in 0..dy.len() {
+            if let Some(t) = board.get(&(y + dy[dir], x + dx[dir])) {
+                if current_tile.features_by_dir(dir)[0].feature == CityFeature {
+                    assert_eq!(
+                        t.features_by_dir((dir + 2) % 4).len(),
+                        current_tile.features_by_dir(dir).len()
+                    );
+                    merge_features(
+                        mf,
+                        t.features_by_dir((dir + 2) % 4),
+                        current_tile.features_by_dir(dir),
+                    );
+                }
+            }
+        }
+        // merge roads and fields
+        for dir in 0..dy.len() {
+            if let Some(t) = board.get(&(y + dy[dir], x + dx[dir])) {
+                if current_tile.features_by_dir(dir)[0].feature != CityFeature {
+                    assert_eq!(
+                        t.features_by_dir((dir + 2) % 4).len(),
+                        current_tile.features_by_dir(dir).len()
+                    );
+                    merge_features(
+                        mf,
+                        t.features_by_dir((dir + 2) % 4),
+                        current_tile.features_by_dir(dir),
+                    );
+                }
+            }
+        }
+
+        // update meepleable positions
+        undo.meepleable_positions = Some(std::mem::take(&mut self.status.meepleable_positions));
+        for f in &current_tile.features() {
+            // meeples can't be placed on the river
+            if f.feature != RiverFeature && mf.get_meeples(f.id as usize).is_empty() {
+                assert!(f.id >= current_tile.feature_starting_id);
+                self.status
+                    .meepleable_positions
+                    .push(f.id - current_tile.feature_starting_id);
+            }
+        }
+
+        // update open side for monastery that was placed just now
+        if m.tile.has_monastery() {
+            let mut filled_count = 0;
+            for dy in -1..2 {
+                for dx in -1..2 {
+                    if board.contains_key(&(y + dy, x + dx)) {
+                        filled_count += 1;
+                    }
+                }
+            }
+            assert!(filled_count <= 9);
+            mf.reduce_open_sides(current_tile.feature_starting_id as usize, filled_count);
+        }
+
+        // update open side for monastery that had been placed around the current tile
+        for dy in -1..2 {
+            for dx in -1..2 {
+                if dy == 0 && dx == 0 {
+                    continue;
+                }
+                if let Some(t) = board.get(&(y + dy, x + dx)) {
+                    if t.tile.has_monastery() {
+                        mf.reduce_open_sides(t.feature_starting_id as usize, 1);
+                    }
+                }
+            }
+        }
+
+        self.history.push(undo);
+
+        Ok(())
+    }
+
+    pub fn apply_meeple_move(&mut self, m: &MeepleMove) -> Result<(), Error> {
+        let y = m.tile_pos.0;
+        let x = m.tile_pos.1;
+
+        let t = match self.status.board.get(&(y, x)) {
+            Some(t) => *t,
+            None => {
+                return Err(move_error(MoveError::NoTileAtPosition { pos: (y, x) }));
+            }
+        };
+        if t.id != self.tile_count - 1 {
+            return Err(move_error(MoveError::NotPlacedTile { tile_pos: (y, x) }));
+        }
+
+        let mut undo = self.start_undo();
+        undo.complete_events = Some(std::mem::take(&mut self.status.complete_events));
+
+        if m.meeple_id != -1 {
+            let owner = meeple_owner(m.meeple_id);
+            let feature_id = t.feature_starting_id + m.meeple_pos;
+            let err = if m.meeple_id < 0
+                || owner >= self.status.player_remaining_meeples.len()
+                || (is_big_meeple(m.meeple_id)
+                    && !self.expansions.contains(&Expansion::InnsAndCathedrals))
+                || (!is_big_meeple(m.meeple_id)
+                    && m.meeple_id % MEEPLE_NUM >= self.rule_set.meeples_per_player)
+            {
+                Some(move_error(MoveError::UnknownMeeple {
+                    meeple_id: m.meeple_id,
+                }))
+            } else if self.status.player_remaining_meeples[owner].is_empty() {
+                Some(move_error(MoveError::NoMeeplesLeft { player: owner }))
+            } else if !self.status.player_remaining_meeples[owner].contains(&m.meeple_id) {
+                Some(move_error(MoveError::MeepleOnBoard {
+                    meeple_id: m.meeple_id,
+                }))
+            } else if m.meeple_pos < 0 || m.meeple_pos as usize >= t.features().len() {
+                Some(move_error(MoveError::InvalidMeeplePosition {
+                    meeple_pos: m.meeple_pos,
+                }))
+            } else if !self
+                .status
+                .mergeable_features
+                .get_meeples(feature_id as usize)
+                .is_empty()
+                || matches!(t.features().get(m.meeple_pos as usize), Some(f) if f.feature == RiverFeature)
+            {
+                Some(move_error(MoveError::FeatureOccupied {
+                    meeple_pos: m.meeple_pos,
+                }))
+            } else {
+                None
+            };
+            if let Some(e) = err {
+                self.status.complete_events = undo.complete_events.unwrap();
+                self.status
+                    .mergeable_features
+                    .rollback(undo.features_checkpoint);
+                return Err(e);
+            }
+
+            self.status
+                .mergeable_features
+                .place_meeple(feature_id as usize, m.meeple_id);
+            self.set_board(
+                &mut undo,
+                (y, x),
+                TileItem {
+                    meeple_id: Some(m.meeple_id),
+                    meeple_pos: Some(m.meeple_pos),
+                    ..t
+                },
+            );
+            assert!(!self.meeple_id_to_pos.contains_key(&m.meeple_id));
+            self.meeple_id_to_pos.insert(m.meeple_id, (y, x));
+            self.status.player_remaining_meeples[owner].remove(&m.meeple_id);
+            undo.meeples.push((m.meeple_id, None));
+        }
+
+        for f in &t.features() {
+            let mf = &mut self.status.mergeable_features;
+            if mf.is_done(f.id as usize) {
+                continue;
+            }
+            if f.feature == FieldFeature {
+                continue;
+            }
+            if mf.is_completed(f.id as usize) {
+                let sz = mf.size(f.id as usize);
+                let meeple_ids = mf.get_meeples(f.id as usize);
+                if meeple_ids.is_empty() {
+                    continue;
+                }
+                // an inn or a cathedral raises the points of a completed road or city
+                let pts = match f.feature {
+                    RoadFeature => {
+                        if mf.has_inn(f.id as usize) {
+                            (sz * 2) as i32
+                        } else {
+                            sz as i32
+                        }
+                    }
+                    CityFeature => {
+                        if mf.has_cathedral(f.id as usize) {
+                            (sz * 3) as i32
+                        } else {
+                            (sz * 2) as i32
+                        }
+                    }
+                    MonasteryFeature => 9,
+                    FieldFeature | RiverFeature => 0,
+                };
+                self.score(&meeple_ids, pts);
+                for meeple_id in &meeple_ids {
+                    self.remove_meeple_from_board(&mut undo, *meeple_id);
+                }
+                self.status.complete_events.push(CompleteEvent {
+                    feature: f.feature.clone(),
+                    meeple_ids,
+                    point: pts,
+                });
+                self.status.mergeable_features.set_as_done(f.id as usize);
+            }
+        }
+
+        // resolve meeples on adjacent monasteries
+        for dy in -1..2 {
+            for dx in -1..2 {
+                if dy == 0 && dx == 0 {
+                    continue;
+                }
+                let t = match self.status.board.get(&(y + dy, x + dx)) {
+                    Some(t) => *t,
+                    None => {
+                        continue;
+                    }
+                };
+                if t.tile.has_monastery() {
+                    let mf = &mut self.status.mergeable_features;
+                    if mf.is_completed(t.feature_starting_id as usize) {
+                        let meeple_ids = mf.get_meeples(t.feature_starting_id as usize);
+                        if meeple_ids.is_empty() {
+                            continue;
+                        }
+                        assert!(meeple_ids.len() == 1);
+                        self.score(&meeple_ids, 9);
+                        self.remove_meeple_from_board(&mut undo, meeple_ids[0]);
+                        self.status.complete_events.push(CompleteEvent {
+                            feature: MonasteryFeature,
+                            meeple_ids,
+                            point: 9,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.history.push(undo);
+
+        Ok(())
+    }
+
+    pub fn apply_discard_move(&mut self, m: &DiscardMove) -> Result<(), Error> {
+        let mut undo = self.start_undo();
+        self.discarded_tiles.push(m.tile);
+        undo.discarded = true;
+        self.history.push(undo);
+        Ok(())
+    }
+
+    // scores all the incomplete features and fields as it is done at the end of a game
+    pub fn apply_final_scoring(&mut self) {
+        let mut undo = self.start_undo();
+        undo.complete_events = Some(std::mem::take(&mut self.status.complete_events));
+
+        let mut farmed_fields = vec![];
+        let tiles: Vec<TileItem> = self.status.board.values().cloned().collect();
+        for t in &tiles {
+            let fs = t.features();
+            for f in &fs {
+                let mf = &mut self.status.mergeable_features;
+                let meeple_ids = mf.get_meeples(f.id as usize);
+                if meeple_ids.is_empty() {
+                    continue;
+                }
+
+                if f.feature != FieldFeature && mf.is_completed(f.id as usize) {
+                    continue;
+                }
+                if mf.is_done(f.id as usize) {
+                    continue;
+                }
+
+                // with the 1st edition rules, fields are scored by city after the other features
+                if f.feature == FieldFeature && self.rule_set.field_edition == FieldEdition::First {
+                    mf.set_as_done(f.id as usize);
+                    farmed_fields.push(f.id as usize);
+                    continue;
+                }
+
+                // an incomplete road with an inn or city with a cathedral is worth nothing
+                let pts = match f.feature {
+                    RoadFeature => {
+                        if mf.has_inn(f.id as usize) {
+                            0
+                        } else {
+                            let sz = mf.size(f.id as usize);
+                            sz as i32
+                        }
+                    }
+                    CityFeature => {
+                        if mf.has_cathedral(f.id as usize) {
+                            0
+                        } else {
+                            let sz = mf.size(f.id as usize);
+                            sz as i32
+                        }
+                    }
+                    MonasteryFeature => {
+                        let open_sides = mf.get_open_sides(f.id as usize);
+                        9 - open_sides
+                    }
+                    FieldFeature => {
+                        let mut p = 0;
+                        let cities = mf.get_facing_cities(f.id as usize);
+                        for city in &cities {
+                            if mf.is_completed(*city) {
+                                p += 3;
+                            }
+                        }
+                        p
+                    }
+                    RiverFeature => 0,
+                };
+                mf.set_as_done(f.id as usize);
+
+                self.score(&meeple_ids, pts);
+                self.status.complete_events.push(CompleteEvent {
+                    feature: f.feature.clone(),
+                    meeple_ids,
+                    point: pts,
+                });
+            }
+        }
+
+        self.score_fields_by_city(&farmed_fields);
+
+        let meeple_ids: Vec<i32> = self.meeple_id_to_pos.keys().cloned().collect();
+        for meeple_id in meeple_ids {
+            self.remove_meeple_from_board(&mut undo, meeple_id);
+        }
+
+        for (player, remaining_meeples) in self.status.player_remaining_meeples.iter().enumerate() {
+            assert_eq!(
+                *remaining_meeples,
+                meeples_of(player, &self.expansions, &self.rule_set)
+            );
+        }
+
+        self.history.push(undo);
+    }
+
+    // 1st edition field scoring: each completed city gives 4 points once,
+    // to the players who have the most farmers on the fields facing it
+    fn score_fields_by_city(&mut self, fields: &[usize]) {
+        let mut scored_cities: Vec<usize> = vec![];
+        for field in fields {
+            let cities = self.status.mergeable_features.get_facing_cities(*field);
+            for city in cities {
+                let mf = &mut self.status.mergeable_features;
+                if !mf.is_completed(city) || scored_cities.iter().any(|c| mf.is_same_set(*c, city))
+                {
+                    continue;
+                }
+                scored_cities.push(city);
+
+                let mut meeple_ids = vec![];
+                for f in fields {
+                    if mf
+                        .get_facing_cities(*f)
+                        .iter()
+                        .any(|c| mf.is_same_set(*c, city))
+                    {
+                        meeple_ids.append(&mut mf.get_meeples(*f));
+                    }
+                }
+                meeple_ids.sort();
+                self.score(&meeple_ids, 4);
+                self.status.complete_events.push(CompleteEvent {
+                    feature: FieldFeature,
+                    meeple_ids,
+                    point: 4,
+                });
+            }
+        }
+
+        // farmers on fields without any completed city
+        for field in fields {
+            for meeple_id in self.status.mergeable_features.get_meeples(*field) {
+                self.status.player_remaining_meeples[meeple_owner(meeple_id)].insert(meeple_id);
+            }
+        }
+    }
+
+    pub fn tileable_positions(&self, t: Tile) -> Vec<TileablePosition> {
+        let board = &self.status.board;
+
+        let tile = TileItem {
+            id: t.to_id(),
+            tile: t,
+            rot: 0,
+            feature_starting_id: 0,
+            meeple_id: None,
+            meeple_pos: None,
+        };
+
+        let mut checked: HashSet<(i32, i32)> = HashSet::new();
+        let mut tileable_positions = vec![];
+        for (y, x) in board.keys() {
+            if !checked.insert((*y, *x)) {
+                continue;
+            }
+
+            let dy = [0, -1, 0, 1];
+            let dx = [1, 0, -1, 0];
+            for di in 0..4 {
+                let ny = y + dy[di];
+                let nx = x + dx[di];
+                if board.contains_key(&(ny, nx)) {
+                    continue;
+                }
+                if !checked.insert((ny, nx)) {
+                    continue;
+                }
+
+                for rot in 0..4 {
+                    let tile = TileItem { rot, ..tile };
+
+                    if let Some(t) = board.get(&(ny - 1, nx)) {
+                        if t.bottom() != tile.top() {
+                            continue;
+                        }
+                    }
+                    if let Some(t) = board.get(&(ny + 1, nx)) {
+                        if t.top() != tile.bottom() {
+                            continue;
+                        }
+                    }
+                    if let Some(t) = board.get(&(ny, nx - 1)) {
+                        if t.right() != tile.left() {
+                            continue;
+                        }
+                    }
+                    if let Some(t) = board.get(&(ny, nx + 1)) {
+                        if t.left() != tile.right() {
+                            continue;
+                        }
+                    }
+
+                    if t.is_river() && self.next_river(&tile, (ny, nx)).is_err() {
+                        continue;
+                    }
+
+                    tileable_positions.push(TileablePosition { pos: (ny, nx), rot })
+                }
+            }
+        }
+
+        // the board is a hash map, so the positions are sorted for agents to break ties the same way every time
+        tileable_positions.sort_by_key(|tp| (tp.pos, tp.rot));
+        tileable_positions
+    }
+}
+
+#[allow(dead_code)]
+fn add_move(
+    mvs: &mut Vec<Move>,
+    tile: Tile,
+    rot: i32,
+    pos: (i32, i32),
+    meeple_id: i32,
+    meeple_pos: i32,
+) {
+    mvs.push(Move::TMove(TileMove {
+        id: -1,
+        ord: -1,
+        game_id: None,
+        player_id: -1,
+        tile,
+        rot,
+        pos,
+    }));
+    mvs.push(Move::MMove(MeepleMove {
+        id: -1,
+        ord: -1,
+        game_id: None,
+        player_id: -1,
+        meeple_id,
+        tile_pos: pos,
+        meeple_pos,
+    }));
+}
+
+#[allow(dead_code)]
+fn assert_same_status(s0: &Status, s1: &Status) {
+    assert_eq!(s0.player_points, s1.player_points);
+    assert_eq!(s0.meepleable_positions, s1.meepleable_positions);
+    assert_eq!(s0.player_remaining_meeples, s1.player_remaining_meeples);
+    assert_eq!(s0.feature_num, s1.feature_num);
+    assert_eq!(s0.tile_id_to_pos, s1.tile_id_to_pos);
+    assert_eq!(s0.complete_events, s1.complete_events);
+    assert_eq!(s0.board.len(), s1.board.len());
+    for (pos, t0) in &s0.board {
+        let t1 = s1.board.get(pos).unwrap();
+        assert_eq!(t0.tile, t1.tile);
+        assert_eq!(t0.rot, t1.rot);
+        assert_eq!(t0.meeple_id, t1.meeple_id);
+        assert_eq!(t0.meeple_pos, t1.meeple_pos);
+    }
+}
+
+#[test]
+fn game_state_undo_test() {
+    // same moves as `calculate_test_for_field0` in calculate.rs
+    let mut mvs = vec![];
+    add_move(&mut mvs, Tile::StartingTile, 0, (0, 0), -1, -1);
+    add_move(&mut mvs, Tile::TripleRoad, 1, (0, 1), 0, 4);
+    add_move(&mut mvs, Tile::Straight, 0, (-1, 1), 7, 1);
+    add_move(&mut mvs, Tile::CityCap, 2, (-1, 0), 1, 0);
+    add_move(&mut mvs, Tile::Triangle, 0, (-1, -1), 8, 0);
+    add_move(&mut mvs, Tile::Separator, 1, (-1, 2), 1, 0);
+    add_move(&mut mvs, Tile::TripleRoad, 3, (-2, 1), -1, -1);
+    add_move(&mut mvs, Tile::Separator, 0, (-1, 3), 2, 0);
+    add_move(&mut mvs, Tile::Monastery, 0, (0, 2), 7, 0);
+    add_move(&mut mvs, Tile::StartingTile, 2, (-2, 2), 1, 0);
+    add_move(&mut mvs, Tile::Straight, 0, (1, 1), 9, 2);
+    add_move(&mut mvs, Tile::VerticalSeparator, 0, (-1, 4), 1, 0);
+    add_move(&mut mvs, Tile::CityCapWithCrossroad, 0, (0, 4), 10, 0);
+    add_move(&mut mvs, Tile::TriangleWithRoad, 2, (-2, 3), 3, 2);
+    add_move(&mut mvs, Tile::TripleCity, 3, (-2, -1), -1, -1);
+    add_move(&mut mvs, Tile::Left, 3, (0, -1), 4, 0);
+    add_move(&mut mvs, Tile::Curve, 3, (0, 3), -1, -1);
+    add_move(&mut mvs, Tile::Left, 1, (0, -2), -1, -1);
+
+    let mut state = GameState::new(2, &[], &RuleSet::default());
+    for (i, mv) in mvs.iter().enumerate() {
+        state.apply(mv).unwrap();
+
+        // every prefix must be equivalent to the state built from scratch
+        let expected =
+            GameState::from_moves(&mvs[0..i + 1].to_vec(), 2, &[], &RuleSet::default()).unwrap();
+        assert_same_status(state.status(), expected.status());
+    }
+
+    // take back everything but the starting tile and replay it again
+    let full = GameState::from_moves(&mvs, 2, &[], &RuleSet::default()).unwrap();
+    for i in (2..mvs.len()).rev() {
+        state.undo();
+        let expected =
+            GameState::from_moves(&mvs[0..i].to_vec(), 2, &[], &RuleSet::default()).unwrap();
+        assert_same_status(state.status(), expected.status());
+    }
+    for mv in &mvs[2..] {
+        state.apply(mv).unwrap();
+    }
+    assert_same_status(state.status(), full.status());
+
+    // final scoring can be taken back as well
+    let before_points = state.status().player_points.clone();
+    state.apply_final_scoring();
+    assert!(state.status().player_points[0] >= before_points[0]);
+    state.undo();
+    assert_eq!(state.status().player_points, before_points);
+    assert_same_status(state.status(), full.status());
+
+    // an illegal move doesn't change anything
+    let depth = state.depth();
+    let illegal = Move::TMove(TileMove {
+        id: -1,
+        ord: -1,
+        game_id: None,
+        player_id: -1,
+        tile: Tile::Monastery,
+        rot: 0,
+        pos: (0, 0),
+    });
+    assert!(state.apply(&illegal).is_err());
+    assert_eq!(state.depth(), depth);
+    assert_same_status(state.status(), full.status());
+}
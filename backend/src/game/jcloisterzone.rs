@@ -0,0 +1,598 @@
+// JCloisterZone saved games (`.jcz`) of the base game.
+//
+//     {
+//       "appVersion": "5.12.0",
+//       "created": "2024-07-29T00:00:00Z",
+//       "setup": {
+//         "sets": {"basic": 1},
+//         "elements": {"small-follower": 7},
+//         "start": [{"tile": "BA/RCr", "x": 0, "y": 0, "rotation": "R0"}]
+//       },
+//       "players": [{"name": "Alice", "slot": 0}, {"name": "Bob", "slot": 1}],
+//       "replay": [
+//         {"type": "PLACE_TILE", "player": 1, "payload": {"tileId": "BA/RR", "rotation": "R90", "position": [1, 0]}},
+//         {"type": "DEPLOY_MEEPLE", "player": 1, "payload": {"meepleType": "small-follower", "pointer": {"position": [1, 0], "location": "N"}}},
+//         {"type": "PLACE_TILE", "player": 0, "payload": {"tileId": "BA/L", "rotation": "R0", "position": [0, 1]}},
+//         {"type": "PASS", "player": 0, "payload": {}}
+//       ]
+//     }
+//
+// positions are `[x, y]`, and rotations are clockwise as ours. a meeple is placed on a location of the tile
+// as it lies on the board: a side (`N`, `E`, `S` or `W`), the left or right half of a side seen from the centre
+// of the tile for fields (`NL`, `NR`, ...), or `CLOISTER`. a location spanning several of them joins them with `.`.
+// players are listed in seat order, and the one who places the first tile plays first.
+// JCloisterZone discards the tiles which can't be placed by itself, so discards aren't written,
+// and messages of other types (e.g., `COMMIT`) are skipped when reading
+use std::collections::BTreeMap;
+
+use rocket::serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::calculate::{calculate, is_big_meeple, Feature};
+use super::game_state::GameState;
+use super::mov::{MeepleMove, Move, TileMove};
+use super::notation::GameRecord;
+use super::rule_set::RuleSet;
+use super::tile::Tile;
+
+// the version whose format is written
+const APP_VERSION: &str = "5.12.0";
+const BASIC_SET: &str = "basic";
+const SMALL_FOLLOWER: &str = "small-follower";
+const PLACE_TILE: &str = "PLACE_TILE";
+const DEPLOY_MEEPLE: &str = "DEPLOY_MEEPLE";
+const PASS: &str = "PASS";
+const CLOISTER: &str = "CLOISTER";
+
+// JCloisterZone's ids of the base game tiles
+const TILE_IDS: [(Tile, &str); 24] = [
+    (Tile::StartingTile, "BA/RCr"),
+    (Tile::Monastery, "BA/L"),
+    (Tile::MonasteryWithRoad, "BA/LR"),
+    (Tile::CityCapWithCrossroad, "BA/CRRR"),
+    (Tile::TriangleWithRoad, "BA/CCRR"),
+    (Tile::TriangleWithRoadWithCOA, "BA/CCRR+"),
+    (Tile::Straight, "BA/RR"),
+    (Tile::CityCap, "BA/C"),
+    (Tile::Separator, "BA/CC"),
+    (Tile::TripleRoad, "BA/RRR"),
+    (Tile::Curve, "BA/Rr"),
+    (Tile::QuadrupleRoad, "BA/RRRR"),
+    (Tile::Connector, "BA/CFC"),
+    (Tile::ConnectorWithCOA, "BA/CFC+"),
+    (Tile::Left, "BA/CrR"),
+    (Tile::Right, "BA/CRr"),
+    (Tile::TripleCity, "BA/CCC"),
+    (Tile::TripleCityWithCOA, "BA/CCC+"),
+    (Tile::VerticalSeparator, "BA/CC.2"),
+    (Tile::TripleCityWithRoad, "BA/CCCR"),
+    (Tile::TripleCityWithRoadWithCOA, "BA/CCCR+"),
+    (Tile::Triangle, "BA/CF"),
+    (Tile::TriangleWithCOA, "BA/CF+"),
+    (Tile::QuadrupleCityWithCOA, "BA/CCCC+"),
+];
+
+// the sides in the order of `TileDefinition::sides`
+const SIDES: [&str; 4] = ["E", "N", "W", "S"];
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+struct SavedGame {
+    #[serde(default)]
+    app_version: String,
+    #[serde(default)]
+    created: Option<String>,
+    setup: Setup,
+    players: Vec<SavedPlayer>,
+    replay: Vec<Message>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Setup {
+    sets: BTreeMap<String, i32>,
+    #[serde(default)]
+    elements: BTreeMap<String, Value>,
+    #[serde(default)]
+    start: Vec<StartTile>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct StartTile {
+    tile: String,
+    x: i32,
+    y: i32,
+    rotation: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct SavedPlayer {
+    name: String,
+    slot: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Message {
+    #[serde(rename = "type")]
+    kind: String,
+    player: usize,
+    #[serde(default)]
+    payload: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+struct PlaceTile {
+    tile_id: String,
+    rotation: String,
+    position: [i32; 2],
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+struct DeployMeeple {
+    meeple_type: String,
+    pointer: Pointer,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Pointer {
+    position: [i32; 2],
+    location: String,
+}
+
+// reads a saved game into a record whose moves are validated by `calculate`. the result is left unknown,
+// since saved games don't have one
+pub fn read(data: &str) -> Result<GameRecord, String> {
+    let saved: SavedGame = match serde_json::from_str(data) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("invalid saved game: {}", e)),
+    };
+    if let Some(set) = saved.setup.sets.keys().find(|s| *s != BASIC_SET) {
+        return Err(format!("{} isn't supported, only the base game is", set));
+    }
+    let player_num = saved.players.len();
+    if player_num < 2 {
+        return Err("a game needs at least 2 players".to_string());
+    }
+    let mut rule_set = RuleSet::default();
+    if let Some(n) = saved.setup.elements.get(SMALL_FOLLOWER) {
+        rule_set.meeples_per_player = match n.as_i64() {
+            Some(n) => n as i32,
+            None => return Err(format!("{} must be a number", SMALL_FOLLOWER)),
+        };
+    }
+    rule_set.validate()?;
+    if let Some(s) = saved.setup.start.first() {
+        if tile_of(&s.tile) != Some(Tile::StartingTile)
+            || (s.x, s.y) != (0, 0)
+            || s.rotation != "R0"
+        {
+            return Err("the starting tile must be placed unrotated at [0, 0]".to_string());
+        }
+    }
+
+    let first_seat = match saved.replay.iter().find(|m| m.kind == PLACE_TILE) {
+        Some(m) => m.player,
+        None => 0,
+    };
+    let starting_seat = (first_seat + player_num - 1) % player_num;
+    let mut state = GameState::new(player_num, &[], &rule_set);
+    let mut moves = vec![];
+    push(
+        &mut state,
+        &mut moves,
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 0,
+            game_id: None,
+            player_id: starting_seat as i32,
+            tile: Tile::StartingTile,
+            rot: 0,
+            pos: (0, 0),
+        }),
+        "the starting tile",
+    )?;
+    let mv = empty_meeple_move(&moves).unwrap();
+    push(&mut state, &mut moves, mv, "the starting tile")?;
+
+    for (i, msg) in saved.replay.iter().enumerate() {
+        let at = format!("message {}", i);
+        if msg.player >= player_num {
+            return Err(format!("{}: there is no player {}", at, msg.player));
+        }
+        let ord = moves.len() as i32;
+        let mv = match msg.kind.as_str() {
+            PLACE_TILE => {
+                // the previous tile had no meeple placed
+                if let Some(mv) = empty_meeple_move(&moves) {
+                    push(&mut state, &mut moves, mv, &at)?;
+                }
+                let p: PlaceTile = payload(msg, &at)?;
+                let tile = match tile_of(&p.tile_id) {
+                    Some(t) => t,
+                    None => {
+                        return Err(format!("{}: tile {} isn't in the base game", at, p.tile_id))
+                    }
+                };
+                Move::TMove(TileMove {
+                    id: -1,
+                    ord: moves.len() as i32,
+                    game_id: None,
+                    player_id: msg.player as i32,
+                    tile,
+                    rot: parse_rotation(&p.rotation, &at)?,
+                    pos: (p.position[1], p.position[0]),
+                })
+            }
+            DEPLOY_MEEPLE => {
+                let tm = match moves.last() {
+                    Some(Move::TMove(tm)) => tm.clone(),
+                    _ => {
+                        return Err(format!(
+                            "{}: a meeple must be placed right after a tile",
+                            at
+                        ))
+                    }
+                };
+                let p: DeployMeeple = payload(msg, &at)?;
+                if (p.pointer.position[1], p.pointer.position[0]) != tm.pos {
+                    return Err(format!("{}: a meeple must be placed on the last tile", at));
+                }
+                let meeple_pos = match feature_at(tm.tile, tm.rot, &p.pointer.location) {
+                    Some(f) => f,
+                    None => return Err(format!("{}: invalid location {}", at, p.pointer.location)),
+                };
+                let meeple_id = match state.status().player_remaining_meeples[tm.player_id as usize]
+                    .iter()
+                    .filter(|m| !is_big_meeple(**m))
+                    .min()
+                {
+                    Some(m) => *m,
+                    None => return Err(format!("{}: no meeple is left", at)),
+                };
+                Move::MMove(MeepleMove {
+                    id: -1,
+                    ord,
+                    game_id: None,
+                    player_id: tm.player_id,
+                    meeple_id,
+                    tile_pos: tm.pos,
+                    meeple_pos,
+                })
+            }
+            PASS => match empty_meeple_move(&moves) {
+                Some(mv) => mv,
+                None => return Err(format!("{}: there is no tile to pass after", at)),
+            },
+            _ => continue,
+        };
+        push(&mut state, &mut moves, mv, &at)?;
+    }
+    if let Some(mv) = empty_meeple_move(&moves) {
+        push(&mut state, &mut moves, mv, "the last tile")?;
+    }
+
+    if let Err(e) = calculate(&moves, player_num, &[], &rule_set, true) {
+        return Err(e.detail.msg);
+    }
+    Ok(GameRecord {
+        player_names: saved.players.into_iter().map(|p| p.name).collect(),
+        first_seat,
+        date: match saved.created {
+            Some(created) => {
+                chrono::NaiveDate::parse_from_str(created.get(..10).unwrap_or(""), "%Y-%m-%d").ok()
+            }
+            None => None,
+        },
+        result: None,
+        expansions: vec![],
+        rule_set,
+        moves,
+    })
+}
+
+// writes a record of a base game. its result and the rules other than the number of meeples aren't written
+pub fn write(record: &GameRecord) -> Result<String, String> {
+    if !record.expansions.is_empty() {
+        return Err("only base games can be written".to_string());
+    }
+    let mut replay = vec![];
+    for (idx, mv) in record.moves.iter().enumerate().skip(2) {
+        let at = format!("move {}", idx);
+        match mv {
+            Move::TMove(tm) => {
+                let tile_id = match id_of(tm.tile) {
+                    Some(id) => id,
+                    None => {
+                        return Err(format!("{}: tile {:?} isn't in the base game", at, tm.tile))
+                    }
+                };
+                replay.push(message(
+                    PLACE_TILE,
+                    tm.player_id,
+                    PlaceTile {
+                        tile_id: tile_id.to_string(),
+                        rotation: format!("R{}", tm.rot.rem_euclid(4) * 90),
+                        position: [tm.pos.1, tm.pos.0],
+                    },
+                )?);
+            }
+            Move::MMove(mm) if mm.meeple_id == -1 => {
+                replay.push(message(
+                    PASS,
+                    mm.player_id,
+                    Value::Object(Default::default()),
+                )?);
+            }
+            Move::MMove(mm) => {
+                if is_big_meeple(mm.meeple_id) {
+                    return Err(format!("{}: big meeples can't be written", at));
+                }
+                let tm = match &record.moves[idx - 1] {
+                    Move::TMove(tm) => tm,
+                    _ => {
+                        return Err(format!(
+                            "{}: a meeple must be placed right after a tile",
+                            at
+                        ))
+                    }
+                };
+                let location = locations(tm.tile, tm.rot, mm.meeple_pos);
+                if location.is_empty() {
+                    return Err(format!("{}: invalid feature {}", at, mm.meeple_pos));
+                }
+                replay.push(message(
+                    DEPLOY_MEEPLE,
+                    mm.player_id,
+                    DeployMeeple {
+                        meeple_type: SMALL_FOLLOWER.to_string(),
+                        pointer: Pointer {
+                            position: [mm.tile_pos.1, mm.tile_pos.0],
+                            location: location.join("."),
+                        },
+                    },
+                )?);
+            }
+            // JCloisterZone discards them by itself
+            Move::DMove(_) => {}
+            Move::InvalidMove => return Err(format!("{} is invalid", at)),
+        }
+    }
+
+    let saved = SavedGame {
+        app_version: APP_VERSION.to_string(),
+        created: record
+            .date
+            .map(|d| format!("{}T00:00:00Z", d.format("%Y-%m-%d"))),
+        setup: Setup {
+            sets: BTreeMap::from([(BASIC_SET.to_string(), 1)]),
+            elements: BTreeMap::from([(
+                SMALL_FOLLOWER.to_string(),
+                Value::from(record.rule_set.meeples_per_player),
+            )]),
+            start: vec![StartTile {
+                tile: id_of(Tile::StartingTile).unwrap().to_string(),
+                x: 0,
+                y: 0,
+                rotation: "R0".to_string(),
+            }],
+        },
+        players: record
+            .player_names
+            .iter()
+            .enumerate()
+            .map(|(slot, name)| SavedPlayer {
+                name: name.clone(),
+                slot: slot as i32,
+            })
+            .collect(),
+        replay,
+    };
+    match serde_json::to_string_pretty(&saved) {
+        Ok(s) => Ok(s),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn tile_of(id: &str) -> Option<Tile> {
+    TILE_IDS.iter().find(|(_, i)| *i == id).map(|(t, _)| *t)
+}
+
+fn id_of(tile: Tile) -> Option<&'static str> {
+    TILE_IDS.iter().find(|(t, _)| *t == tile).map(|(_, i)| *i)
+}
+
+// the sides and halves of sides the feature of the tile touches on the board, from the north clockwise
+fn locations(tile: Tile, rot: i32, feature: i32) -> Vec<String> {
+    let d = match tile.definition() {
+        Some(d) => d,
+        None => return vec![],
+    };
+    let f = match d.features.get(feature as usize) {
+        Some(f) => f,
+        None => return vec![],
+    };
+    if f.kind == Feature::MonasteryFeature {
+        return vec![CLOISTER.to_string()];
+    }
+    let mut locations = vec![];
+    // N, E, S and W
+    for dir in [1, 0, 3, 2] {
+        let side = SIDES[dir];
+        let fs = &d.side_features[(rot.rem_euclid(4) as usize + dir) % 4];
+        let halves = if fs.len() == 1 {
+            if f.kind == Feature::FieldFeature {
+                vec![(fs[0], "L"), (fs[0], "R")]
+            } else {
+                vec![(fs[0], "")]
+            }
+        } else {
+            vec![(fs[0], "L"), (fs[1], ""), (fs[2], "R")]
+        };
+        for (i, half) in halves {
+            if i == feature as usize {
+                locations.push(format!("{}{}", side, half));
+            }
+        }
+    }
+    locations
+}
+
+// the feature at the first of the locations
+fn feature_at(tile: Tile, rot: i32, location: &str) -> Option<i32> {
+    let d = tile.definition()?;
+    let first = location.split('.').next()?;
+    if first == CLOISTER {
+        return d
+            .features
+            .iter()
+            .position(|f| f.kind == Feature::MonasteryFeature)
+            .map(|f| f as i32);
+    }
+    let dir = SIDES.iter().position(|s| first.starts_with(s))?;
+    let fs = &d.side_features[(rot.rem_euclid(4) as usize + dir) % 4];
+    let f = match (fs.len(), &first[1..]) {
+        (1, "" | "L" | "R") => fs[0],
+        (3, "L") => fs[0],
+        (3, "") => fs[1],
+        (3, "R") => fs[2],
+        _ => return None,
+    };
+    Some(f as i32)
+}
+
+fn parse_rotation(rotation: &str, at: &str) -> Result<i32, String> {
+    match rotation {
+        "R0" => Ok(0),
+        "R90" => Ok(1),
+        "R180" => Ok(2),
+        "R270" => Ok(3),
+        _ => Err(format!("{}: invalid rotation {}", at, rotation)),
+    }
+}
+
+fn payload<T: for<'de> Deserialize<'de>>(msg: &Message, at: &str) -> Result<T, String> {
+    match serde_json::from_value(msg.payload.clone()) {
+        Ok(p) => Ok(p),
+        Err(e) => Err(format!("{}: invalid {} payload: {}", at, msg.kind, e)),
+    }
+}
+
+fn message<T: Serialize>(kind: &str, player_id: i32, payload: T) -> Result<Message, String> {
+    match serde_json::to_value(payload) {
+        Ok(payload) => Ok(Message {
+            kind: kind.to_string(),
+            player: player_id as usize,
+            payload,
+        }),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// the meeple move of placing no meeple on the last tile, if it has no meeple move yet
+fn empty_meeple_move(moves: &[Move]) -> Option<Move> {
+    match moves.last() {
+        Some(Move::TMove(tm)) => Some(Move::MMove(MeepleMove {
+            id: -1,
+            ord: moves.len() as i32,
+            game_id: None,
+            player_id: tm.player_id,
+            meeple_id: -1,
+            tile_pos: tm.pos,
+            meeple_pos: -1,
+        })),
+        _ => None,
+    }
+}
+
+fn push(state: &mut GameState, moves: &mut Vec<Move>, mv: Move, at: &str) -> Result<(), String> {
+    if let Err(e) = state.apply(&mv) {
+        return Err(format!("{}: {}", at, e.detail.msg));
+    }
+    moves.push(mv);
+    Ok(())
+}
+
+#[test]
+fn jcloisterzone_test() {
+    let data = include_str!("testdata/basic.jcz");
+    let r = read(data).unwrap();
+    assert_eq!(r.player_names, vec!["Alice", "Bob"]);
+    // Bob places the first tile, so Alice places the starting tile
+    assert_eq!(r.first_seat, 1);
+    assert_eq!(r.moves[0].player_id(), 0);
+    assert_eq!(r.date, chrono::NaiveDate::from_ymd_opt(2024, 7, 29));
+    assert_eq!(r.result, None);
+    assert_eq!(r.moves.len(), 10);
+    assert_eq!(
+        r.moves[2],
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 2,
+            game_id: None,
+            player_id: 1,
+            tile: Tile::Straight,
+            rot: 1,
+            pos: (0, 1),
+        })
+    );
+    let meeples: Vec<(i32, i32, i32)> = r
+        .moves
+        .iter()
+        .filter_map(|mv| match mv {
+            Move::MMove(m) => Some((m.player_id, m.meeple_id, m.meeple_pos)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        meeples,
+        vec![(0, -1, -1), (1, 7, 1), (0, 0, 0), (1, -1, -1), (0, 1, 0)]
+    );
+
+    // written and read back, the record is the same
+    assert_eq!(read(&write(&r).unwrap()).unwrap(), r);
+
+    let e = read(&data.replace("\"basic\": 1", "\"basic\": 1, \"inns-and-cathedrals\": 1"))
+        .unwrap_err();
+    assert_eq!(
+        e,
+        "inns-and-cathedrals isn't supported, only the base game is"
+    );
+    let e = read(&data.replace("BA/L\"", "IC/L\"")).unwrap_err();
+    assert_eq!(e, "message 3: tile IC/L isn't in the base game");
+    let e = read(&data.replace("\"location\": \"NL\"", "\"location\": \"X\"")).unwrap_err();
+    assert_eq!(e, "message 8: invalid location X");
+    let e = read(&data.replace("\"position\": [0, -1]", "\"position\": [5, 5]")).unwrap_err();
+    assert!(e.starts_with("message 5: "), "{}", e);
+    assert!(read("{}").is_err());
+
+    let mut r = r;
+    r.expansions = vec![super::tile::Expansion::River];
+    assert!(write(&r).is_err());
+}
+
+#[test]
+fn locations_test() {
+    assert_eq!(locations(Tile::Straight, 1, 1), vec!["E", "W"]);
+    assert_eq!(
+        locations(Tile::Straight, 1, 0),
+        vec!["NL", "NR", "EL", "WR"]
+    );
+    assert_eq!(locations(Tile::Monastery, 0, 0), vec!["CLOISTER"]);
+    assert!(locations(Tile::Monastery, 0, 5).is_empty());
+    // every feature is read back from where it's written
+    for (tile, _) in TILE_IDS {
+        let d = tile.definition().unwrap();
+        for rot in 0..4 {
+            for f in 0..d.features.len() as i32 {
+                let location = locations(tile, rot, f).join(".");
+                assert_eq!(feature_at(tile, rot, &location), Some(f), "{:?}", tile);
+            }
+        }
+    }
+}
@@ -1,4 +1,20 @@
-#[derive(Debug)]
+// every write to the union-find is recorded so that it can be rolled back (see `rollback`)
+#[derive(Debug, Clone)]
+enum Change {
+    NewFeature,
+    Par(usize, usize),
+    Rank(usize, usize),
+    Meeples(usize, Vec<i32>),
+    OpenSides(usize, i32),
+    FacingCities(usize, Vec<usize>),
+    Done(usize, bool),
+    TileIds(usize, Vec<i32>),
+    Coa(usize, i32),
+    Inn(usize, bool),
+    Cathedral(usize, bool),
+}
+
+#[derive(Debug, Clone)]
 pub struct MergeableFeature {
     par: Vec<usize>,
     rank: Vec<usize>,
@@ -8,6 +24,9 @@ pub struct MergeableFeature {
     done: Vec<bool>,
     tile_ids: Vec<Vec<i32>>,
     coa: Vec<i32>,
+    inn: Vec<bool>,       // for road
+    cathedral: Vec<bool>, // for city
+    history: Vec<Change>,
 }
 
 impl MergeableFeature {
@@ -21,9 +40,19 @@ impl MergeableFeature {
             done: vec![],
             tile_ids: vec![],
             coa: vec![],
+            inn: vec![],
+            cathedral: vec![],
+            history: vec![],
         }
     }
-    pub fn new_feature(&mut self, tile_id: i32, open_side: i32, with_coa: bool) {
+    pub fn new_feature(
+        &mut self,
+        tile_id: i32,
+        open_side: i32,
+        with_coa: bool,
+        with_inn: bool,
+        with_cathedral: bool,
+    ) {
         self.par.push(self.par.len());
         self.rank.push(1);
         self.meeples.push(vec![]);
@@ -32,17 +61,23 @@ impl MergeableFeature {
         self.done.push(false);
         self.tile_ids.push(vec![tile_id]);
         self.coa.push(if with_coa { 1 } else { 0 });
+        self.inn.push(with_inn);
+        self.cathedral.push(with_cathedral);
+        self.history.push(Change::NewFeature);
     }
     pub fn set_cities(&mut self, x: usize, city: usize) {
+        self.history
+            .push(Change::FacingCities(x, self.facing_cities[x].clone()));
         self.facing_cities[x].push(city);
     }
+    // no path compression: sets are united by size so the trees stay shallow,
+    // and finding a root doesn't write anything that would have to be rolled back
     fn root(&mut self, x: usize) -> usize {
-        if x != self.par[x] {
-            let par = self.par[x];
-            let r = self.root(par);
-            self.par[x] = r;
+        let mut x = x;
+        while x != self.par[x] {
+            x = self.par[x];
         }
-        self.par[x]
+        x
     }
     pub fn unite(&mut self, x: usize, y: usize) {
         let mut x = self.root(x);
@@ -52,13 +87,29 @@ impl MergeableFeature {
         }
         if x == y {
             // when merging already same set, only open_size reduced by 2
+            self.history.push(Change::OpenSides(y, self.open_sides[y]));
             self.open_sides[y] -= 2;
             return;
         }
+        self.history.push(Change::OpenSides(y, self.open_sides[y]));
+        self.history.push(Change::Par(x, self.par[x]));
+        self.history.push(Change::Rank(y, self.rank[y]));
+        self.history.push(Change::Coa(y, self.coa[y]));
+        self.history.push(Change::Inn(y, self.inn[y]));
+        self.history.push(Change::Cathedral(y, self.cathedral[y]));
+        self.history
+            .push(Change::Meeples(y, self.meeples[y].clone()));
+        self.history
+            .push(Change::TileIds(y, self.tile_ids[y].clone()));
+        self.history
+            .push(Change::FacingCities(y, self.facing_cities[y].clone()));
+
         self.open_sides[y] = self.open_sides[y] + self.open_sides[x] - 2;
         self.par[x] = y;
         self.rank[y] += self.rank[x];
         self.coa[y] += self.coa[x];
+        self.inn[y] |= self.inn[x];
+        self.cathedral[y] |= self.cathedral[x];
 
         // merge meeples
         let mut v = vec![];
@@ -97,6 +148,8 @@ impl MergeableFeature {
     pub fn place_meeple(&mut self, x: usize, meeple_id: i32) {
         let x = self.root(x);
         assert_eq!(self.meeples[x].len(), 0);
+        self.history
+            .push(Change::Meeples(x, self.meeples[x].clone()));
         self.meeples[x].push(meeple_id);
     }
     #[allow(unused)]
@@ -120,16 +173,27 @@ impl MergeableFeature {
     // return the number of unique tiles & the number of coa
     pub fn size(&mut self, x: usize) -> usize {
         let x = self.root(x);
-        self.tile_ids[x].sort();
-        self.tile_ids[x].dedup();
-        self.tile_ids[x].len() + self.coa[x] as usize
+        let mut tile_ids = self.tile_ids[x].clone();
+        tile_ids.sort();
+        tile_ids.dedup();
+        tile_ids.len() + self.coa[x] as usize
+    }
+    pub fn has_inn(&mut self, x: usize) -> bool {
+        let x = self.root(x);
+        self.inn[x]
+    }
+    pub fn has_cathedral(&mut self, x: usize) -> bool {
+        let x = self.root(x);
+        self.cathedral[x]
     }
     pub fn reduce_open_sides(&mut self, x: usize, count: i32) {
         let x = self.root(x);
+        self.history.push(Change::OpenSides(x, self.open_sides[x]));
         self.open_sides[x] -= count;
     }
     pub fn set_as_done(&mut self, x: usize) {
         let x = self.root(x);
+        self.history.push(Change::Done(x, self.done[x]));
         self.done[x] = true;
     }
     pub fn is_done(&mut self, x: usize) -> bool {
@@ -140,4 +204,37 @@ impl MergeableFeature {
         let x = self.root(x);
         self.tile_ids[x].clone()
     }
+    // returns a point that `rollback` can restore to
+    pub fn checkpoint(&self) -> usize {
+        self.history.len()
+    }
+    // undoes every change made after `checkpoint` was taken
+    pub fn rollback(&mut self, checkpoint: usize) {
+        while self.history.len() > checkpoint {
+            match self.history.pop().unwrap() {
+                Change::NewFeature => {
+                    self.par.pop();
+                    self.rank.pop();
+                    self.meeples.pop();
+                    self.open_sides.pop();
+                    self.facing_cities.pop();
+                    self.done.pop();
+                    self.tile_ids.pop();
+                    self.coa.pop();
+                    self.inn.pop();
+                    self.cathedral.pop();
+                }
+                Change::Par(x, v) => self.par[x] = v,
+                Change::Rank(x, v) => self.rank[x] = v,
+                Change::Meeples(x, v) => self.meeples[x] = v,
+                Change::OpenSides(x, v) => self.open_sides[x] = v,
+                Change::FacingCities(x, v) => self.facing_cities[x] = v,
+                Change::Done(x, v) => self.done[x] = v,
+                Change::TileIds(x, v) => self.tile_ids[x] = v,
+                Change::Coa(x, v) => self.coa[x] = v,
+                Change::Inn(x, v) => self.inn[x] = v,
+                Change::Cathedral(x, v) => self.cathedral[x] = v,
+            }
+        }
+    }
 }
@@ -1,13 +1,20 @@
+pub mod agent;
 pub mod board;
 pub mod calculate;
 pub mod calculate_next_move;
 pub mod debug_moves;
 pub mod decoder;
 pub mod evaluate;
+pub mod expectimax;
+pub mod game_state;
+pub mod jcloisterzone;
+pub mod mcts;
 pub mod mergeable_feature;
 pub mod mov;
+pub mod notation;
 pub mod rating;
-pub mod solver;
+pub mod render;
+pub mod rule_set;
 pub mod tile;
 
 use diesel::pg::PgConnection;
@@ -17,15 +24,16 @@ use diesel::r2d2::Pool;
 use rocket::serde::{Deserialize, Serialize};
 
 use crate::database;
-use crate::error::{bad_request_error, Error};
-use crate::game::rating::calculate_rating;
-use crate::game::solver::SolveResult;
+use crate::error::{
+    bad_request_error, forbidden_error, internal_server_error, move_error, Error, MoveError,
+};
+use crate::game::rating::{calculate_draw_rating, calculate_rating};
 use crate::game::tile::to_tile;
 
-use self::board::{Board, BoardTile};
+use self::board::Board;
 use self::calculate::calculate;
-use self::solver::solve;
-use self::tile::Tile;
+use self::rule_set::{FieldEdition, RuleSet};
+use self::tile::{Expansion, Tile};
 use mov::Move::*;
 use mov::{DiscardMove, MeepleMove, TileMove};
 use rand::Rng;
@@ -73,6 +81,103 @@ pub struct Game {
     pub after_player1_rating: Option<i32>,
     pub first_player_id: Option<i32>,
     pub winner_player_id: Option<i32>,
+    pub player_ids: Vec<i32>,
+    pub player_names: Vec<String>,
+    pub player_colors: Vec<i32>,
+    pub player_points: Vec<i32>,
+    pub expansions: Vec<String>,
+    pub field_edition: String,
+    pub meeples_per_player: i32,
+    pub tie_is_draw: bool,
+    pub redraw_on_discard: bool,
+    // kept secret until the game ends, since it tells the tiles to be drawn (see `get_deck`)
+    #[serde(skip_serializing)]
+    pub seed: i64,
+    pub challenge_id: Option<i32>,
+    // the agent playing each seat (see `agent::by_name`), or None for human players
+    pub player_agents: Vec<Option<String>>,
+    // the game ended in a draw, so `winner_player_id` is None
+    pub is_draw: bool,
+}
+
+impl Game {
+    pub fn player_num(&self) -> usize {
+        self.player_ids.len()
+    }
+
+    // index of the player in `player_ids` (which is also the player's index in `Status`)
+    pub fn seat(&self, player_id: i32) -> Option<usize> {
+        self.player_ids.iter().position(|pid| *pid == player_id)
+    }
+
+    pub fn enabled_expansions(&self) -> Vec<Expansion> {
+        self.expansions
+            .iter()
+            .filter_map(|e| Expansion::from_name(e))
+            .collect()
+    }
+
+    pub fn rule_set(&self) -> RuleSet {
+        RuleSet {
+            field_edition: match FieldEdition::from_name(&self.field_edition) {
+                Some(e) => e,
+                None => FieldEdition::Third,
+            },
+            meeples_per_player: self.meeples_per_player,
+            tie_is_draw: self.tie_is_draw,
+            redraw_on_discard: self.redraw_on_discard,
+        }
+    }
+
+    // the agent playing for `player_id`, if the player is a bot
+    pub fn agent_of(&self, player_id: i32) -> Option<&str> {
+        match self.seat(player_id) {
+            Some(seat) => self.player_agents[seat].as_deref(),
+            None => None,
+        }
+    }
+
+    // whether the current player is a bot, whose move is made by `wait_ai_move`
+    pub fn is_agent_turn(&self) -> bool {
+        match self.current_player_id {
+            Some(pid) => self.agent_of(pid).is_some(),
+            None => false,
+        }
+    }
+
+    // player who plays after `player_id`
+    pub fn next_player_id(&self, player_id: i32) -> i32 {
+        match self.seat(player_id) {
+            Some(seat) => self.player_ids[(seat + 1) % self.player_num()],
+            None => panic!("player {} is not in game {}", player_id, self.id),
+        }
+    }
+}
+
+// a difficulty level of the AI, which is the agent a bot plays with (see `agent::LEVELS`)
+#[derive(Serialize, Queryable, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = schema::ai_level)]
+pub struct AILevel {
+    pub name: String,
+    // the rating of the AI at this level, which rated games against it change
+    pub rating: i32,
+}
+
+// how a new game is dealt
+#[derive(Clone, Debug)]
+pub struct Deal {
+    // the deck is shuffled with it
+    pub seed: i64,
+    pub first_seat: usize,
+    pub challenge_id: Option<i32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Deck {
+    pub seed: i64,
+    pub tiles: Vec<Tile>,
 }
 
 #[derive(Serialize, Deserialize, Queryable, Clone, PartialEq, Debug)]
@@ -95,7 +200,18 @@ pub struct TileMoveResult {
     pub meepleable_positions: Vec<i32>,
 }
 
-#[derive(Serialize, Queryable, Clone)]
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct GameEnd {
+    // None for a draw
+    pub winner_player_id: Option<i32>,
+    pub is_draw: bool,
+    pub player_points: Vec<i32>,
+    // events of the final scoring
+    pub complete_events: Vec<CompleteEvent>,
+}
+
+#[derive(Serialize, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct CreateMoveResult {
     pub tile_move: mov::Move,
@@ -103,10 +219,21 @@ pub struct CreateMoveResult {
     pub complete_events: Vec<CompleteEvent>,
     pub current_tile_id: i32,
     pub next_tile_id: i32,
+    // set when the move was the last one of the game
+    pub game_end: Option<GameEnd>,
 }
 
 pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 
+// the meeple move, its complete events, the current and next tile ids after the move, and the end of the game if it ended
+type MeepleMoveOutcome = (mov::Move, Vec<CompleteEvent>, i32, i32, Option<GameEnd>);
+// moves made by AI, complete events of them, and the end of the game if it ended
+type AIMoveOutcome = (Vec<mov::Move>, Vec<CompleteEvent>, Option<GameEnd>);
+
+// the player of seat i in an imported game is `IMPORTED_PLAYER_ID - i`. these players aren't registered, but have
+// rows of their own (see the imported-player migration)
+const IMPORTED_PLAYER_ID: i32 = -2;
+
 pub fn get_waiting_games(db: &DbPool) -> Result<Vec<WaitingGame>, Error> {
     database::get_waiting_games(db)
 }
@@ -115,7 +242,21 @@ pub fn create_waiting_game(db: &DbPool, player_id: i32) -> Result<WaitingGame, E
     database::create_waiting_game(db, player_id)
 }
 
-pub fn update_waiting_game(db: &DbPool, id: i32, game_id: i32) -> Result<WaitingGame, Error> {
+// the player who found the waiting game tells its owner which game they are matched in,
+// so both of them must play in that game
+pub fn update_waiting_game(
+    db: &DbPool,
+    caller_id: i32,
+    id: i32,
+    game_id: i32,
+) -> Result<WaitingGame, Error> {
+    let waiting_game = database::get_waiting_game(db, id)?;
+    let gm = database::get_game(db, game_id)?;
+    if gm.seat(caller_id).is_none() || gm.seat(waiting_game.player_id).is_none() {
+        return Err(forbidden_error(
+            "waiting games can only be matched with games both players play in".to_string(),
+        ));
+    }
     database::update_waiting_game(db, id, game_id)
 }
 
@@ -125,92 +266,217 @@ pub fn delete_waiting_games(db: &DbPool, player_id: i32) -> Result<(), Error> {
 
 pub fn create_game(
     db: &DbPool,
-    player0_id: i32,
-    player1_id: i32,
-    player0_color: i32,
-    player1_color: i32,
+    player_ids: Vec<i32>,
+    player_colors: Vec<i32>,
     is_rated: bool,
+    expansions: Vec<Expansion>,
+    rule_set: RuleSet,
+    // a game whose deck is used again, so that the same tiles are drawn in the same order
+    rematch_of: Option<i32>,
+    // the level the bots play at, instead of their own agents
+    ai_level: Option<String>,
 ) -> Result<Game, Error> {
     let mut rng = rand::thread_rng();
-    let first_player_id = if rng.gen_range(0..2) < 1 {
-        player0_id
-    } else {
-        player1_id
+    let seed = match rematch_of {
+        Some(gmid) => {
+            let gm = database::get_game(db, gmid)?;
+            // otherwise the rematch would tell the tiles still to be drawn in the original game
+            check_deck_revealed(db, &gm)?;
+            gm.seed
+        }
+        None => rng.gen(),
     };
-    let second_player_id = if first_player_id == player0_id {
-        player1_id
-    } else {
-        player0_id
+    let deal = Deal {
+        seed,
+        first_seat: rng.gen_range(0..player_ids.len().max(1)),
+        challenge_id: None,
     };
+    create_dealt_game(
+        db,
+        player_ids,
+        player_colors,
+        is_rated,
+        expansions,
+        rule_set,
+        deal,
+        ai_level,
+    )
+}
 
-    let tiles = tile::tiles();
-    let cur_tile = tiles[rng.gen_range(0..tiles.len())];
-    let rem_tiles = tile::remaining_tiles(vec![cur_tile]);
-    let next_tile = rem_tiles[rng.gen_range(0..rem_tiles.len())];
+// creates a game with the given deck and first player, e.g. for a daily challenge
+pub fn create_dealt_game(
+    db: &DbPool,
+    player_ids: Vec<i32>,
+    player_colors: Vec<i32>,
+    is_rated: bool,
+    expansions: Vec<Expansion>,
+    rule_set: RuleSet,
+    deal: Deal,
+    ai_level: Option<String>,
+) -> Result<Game, Error> {
+    let player_num = player_ids.len();
+    if !(2..=5).contains(&player_num) {
+        return Err(bad_request_error(
+            "a game must have 2 to 5 players".to_string(),
+        ));
+    }
+    if player_colors.len() != player_num {
+        return Err(bad_request_error(
+            "the number of colors must be the same as the number of players".to_string(),
+        ));
+    }
+    if is_rated && player_num != 2 {
+        return Err(bad_request_error(
+            "only two-player games can be rated".to_string(),
+        ));
+    }
+    if let Err(msg) = rule_set.validate() {
+        return Err(bad_request_error(msg));
+    }
+    if let Some(level) = &ai_level {
+        if !agent::LEVELS.contains(&level.as_str()) {
+            return Err(bad_request_error(format!("unknown AI level {}", level)));
+        }
+    }
 
-    let player0_name = match database::get_player(db, player0_id) {
-        Ok(p) => p.name,
-        Err(e) => {
-            return Err(e);
+    let first_seat = deal.first_seat % player_num;
+    let first_player_id = player_ids[first_seat];
+    // the player before the first player places the starting tile
+    let second_player_id = player_ids[(first_seat + player_num - 1) % player_num];
+    // the player after the first player draws `next_tile`
+    let next_player_id = player_ids[(first_seat + 1) % player_num];
+
+    let starting_tile = tile::starting_tile(&expansions);
+    let deck = tile::deck(deal.seed as u64, &expansions);
+    let cur_tile = tile::draw(&deck, &[starting_tile], &expansions);
+    let next_tile = tile::draw(&deck, &[starting_tile, cur_tile], &expansions);
+
+    let mut player_names = vec![];
+    // bots keep playing with the agent they had when the game started
+    let mut player_agents = vec![];
+    for player_id in &player_ids {
+        match database::get_player(db, *player_id) {
+            Ok(p) => {
+                player_names.push(p.name);
+                player_agents.push(match (p.agent, &ai_level) {
+                    (Some(_), Some(level)) => Some(level.clone()),
+                    (agent, _) => agent,
+                });
+            }
+            Err(e) => {
+                return Err(e);
+            }
         }
-    };
-    let player1_name = match database::get_player(db, player1_id) {
-        Ok(p) => p.name,
-        Err(e) => {
-            return Err(e);
+    }
+    if ai_level.is_some() && player_agents.iter().all(|a| a.is_none()) {
+        return Err(bad_request_error(
+            "an AI level is only for games with a bot".to_string(),
+        ));
+    }
+    for name in player_agents.iter().flatten() {
+        match agent::by_name(name) {
+            Some(a) if a.supports(player_num) => {}
+            Some(_) => {
+                return Err(bad_request_error(format!(
+                    "{} bots can't play games of {} players",
+                    name, player_num
+                )));
+            }
+            None => {
+                return Err(internal_server_error(format!("unknown agent {}", name)));
+            }
         }
-    };
+    }
 
-    let g = match database::create_game(
-        db,
-        player0_id,
-        player1_id,
-        Some(next_tile.to_id()),
-        Some(second_player_id),
-        Some(cur_tile.to_id()),
-        Some(first_player_id),
-        player0_name,
-        player1_name,
-        player0_color,
-        player1_color,
-        is_rated,
-        Some(first_player_id),
-    ) {
-        Ok(g) => g,
-        Err(e) => {
-            return Err(e);
-        }
-    };
+    // the game is created with its starting moves, so that a failed insert doesn't leave a game without moves
+    let conn = &mut db.get().unwrap();
+    conn.transaction(|conn| {
+        let g = database::insert_game(
+            conn,
+            player_ids,
+            Some(next_tile.to_id()),
+            Some(next_player_id),
+            Some(cur_tile.to_id()),
+            Some(first_player_id),
+            player_names,
+            player_colors,
+            is_rated,
+            Some(first_player_id),
+            expansions.iter().map(|e| e.name()).collect(),
+            &rule_set,
+            deal.seed,
+            deal.challenge_id,
+            player_agents,
+        )?;
+
+        database::insert_move(
+            conn,
+            TMove(TileMove {
+                id: -1, // ignored
+                ord: 0,
+                game_id: Some(g.id),
+                player_id: second_player_id,
+                tile: starting_tile,
+                rot: 0,
+                pos: (0, 0),
+            }),
+        )?;
+        database::insert_move(
+            conn,
+            MMove(MeepleMove {
+                id: -1, // ignored
+                ord: 1,
+                game_id: Some(g.id),
+                player_id: second_player_id,
+                meeple_id: -1,
+                tile_pos: (0, 0),
+                meeple_pos: -1,
+            }),
+        )?;
 
-    let mv0 = TMove(TileMove {
-        id: -1, // ignored
-        ord: 0,
-        game_id: Some(g.id),
-        player_id: second_player_id,
-        tile: StartingTile,
-        rot: 0,
-        pos: (0, 0),
-    });
-    let mv1 = MMove(MeepleMove {
-        id: -1, // ignored
-        ord: 1,
-        game_id: Some(g.id),
-        player_id: second_player_id,
-        meeple_id: -1,
-        tile_pos: (0, 0),
-        meeple_pos: -1,
-    });
+        Ok(g)
+    })
+}
 
-    match database::create_move(db, mv0.clone()) {
-        Err(e) => return Err(e),
-        _ => {}
+// the tile drawn from the game's deck after `out_tiles`
+fn draw_tile(gm: &Game, out_tiles: &[Tile]) -> Tile {
+    let expansions = gm.enabled_expansions();
+    tile::draw(
+        &tile::deck(gm.seed as u64, &expansions),
+        out_tiles,
+        &expansions,
+    )
+}
+
+// checks that the game is still going on, that it's `player_id`'s turn, and that `tile` is the tile drawn for the turn
+fn validate_turn(gm: &Game, player_id: i32, tile: Option<Tile>) -> Result<(), Error> {
+    if gm.winner_player_id.is_some() || gm.is_draw || gm.current_tile_id == Some(Invalid.to_id()) {
+        return Err(move_error(MoveError::GameEnded));
     }
-    match database::create_move(db, mv1.clone()) {
-        Err(e) => return Err(e),
-        _ => {}
+    if gm.current_player_id != Some(player_id) {
+        return Err(move_error(MoveError::NotCurrentPlayer {
+            player_id,
+            current_player_id: gm.current_player_id,
+        }));
     }
+    if let Some(tile) = tile {
+        let current_tile = match gm.current_tile_id {
+            Some(tid) => to_tile(tid),
+            None => Invalid,
+        };
+        if tile != current_tile {
+            return Err(move_error(MoveError::TileNotCurrent { tile, current_tile }));
+        }
+    }
+    Ok(())
+}
 
-    Ok(g)
+// checks that the player in `seat` places one of their own meeples (or none)
+fn validate_meeple_owner(seat: Option<usize>, meeple_id: i32) -> Result<(), Error> {
+    if meeple_id >= 0 && seat != Some(calculate::meeple_owner(meeple_id)) {
+        return Err(move_error(MoveError::NotOwnMeeple { meeple_id }));
+    }
+    Ok(())
 }
 
 pub fn create_move(
@@ -223,30 +489,9 @@ pub fn create_move(
     meeple_id: i32,
     meeple_pos: i32,
 ) -> Result<CreateMoveResult, Error> {
-    let tm = create_tile_move(db, game_id, player_id, tile, rot, pos)?;
-    let (mm, complete_events, current_tile_id, next_tile_id) =
-        create_meeple_move(db, game_id, player_id, meeple_id, pos, meeple_pos)?;
-
-    Ok(CreateMoveResult {
-        tile_move: tm,
-        meeple_move: mm,
-        complete_events,
-        current_tile_id,
-        next_tile_id,
-    })
-}
-
-pub fn create_tile_move(
-    db: &DbPool,
-    game_id: Option<i32>,
-    player_id: i32,
-    tile: tile::Tile,
-    rot: i32,
-    pos: (i32, i32),
-) -> Result<mov::Move, Error> {
-    // dangling move for voting
+    // dangling moves for voting
     if let None = game_id {
-        return database::create_move(
+        let tm = database::create_move(
             db,
             TMove(TileMove {
                 id: -1, // ignored
@@ -257,10 +502,65 @@ pub fn create_tile_move(
                 rot,
                 pos,
             }),
-        );
+        )?;
+        let mm = database::create_move(
+            db,
+            MMove(MeepleMove {
+                id: -1, // ignored
+                ord: -1,
+                game_id,
+                player_id,
+                meeple_id,
+                tile_pos: pos,
+                meeple_pos,
+            }),
+        )?;
+        return Ok(CreateMoveResult {
+            tile_move: tm,
+            meeple_move: mm,
+            complete_events: vec![],
+            current_tile_id: -1,
+            next_tile_id: -1,
+            game_end: None,
+        });
     }
 
-    let mut moves = match database::list_moves(db, game_id.unwrap(), None) {
+    // the tile move is only saved with its meeple move, so that a rejected meeple move can be made again
+    let conn = &mut db.get().unwrap();
+    conn.transaction(|conn| {
+        let tm = append_tile_move(conn, game_id, player_id, tile, rot, pos)?;
+        let (mm, complete_events, current_tile_id, next_tile_id, game_end) =
+            append_meeple_move(conn, game_id, player_id, meeple_id, pos, meeple_pos)?;
+
+        Ok(CreateMoveResult {
+            tile_move: tm,
+            meeple_move: mm,
+            complete_events,
+            current_tile_id,
+            next_tile_id,
+            game_end,
+        })
+    })
+}
+
+// the game is locked until the move is saved, so that a concurrent move is validated against this one
+fn append_tile_move(
+    conn: &mut database::DbConn,
+    game_id: Option<i32>,
+    player_id: i32,
+    tile: tile::Tile,
+    rot: i32,
+    pos: (i32, i32),
+) -> Result<mov::Move, Error> {
+    let gm = match database::lock_game(conn, game_id.unwrap()) {
+        Ok(game) => game,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    validate_turn(&gm, player_id, Some(tile))?;
+
+    let mut moves = match database::find_moves(conn, game_id.unwrap(), None) {
         Ok(mvs) => mvs,
         Err(e) => {
             return Err(e);
@@ -270,16 +570,10 @@ pub fn create_tile_move(
     let last_move = moves.last().unwrap();
 
     match last_move {
-        TMove(_) => {
-            return Err(bad_request_error(
-                "move before a tile move must not be a tile move".to_string(),
-            ))
-        }
+        TMove(_) => return Err(move_error(MoveError::TileMoveAfterTileMove)),
         MMove(mm) => {
             if mm.player_id == player_id {
-                return Err(bad_request_error(
-                    "player of the previous meeple move must not be the same player who is going to play".to_string(),
-                ));
+                return Err(move_error(MoveError::WrongTurn { player_id }));
             }
         }
         _ => {}
@@ -298,9 +592,15 @@ pub fn create_tile_move(
     });
     moves.push(mv.clone());
 
-    calculate::calculate(&moves, false)?;
+    calculate::calculate(
+        &moves,
+        gm.player_num(),
+        &gm.enabled_expansions(),
+        &gm.rule_set(),
+        false,
+    )?;
 
-    database::create_move(db, mv)
+    database::insert_move(conn, mv)
 }
 
 pub fn try_create_tile_move(
@@ -311,6 +611,14 @@ pub fn try_create_tile_move(
     rot: i32,
     pos: (i32, i32),
 ) -> Result<TileMoveResult, Error> {
+    let gm = match database::get_game(db, game_id.unwrap()) {
+        Ok(game) => game,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    validate_turn(&gm, player_id, Some(tile))?;
+
     let mut moves = match database::list_moves(db, game_id.unwrap(), None) {
         Ok(mvs) => mvs,
         Err(e) => {
@@ -321,16 +629,10 @@ pub fn try_create_tile_move(
     let last_move = moves.last().unwrap();
 
     match last_move {
-        TMove(_) => {
-            return Err(bad_request_error(
-                "move before a tile move must not be a tile move".to_string(),
-            ))
-        }
+        TMove(_) => return Err(move_error(MoveError::TileMoveAfterTileMove)),
         MMove(mm) => {
             if mm.player_id == player_id {
-                return Err(bad_request_error(
-                    "player of the previous meeple move must not be the same player who is going to play".to_string(),
-                ));
+                return Err(move_error(MoveError::WrongTurn { player_id }));
             }
         }
         _ => {}
@@ -349,51 +651,37 @@ pub fn try_create_tile_move(
     });
     moves.push(mv.clone());
 
-    let s = calculate::calculate(&moves, false)?;
+    let s = calculate::calculate(
+        &moves,
+        gm.player_num(),
+        &gm.enabled_expansions(),
+        &gm.rule_set(),
+        false,
+    )?;
 
     Ok(TileMoveResult {
         meepleable_positions: s.meepleable_positions,
     })
 }
 
-pub fn create_meeple_move(
-    db: &DbPool,
+fn append_meeple_move(
+    conn: &mut database::DbConn,
     game_id: Option<i32>,
     player_id: i32,
     meeple_id: i32,
     tile_pos: (i32, i32),
     meeple_pos: i32,
-) -> Result<(mov::Move, Vec<CompleteEvent>, i32, i32), Error> {
-    // dangling move for voting
-    if let None = game_id {
-        return Ok((
-            database::create_move(
-                db,
-                MMove(MeepleMove {
-                    id: -1, // ignored
-                    ord: -1,
-                    game_id,
-                    player_id,
-                    meeple_id,
-                    tile_pos,
-                    meeple_pos,
-                }),
-            )?,
-            vec![],
-            -1,
-            -1,
-        ));
-    }
-
-    let gm = match database::get_game(db, game_id.unwrap()) {
+) -> Result<MeepleMoveOutcome, Error> {
+    let gm = match database::lock_game(conn, game_id.unwrap()) {
         Ok(game) => game,
         Err(e) => {
             return Err(e);
         }
     };
+    validate_turn(&gm, player_id, None)?;
+    validate_meeple_owner(gm.seat(player_id), meeple_id)?;
 
-    let mut rng = rand::thread_rng();
-    let mut moves = match database::list_moves(db, game_id.unwrap(), None) {
+    let mut moves = match database::find_moves(conn, game_id.unwrap(), None) {
         Ok(mvs) => mvs,
         Err(e) => {
             return Err(e);
@@ -404,11 +692,7 @@ pub fn create_meeple_move(
 
     match last_move {
         TMove(_) => {}
-        _ => {
-            return Err(bad_request_error(
-                "move before a meeple move must be a tile move".to_string(),
-            ))
-        }
+        _ => return Err(move_error(MoveError::MeepleMoveWithoutTileMove)),
     }
 
     let ord = last_move.ord() + 1;
@@ -426,8 +710,14 @@ pub fn create_meeple_move(
 
     let mut complete_events = vec![];
 
-    let res = calculate::calculate(&moves, false);
-    let (player0_point, player1_point) = match res {
+    let res = calculate::calculate(
+        &moves,
+        gm.player_num(),
+        &gm.enabled_expansions(),
+        &gm.rule_set(),
+        false,
+    );
+    let player_points = match res {
         Ok(s) => {
             for e in &s.complete_events {
                 complete_events.push(CompleteEvent {
@@ -436,7 +726,7 @@ pub fn create_meeple_move(
                     point: e.point,
                 })
             }
-            (s.player0_point, s.player1_point)
+            s.player_points
         }
         Err(e) => {
             return Err(e);
@@ -452,7 +742,7 @@ pub fn create_meeple_move(
         }
         None => {}
     }
-    for mv in moves {
+    for mv in &moves {
         match mv {
             mov::Move::TMove(tm) => {
                 out_tiles.push(tm.tile.clone());
@@ -463,32 +753,17 @@ pub fn create_meeple_move(
             _ => {}
         }
     }
-    let remaining_tiles = tile::remaining_tiles(out_tiles.clone());
-
-    let next_tile = if remaining_tiles.len() == 0 {
-        Invalid
-    } else {
-        remaining_tiles[rng.gen_range(0..remaining_tiles.len())]
-    };
+    let next_tile = draw_tile(&gm, &out_tiles);
 
-    let cur_player_id = if player_id == gm.player0_id {
-        gm.player1_id
-    } else {
-        gm.player0_id
-    };
-    let next_player_id = if player_id == gm.player0_id {
-        gm.player0_id
-    } else {
-        gm.player1_id
-    };
+    let cur_player_id = gm.next_player_id(player_id);
+    let next_player_id = gm.next_player_id(cur_player_id);
 
-    match database::update_game(
-        db,
+    database::update_game(
+        conn,
         game_id.unwrap(),
         next_tile.to_id(),
         next_player_id,
-        player0_point,
-        player1_point,
+        player_points,
         gm.next_tile_id.unwrap(),
         cur_player_id,
         gm.before_player0_rating,
@@ -497,18 +772,22 @@ pub fn create_meeple_move(
         gm.after_player1_rating,
         gm.first_player_id,
         gm.winner_player_id,
-    ) {
-        Err(e) => {
-            return Err(e);
-        }
-        Ok(_) => {}
-    }
+    )?;
+    let mv = database::insert_move(conn, mv)?;
+
+    // there's no tile left for the next player, so the game ends with this move
+    let game_end = if gm.next_tile_id == Some(Invalid.to_id()) {
+        Some(finish_game(conn, &gm, &moves)?)
+    } else {
+        None
+    };
 
     Ok((
-        database::create_move(db, mv)?,
+        mv,
         complete_events,
         gm.next_tile_id.unwrap(),
         next_tile.to_id(),
+        game_end,
     ))
 }
 
@@ -517,10 +796,10 @@ pub fn create_discard_move(
     game_id: Option<i32>,
     player_id: i32,
     tile: tile::Tile,
-) -> Result<mov::Move, Error> {
+) -> Result<(mov::Move, Option<GameEnd>), Error> {
     // dangling move for voting (although discard move is not really used)
     if let None = game_id {
-        return database::create_move(
+        let mv = database::create_move(
             db,
             DMove(DiscardMove {
                 id: -1, // ignored
@@ -529,19 +808,29 @@ pub fn create_discard_move(
                 player_id,
                 tile,
             }),
-        );
+        )?;
+        return Ok((mv, None));
     }
 
-    let mut rng = rand::thread_rng();
+    let conn = &mut db.get().unwrap();
+    conn.transaction(|conn| append_discard_move(conn, game_id, player_id, tile))
+}
 
-    let gm = match database::get_game(db, game_id.unwrap()) {
+fn append_discard_move(
+    conn: &mut database::DbConn,
+    game_id: Option<i32>,
+    player_id: i32,
+    tile: tile::Tile,
+) -> Result<(mov::Move, Option<GameEnd>), Error> {
+    let gm = match database::lock_game(conn, game_id.unwrap()) {
         Ok(game) => game,
         Err(e) => {
             return Err(e);
         }
     };
+    validate_turn(&gm, player_id, Some(tile))?;
 
-    let mut moves = match database::list_moves(db, game_id.unwrap(), None) {
+    let mut moves = match database::find_moves(conn, game_id.unwrap(), None) {
         Ok(mvs) => mvs,
         Err(e) => {
             return Err(e);
@@ -549,6 +838,14 @@ pub fn create_discard_move(
     };
     assert!(moves.len() != 0);
 
+    calculate::validate_discard(
+        &moves,
+        gm.player_num(),
+        &gm.enabled_expansions(),
+        &gm.rule_set(),
+        tile,
+    )?;
+
     let ord = moves.last().unwrap().ord() + 1;
 
     let mv = DMove(DiscardMove {
@@ -569,7 +866,7 @@ pub fn create_discard_move(
         }
         None => {}
     }
-    for mv in moves {
+    for mv in &moves {
         match mv {
             mov::Move::TMove(tm) => {
                 out_tiles.push(tm.tile.clone());
@@ -580,45 +877,55 @@ pub fn create_discard_move(
             _ => {}
         }
     }
-    let remaining_tiles = tile::remaining_tiles(out_tiles.clone());
-
-    let draw_tile = if remaining_tiles.len() == 0 {
-        Invalid
-    } else {
-        remaining_tiles[rng.gen_range(0..remaining_tiles.len())]
-    };
-
-    // TODO: what if thre's no tile to draw on the second last turn?
+    let draw_tile = draw_tile(&gm, &out_tiles);
+
+    // the player draws `draw_tile` instead of the discarded one, or the turn passes to the next player.
+    // when there's no tile left to draw (e.g., on the second last turn), the turn passes as well
+    let (next_tile_id, next_player_id, cur_tile_id, cur_player_id) =
+        if gm.redraw_on_discard && draw_tile != Invalid {
+            (
+                gm.next_tile_id.unwrap(),
+                gm.next_player_id.unwrap(),
+                draw_tile.to_id(),
+                gm.current_player_id.unwrap(),
+            )
+        } else {
+            (
+                draw_tile.to_id(),
+                gm.next_player_id(gm.next_player_id.unwrap()),
+                gm.next_tile_id.unwrap(),
+                gm.next_player_id.unwrap(),
+            )
+        };
 
-    match database::update_game(
-        db,
+    database::update_game(
+        conn,
         game_id.unwrap(),
-        gm.next_tile_id.unwrap(),
-        gm.next_player_id.unwrap(),
-        gm.player0_point,
-        gm.player1_point,
-        draw_tile.to_id(),
-        gm.current_player_id.unwrap(),
+        next_tile_id,
+        next_player_id,
+        gm.player_points.clone(),
+        cur_tile_id,
+        cur_player_id,
         gm.before_player0_rating,
         gm.before_player1_rating,
         gm.after_player0_rating,
         gm.after_player1_rating,
         gm.first_player_id,
         gm.winner_player_id,
-    ) {
-        Err(e) => {
-            return Err(e);
-        }
-        Ok(_) => {}
-    }
+    )?;
+    let mv = database::insert_move(conn, mv)?;
 
-    database::create_move(db, mv)
+    // the discarded tile was the last one
+    let game_end = if cur_tile_id == Invalid.to_id() {
+        Some(finish_game(conn, &gm, &moves)?)
+    } else {
+        None
+    };
+    Ok((mv, game_end))
 }
 
-pub fn wait_ai_move(
-    db: &DbPool,
-    game_id: i32,
-) -> Result<(Vec<mov::Move>, Vec<CompleteEvent>), Error> {
+// the bot of the current player makes its move with its agent
+pub fn wait_ai_move(db: &DbPool, game_id: i32) -> Result<AIMoveOutcome, Error> {
     let game = match database::get_game(db, game_id) {
         Ok(gm) => gm,
         Err(e) => {
@@ -634,49 +941,265 @@ pub fn wait_ai_move(
     };
     assert!(moves.len() != 0);
 
+    let player_id = match game.current_player_id {
+        Some(pid) => pid,
+        None => {
+            return Err(bad_request_error("the game has ended".to_string()));
+        }
+    };
+    let agent = match game.agent_of(player_id) {
+        Some(name) => match agent::by_name(name) {
+            Some(a) => a,
+            None => {
+                return Err(internal_server_error(format!("unknown agent {}", name)));
+            }
+        },
+        None => {
+            return Err(bad_request_error(format!(
+                "player {} is not a bot",
+                player_id
+            )));
+        }
+    };
+
     let placing_tile = tile::to_tile(game.current_tile_id.unwrap());
+    let state = agent::AgentState {
+        game_id: Some(game.id),
+        player_ids: game.player_ids.clone(),
+        player_id,
+        moves,
+        expansions: game.enabled_expansions(),
+        rule_set: game.rule_set(),
+    };
 
-    match calculate_next_move::calculate_next_move(
-        &moves,
-        Some(game.id),
-        game.player0_id,
-        game.player1_id,
-        1,
-        placing_tile,
-    ) {
-        Some((tile_move, meeple_move)) => {
-            let tile_move = create_tile_move(
+    match agent.choose(&state, placing_tile) {
+        agent::Decision::Place(tile_move, meeple_move) => {
+            let r = create_move(
                 db,
                 Some(game.id),
-                1,
+                player_id,
                 placing_tile,
                 tile_move.rot,
                 tile_move.pos,
-            )?;
-
-            let (meeple_move, complete_events, _, _) = create_meeple_move(
-                db,
-                Some(game.id),
-                1,
                 meeple_move.meeple_id,
-                meeple_move.tile_pos,
                 meeple_move.meeple_pos,
             )?;
 
-            Ok((vec![tile_move, meeple_move], complete_events))
+            Ok((
+                vec![r.tile_move, r.meeple_move],
+                r.complete_events,
+                r.game_end,
+            ))
         }
-        None => {
-            let discard_move = create_discard_move(db, Some(game.id), 1, placing_tile)?;
+        agent::Decision::Discard => {
+            let (discard_move, game_end) =
+                create_discard_move(db, Some(game.id), player_id, placing_tile)?;
 
-            Ok((vec![discard_move], vec![]))
+            Ok((vec![discard_move], vec![], game_end))
         }
     }
 }
 
+// the difficulty levels of the AI, the easiest first
+pub fn get_ai_levels(db: &DbPool) -> Result<Vec<AILevel>, Error> {
+    database::get_ai_levels(db)
+}
+
 pub fn get_game(db: &DbPool, game_id: i32) -> Result<Game, Error> {
     database::get_game(db, game_id)
 }
 
+// the game written in the text notation (see `notation`)
+pub fn export_game(db: &DbPool, game_id: i32) -> Result<String, Error> {
+    let record = game_record(db, game_id)?;
+    match notation::encode(&record) {
+        Ok(text) => Ok(text),
+        Err(msg) => Err(internal_server_error(msg)),
+    }
+}
+
+// the game written as a JCloisterZone saved game, which is only possible for base games
+pub fn export_jcz_game(db: &DbPool, game_id: i32) -> Result<String, Error> {
+    let record = game_record(db, game_id)?;
+    match jcloisterzone::write(&record) {
+        Ok(data) => Ok(data),
+        Err(msg) => Err(bad_request_error(msg)),
+    }
+}
+
+fn game_record(db: &DbPool, game_id: i32) -> Result<notation::GameRecord, Error> {
+    let gm = database::get_game(db, game_id)?;
+    let moves = database::list_moves(db, game_id, None)?;
+    let mut seat_moves = vec![];
+    for mv in moves {
+        let seat = match gm.seat(mv.player_id()) {
+            Some(seat) => seat as i32,
+            None => {
+                return Err(internal_server_error(format!(
+                    "move {} is made by player {} who is not in game {}",
+                    mv.ord(),
+                    mv.player_id(),
+                    gm.id
+                )))
+            }
+        };
+        seat_moves.push(with_player(mv, None, seat));
+    }
+    let first_seat = match gm.first_player_id.and_then(|pid| gm.seat(pid)) {
+        Some(seat) => seat,
+        None => 0,
+    };
+    Ok(notation::GameRecord {
+        player_names: gm.player_names.clone(),
+        first_seat,
+        date: Some(gm.created_at.date()),
+        result: match gm.ended_at {
+            Some(_) => Some(gm.player_points.clone()),
+            None => None,
+        },
+        expansions: gm.enabled_expansions(),
+        rule_set: gm.rule_set(),
+        moves: seat_moves,
+    })
+}
+
+// creates a game from the text notation. imported games are archives, whose players aren't registered
+pub fn import_game(db: &DbPool, text: &str) -> Result<Game, Error> {
+    let record = match notation::parse(text) {
+        Ok(r) => r,
+        Err(msg) => return Err(bad_request_error(msg)),
+    };
+    create_imported_game(db, &record)
+}
+
+// creates a game from a JCloisterZone saved game, which is left in progress since it has no result
+pub fn import_jcz_game(db: &DbPool, data: &str) -> Result<Game, Error> {
+    let record = match jcloisterzone::read(data) {
+        Ok(r) => r,
+        Err(msg) => return Err(bad_request_error(msg)),
+    };
+    create_imported_game(db, &record)
+}
+
+fn create_imported_game(db: &DbPool, record: &notation::GameRecord) -> Result<Game, Error> {
+    let player_num = record.player_names.len();
+    if let Some(points) = &record.result {
+        let s = calculate(
+            &record.moves,
+            player_num,
+            &record.expansions,
+            &record.rule_set,
+            true,
+        )?;
+        if s.player_points != *points {
+            return Err(bad_request_error(format!(
+                "the result doesn't match the moves, which score {:?}",
+                s.player_points
+            )));
+        }
+    }
+
+    let player_ids: Vec<i32> = (0..player_num as i32)
+        .map(|seat| IMPORTED_PLAYER_ID - seat)
+        .collect();
+    let first_player_id = player_ids[record.first_seat];
+    // the game is created with its moves, so that a record which can't be stored doesn't leave a game behind
+    let conn = &mut db.get().unwrap();
+    conn.transaction(|conn| {
+        let gm = database::insert_game(
+            conn,
+            player_ids.clone(),
+            None,
+            None,
+            None,
+            None,
+            record.player_names.clone(),
+            (0..player_num as i32).collect(),
+            false,
+            Some(first_player_id),
+            record.expansions.iter().map(|e| e.name()).collect(),
+            &record.rule_set,
+            0, // imported games never draw tiles
+            None,
+            vec![None; player_num],
+        )?;
+
+        for mv in &record.moves {
+            let player_id = player_ids[mv.player_id() as usize];
+            database::insert_move(conn, with_player(mv.clone(), Some(gm.id), player_id))?;
+        }
+        match &record.result {
+            Some(points) => {
+                let winner_player_id = decide_winner(&gm, points, first_player_id);
+                database::end_game(
+                    conn,
+                    gm.id,
+                    points.clone(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    first_player_id,
+                    winner_player_id,
+                )
+            }
+            None => Ok(gm),
+        }
+    })
+}
+
+// the move made by `player_id` in `game_id`
+fn with_player(mv: mov::Move, game_id: Option<i32>, player_id: i32) -> mov::Move {
+    match mv {
+        TMove(m) => TMove(TileMove {
+            game_id,
+            player_id,
+            ..m
+        }),
+        MMove(m) => MMove(MeepleMove {
+            game_id,
+            player_id,
+            ..m
+        }),
+        DMove(m) => DMove(DiscardMove {
+            game_id,
+            player_id,
+            ..m
+        }),
+        mov::Move::InvalidMove => mv,
+    }
+}
+
+// the deck of a game and the seed it was shuffled with, which can be checked against the tiles drawn.
+// they are only revealed after the game ends
+pub fn get_deck(db: &DbPool, game_id: i32) -> Result<Deck, Error> {
+    let gm = database::get_game(db, game_id)?;
+    check_deck_revealed(db, &gm)?;
+    Ok(Deck {
+        seed: gm.seed,
+        tiles: tile::deck(gm.seed as u64, &gm.enabled_expansions()),
+    })
+}
+
+// the deck of a game is secret while it goes on. the deck of a daily challenge is shared by all its games,
+// so it stays secret until the day is over
+fn check_deck_revealed(db: &DbPool, gm: &Game) -> Result<(), Error> {
+    if gm.ended_at.is_none() {
+        return Err(forbidden_error(
+            "the deck is revealed after the game ends".to_string(),
+        ));
+    }
+    if let Some(cid) = gm.challenge_id {
+        let c = database::get_challenge(db, cid)?;
+        if c.date >= chrono::Utc::now().date_naive() {
+            return Err(forbidden_error(
+                "the deck of a daily challenge is revealed after the day is over".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 pub fn get_games(
     db: &DbPool,
     player_id: Option<i32>,
@@ -699,6 +1222,7 @@ pub fn get_moves(
     }
 }
 
+// final scoring of the game. the result is recorded by `finish_game` when the last move is made
 pub fn get_final_events(db: &DbPool, game_id: Option<i32>) -> Result<MeepleMoveResult, Error> {
     let gid = match game_id {
         Some(gid) => gid,
@@ -723,6 +1247,40 @@ pub fn get_final_events(db: &DbPool, game_id: Option<i32>) -> Result<MeepleMoveR
         }
     };
 
+    let (_, complete_events) = final_status(&gm, &moves)?;
+
+    Ok(MeepleMoveResult { complete_events })
+}
+
+fn final_status(
+    gm: &Game,
+    moves: &Vec<mov::Move>,
+) -> Result<(Vec<i32>, Vec<CompleteEvent>), Error> {
+    let s = calculate::calculate(
+        moves,
+        gm.player_num(),
+        &gm.enabled_expansions(),
+        &gm.rule_set(),
+        true,
+    )?;
+    let complete_events = s
+        .complete_events
+        .iter()
+        .map(|e| CompleteEvent {
+            meeple_ids: e.meeple_ids.clone(),
+            feature: e.feature.clone().to_string(),
+            point: e.point,
+        })
+        .collect();
+    Ok((s.player_points, complete_events))
+}
+
+// scores the game at the end, and records the winner, the ratings and when the game ended
+fn finish_game(
+    conn: &mut database::DbConn,
+    gm: &Game,
+    moves: &Vec<mov::Move>,
+) -> Result<GameEnd, Error> {
     let first_player_id = match gm.first_player_id {
         Some(fpi) => fpi,
         None => {
@@ -734,209 +1292,128 @@ pub fn get_final_events(db: &DbPool, game_id: Option<i32>) -> Result<MeepleMoveR
         }
     };
 
-    let mut complete_events = vec![];
+    let (player_points, complete_events) = final_status(gm, moves)?;
 
-    let res = calculate::calculate(&moves, true);
-    let (player0_point, player1_point) = match res {
-        Ok(s) => {
-            for e in &s.complete_events {
-                complete_events.push(CompleteEvent {
-                    meeple_ids: e.meeple_ids.clone(),
-                    feature: e.feature.clone().to_string(),
-                    point: e.point,
-                })
-            }
-            (s.player0_point, s.player1_point)
-        }
-        Err(e) => {
-            return Err(e);
-        }
-    };
+    let player0 = database::find_player(conn, gm.player0_id)?;
+    let player1 = database::find_player(conn, gm.player1_id)?;
 
-    if gm.winner_player_id == None {
-        let player0 = match database::get_player(db, gm.player0_id) {
-            Ok(p) => p,
-            Err(e) => {
-                return Err(e);
-            }
+    // bots are rated by the level they played at, rather than as players
+    let level0 = gm.agent_of(player0.id);
+    let level1 = gm.agent_of(player1.id);
+    let mut before_player0_rating = match level0 {
+        Some(l) => database::find_ai_level(conn, l)?.map(|l| l.rating),
+        None => player0.rating,
+    };
+    let mut before_player1_rating = match level1 {
+        Some(l) => database::find_ai_level(conn, l)?.map(|l| l.rating),
+        None => player1.rating,
+    };
+    let winner_player_id = decide_winner(gm, &player_points, first_player_id);
+    let player0_win = winner_player_id == Some(gm.player0_id);
+    let is_draw = winner_player_id.is_none();
+    let mut after_player0_rating = before_player0_rating;
+    let mut after_player1_rating = before_player1_rating;
+    if gm.is_rated {
+        before_player0_rating = match before_player0_rating {
+            Some(r) => Some(r),
+            None => Some(1500),
         };
-        let player1 = match database::get_player(db, gm.player1_id) {
-            Ok(p) => p,
-            Err(e) => {
-                return Err(e);
-            }
+        before_player1_rating = match before_player1_rating {
+            Some(r) => Some(r),
+            None => Some(1500),
         };
-
-        let mut before_player0_rating = player0.rating;
-        let mut before_player1_rating = player1.rating;
-        let player0_win = player0_point > player1_point
-            || (player0_point == player1_point && first_player_id == gm.player1_id);
-        let winner_player_id = if player0_win {
-            gm.player0_id
+        let (r0, r1) = if is_draw {
+            calculate_draw_rating(
+                before_player0_rating.unwrap(),
+                before_player1_rating.unwrap(),
+            )
         } else {
-            gm.player1_id
-        };
-        let mut after_player0_rating = player0.rating;
-        let mut after_player1_rating = player1.rating;
-        if gm.is_rated {
-            before_player0_rating = match before_player0_rating {
-                Some(r) => Some(r),
-                None => Some(1500),
-            };
-            before_player1_rating = match before_player1_rating {
-                Some(r) => Some(r),
-                None => Some(1500),
-            };
-            (after_player0_rating, after_player1_rating) = match calculate_rating(
+            calculate_rating(
                 before_player0_rating.unwrap(),
                 before_player1_rating.unwrap(),
                 player0_win,
-            ) {
-                (r0, r1) => (Some(r0), Some(r1)),
-            };
-
-            // Currently & experimentally, AI's rating is always 1500
-            if player0.id == 1 {
-                after_player0_rating = Some(1500);
-            }
-            if player1.id == 1 {
-                after_player1_rating = Some(1500);
-            }
+            )
+        };
+        (after_player0_rating, after_player1_rating) = (Some(r0), Some(r1));
 
-            match database::update_player(
-                db,
-                player0.id,
-                player0.name,
-                player0.meeple_color,
-                after_player0_rating,
-                player0.tile_edition,
-                player0.profile_image_url,
-            ) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-            match database::update_player(
-                db,
-                player1.id,
-                player1.name,
-                player1.meeple_color,
-                after_player1_rating,
-                player1.tile_edition,
-                player1.profile_image_url,
-            ) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(e);
-                }
+        match level0 {
+            Some(l) => database::update_ai_level_rating(conn, l, r0)?,
+            None => {
+                database::update_player_rating(conn, player0.id, after_player0_rating)?;
             }
         }
-
-        match database::update_game(
-            db,
-            gm.id,
-            gm.next_tile_id.unwrap(),
-            gm.next_player_id.unwrap(),
-            player0_point,
-            player1_point,
-            gm.current_tile_id.unwrap(),
-            gm.current_player_id.unwrap(),
-            before_player0_rating,
-            before_player1_rating,
-            after_player0_rating,
-            after_player1_rating,
-            Some(first_player_id),
-            Some(winner_player_id),
-        ) {
-            Err(e) => {
-                return Err(e);
+        match level1 {
+            Some(l) => database::update_ai_level_rating(conn, l, r1)?,
+            None => {
+                database::update_player_rating(conn, player1.id, after_player1_rating)?;
             }
-            Ok(_) => {}
         }
-
-        /*
-        thread::spawn(move || {
-            create_optimal_move(gm.id, 3, gm.player0_id, gm.player1_id);
-        });
-        */
     }
 
-    Ok(MeepleMoveResult { complete_events })
+    database::end_game(
+        conn,
+        gm.id,
+        player_points.clone(),
+        before_player0_rating,
+        before_player1_rating,
+        after_player0_rating,
+        after_player1_rating,
+        first_player_id,
+        winner_player_id,
+    )?;
+
+    Ok(GameEnd {
+        winner_player_id,
+        is_draw,
+        player_points,
+        complete_events,
+    })
 }
 
-#[allow(dead_code, unused_assignments)]
-fn create_optimal_move(db: &DbPool, game_id: i32, last_n: i32, player0_id: i32, player1_id: i32) {
-    let mut moves = match database::list_moves(db, game_id, None) {
-        Ok(mvs) => mvs,
-        Err(_) => {
-            return;
-        }
+// the player with the most points wins.
+// when some players are tied, the one who plays later in the turn order wins,
+// or the game is a draw (None) if the rule set says so
+fn decide_winner(gm: &Game, player_points: &Vec<i32>, first_player_id: i32) -> Option<i32> {
+    let player_num = gm.player_num();
+    let max_point = match player_points.iter().max() {
+        Some(p) => *p,
+        None => 0,
     };
-
-    let mut tile_count = 0;
-
-    let mut next_tile = Tile::Invalid;
-    loop {
-        match moves.last().unwrap() {
-            TMove(tm) => {
-                tile_count += 1;
-                if tile_count == last_n {
-                    next_tile = tm.tile;
-                    moves.pop();
-                    break;
-                }
-            }
-            _ => {}
+    if gm.tie_is_draw && player_points.iter().filter(|p| **p == max_point).count() > 1 {
+        return None;
+    }
+    let first_seat = match gm.seat(first_player_id) {
+        Some(seat) => seat,
+        None => 0,
+    };
+    let mut winner_seat = first_seat;
+    for i in 0..player_num {
+        let seat = (first_seat + i) % player_num;
+        if player_points[seat] >= player_points[winner_seat] {
+            winner_seat = seat;
         }
-        moves.pop();
     }
+    Some(gm.player_ids[winner_seat])
+}
 
-    let ((mut tile_move, mut meeple_move), solve_result) = solve(
+// the board after `move_id` (or the last move) drawn as SVG
+pub fn render_board(db: &DbPool, game_id: i32, move_id: Option<i32>) -> Result<String, Error> {
+    let gm = database::get_game(db, game_id)?;
+    let moves = database::list_moves(db, game_id, move_id)?;
+    let s = calculate(
         &moves,
-        Some(game_id),
-        player0_id,
-        player1_id,
-        next_tile,
-        false,
+        gm.player_num(),
+        &gm.enabled_expansions(),
+        &gm.rule_set(),
         false,
-    );
-
-    if solve_result != SolveResult::AlwaysWin {
-        return;
-    }
-
-    tile_move.ord = -1;
-    meeple_move.ord = -1;
-
-    let tile_move_id = match database::create_move(db, TMove(tile_move)) {
-        Ok(m) => match m {
-            TMove(tm) => tm.id,
-            _ => {
-                return;
-            }
-        },
-        Err(e) => {
-            panic!("{:?}", e.detail.msg);
-        }
-    };
-    let meeple_move_id = match database::create_move(db, MMove(meeple_move)) {
-        Ok(m) => match m {
-            MMove(mm) => mm.id,
-            _ => {
-                return;
-            }
-        },
-        Err(e) => {
-            panic!("{:?}", e.detail.msg);
-        }
-    };
+    )?;
+    Ok(render::svg(&s.board, &gm.player_colors))
+}
 
-    match database::create_optimal_move(db, game_id, last_n, tile_move_id, meeple_move_id) {
-        Ok(_) => {}
-        Err(e) => {
-            panic!("{:?}", e.detail.msg);
-        }
+pub fn render_png(svg: &str) -> Result<Vec<u8>, Error> {
+    match render::png(svg) {
+        Ok(png) => Ok(png),
+        Err(msg) => Err(internal_server_error(msg)),
     }
 }
 
@@ -950,6 +1427,13 @@ pub fn get_board(db: &DbPool, game_id: Option<i32>, move_id: Option<i32>) -> Res
         }
     };
 
+    let gm = match database::get_game(db, gid) {
+        Ok(game) => game,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
     let moves = match database::list_moves(db, gid, move_id) {
         Ok(mvs) => mvs,
         Err(e) => {
@@ -957,64 +1441,59 @@ pub fn get_board(db: &DbPool, game_id: Option<i32>, move_id: Option<i32>) -> Res
         }
     };
 
-    let (player0_point, player1_point, b, meepleable_positions, complete_events) =
-        match calculate(&moves, false) {
-            Ok(s) => {
-                let mut complete_events = vec![];
-                for e in &s.complete_events {
-                    complete_events.push(CompleteEvent {
-                        meeple_ids: e.meeple_ids.clone(),
-                        feature: e.feature.clone().to_string(),
-                        point: e.point,
-                    })
-                }
-                (
-                    s.player0_point,
-                    s.player1_point,
-                    s.board,
-                    s.meepleable_positions,
-                    complete_events,
-                )
-            }
-            Err(e) => {
-                return Err(e);
+    let (player_points, b, meepleable_positions, complete_events) = match calculate(
+        &moves,
+        gm.player_num(),
+        &gm.enabled_expansions(),
+        &gm.rule_set(),
+        false,
+    ) {
+        Ok(s) => {
+            let mut complete_events = vec![];
+            for e in &s.complete_events {
+                complete_events.push(CompleteEvent {
+                    meeple_ids: e.meeple_ids.clone(),
+                    feature: e.feature.clone().to_string(),
+                    point: e.point,
+                })
             }
-        };
+            (
+                s.player_points,
+                s.board,
+                s.meepleable_positions,
+                complete_events,
+            )
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
 
-    let board_size = 20 * 2 + 1;
-    let mut tiles = vec![
-        vec![
-            BoardTile {
-                id: -1,
-                rot: -1,
-                meeple_id: -1,
-                meeple_pos: -1,
-            };
-            board_size
-        ];
-        board_size
-    ];
-    for ((y, x), t) in b.iter() {
-        tiles[(*y + board_size as i32 / 2) as usize][(*x + board_size as i32 / 2) as usize] =
-            BoardTile {
-                id: t.tile.to_id(),
-                rot: t.rot,
-                meeple_id: match t.meeple_id {
-                    Some(mid) => mid,
-                    None => -1,
-                },
-                meeple_pos: match t.meeple_pos {
-                    Some(mpos) => mpos,
-                    None => -1,
-                },
-            }
-    }
+    let (tiles, bounds) = board::board_tiles(&b);
 
     Ok(Board {
-        player0_point,
-        player1_point,
+        player0_point: player_points[0],
+        player1_point: player_points[1],
+        player_points,
         tiles,
+        bounds,
         meepleable_positions,
         complete_events,
     })
 }
+
+#[test]
+fn validate_meeple_owner_test() {
+    assert!(validate_meeple_owner(Some(1), -1).is_ok());
+    assert!(validate_meeple_owner(Some(1), 7).is_ok());
+    assert!(validate_meeple_owner(Some(1), calculate::BIG_MEEPLE_ID_OFFSET + 1).is_ok());
+    // the first player's meeple, placed by the second player
+    match validate_meeple_owner(Some(1), 0) {
+        Ok(_) => panic!("a meeple of another player must not be placed"),
+        Err(e) => assert_eq!(
+            e.detail.reason,
+            Some(MoveError::NotOwnMeeple { meeple_id: 0 })
+        ),
+    }
+    assert!(validate_meeple_owner(None, 0).is_err());
+}
@@ -2,7 +2,7 @@ use self::Move::{DMove, InvalidMove, MMove, TMove};
 use super::tile::Tile;
 use rocket::serde::Serialize;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct TileMove {
     pub id: i32,
@@ -14,7 +14,7 @@ pub struct TileMove {
     pub pos: (i32, i32),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct MeepleMove {
     pub id: i32,
@@ -26,7 +26,7 @@ pub struct MeepleMove {
     pub meeple_pos: i32,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct DiscardMove {
     pub id: i32,
@@ -36,7 +36,7 @@ pub struct DiscardMove {
     pub tile: Tile,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub enum Move {
     TMove(TileMove),
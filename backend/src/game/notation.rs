@@ -0,0 +1,650 @@
+// a PGN-like text notation for games.
+//
+//     [Player1 "Alice"]
+//     [Player2 "Bob"]
+//     [First "2"]
+//     [Date "2024-07-22"]
+//     [Result "52-48"]
+//     [Expansions "river"]
+//     [FieldEdition "third"]
+//     [MeeplesPerPlayer "7"]
+//     [TieIsDraw "false"]
+//     [RedrawOnDiscard "true"]
+//
+//     1. 37r1@0,1 2. 38r0@0,2m1 3. 10x ...
+//
+// players are numbered from 1 in the turn order, and `First` is the one who plays the first turn
+// (the one before them places the starting tile). `Result` has the points of each player, or is `*`.
+// a turn is `<tile id>r<rotation>@<y>,<x>`, followed by `m<feature>` when a meeple is placed on the tile
+// (`M<feature>` for a big meeple), or `<tile id>x` when the tile is discarded.
+// whose turn it is follows from the rules, so it isn't written. turn numbers are optional when parsing
+use super::calculate::{big_meeple_id_of, is_big_meeple, validate_discard};
+use super::game_state::GameState;
+use super::mov::{DiscardMove, MeepleMove, Move, TileMove};
+use super::rule_set::{FieldEdition, RuleSet};
+use super::tile::{self, to_tile, Expansion, Tile};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub player_names: Vec<String>,
+    pub first_seat: usize,
+    pub date: Option<chrono::NaiveDate>,
+    // points of each player when the game has ended
+    pub result: Option<Vec<i32>>,
+    pub expansions: Vec<Expansion>,
+    pub rule_set: RuleSet,
+    // all the moves including the starting tile, with seats as `player_id`s and without `game_id`s
+    pub moves: Vec<Move>,
+}
+
+// turns are wrapped into lines of about this width
+const LINE_WIDTH: usize = 80;
+
+pub fn encode(record: &GameRecord) -> Result<String, String> {
+    let player_num = record.player_names.len();
+    let mut out = String::new();
+    for (i, name) in record.player_names.iter().enumerate() {
+        out += &tag(&format!("Player{}", i + 1), name);
+    }
+    out += &tag("First", &(record.first_seat + 1).to_string());
+    if let Some(date) = record.date {
+        out += &tag("Date", &date.format("%Y-%m-%d").to_string());
+    }
+    let result = match &record.result {
+        Some(points) => points
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join("-"),
+        None => "*".to_string(),
+    };
+    out += &tag("Result", &result);
+    if !record.expansions.is_empty() {
+        let names: Vec<String> = record.expansions.iter().map(|e| e.name()).collect();
+        out += &tag("Expansions", &names.join(" "));
+    }
+    out += &tag("FieldEdition", &record.rule_set.field_edition.name());
+    out += &tag(
+        "MeeplesPerPlayer",
+        &record.rule_set.meeples_per_player.to_string(),
+    );
+    out += &tag("TieIsDraw", &record.rule_set.tie_is_draw.to_string());
+    out += &tag(
+        "RedrawOnDiscard",
+        &record.rule_set.redraw_on_discard.to_string(),
+    );
+    out += "\n";
+
+    let mut turns = TurnOrder::new(player_num, record.first_seat, record);
+    let mut line = String::new();
+    let mut idx = 2; // after the starting tile
+    while idx < record.moves.len() {
+        let (token, player_id, consumed) = match (&record.moves[idx], record.moves.get(idx + 1)) {
+            (Move::TMove(tm), Some(Move::MMove(mm))) => {
+                let mut token = format!("{}r{}@{},{}", tm.tile.to_id(), tm.rot, tm.pos.0, tm.pos.1);
+                if mm.meeple_id != -1 {
+                    let m = if is_big_meeple(mm.meeple_id) {
+                        'M'
+                    } else {
+                        'm'
+                    };
+                    token += &format!("{}{}", m, mm.meeple_pos);
+                }
+                (token, tm.player_id, 2)
+            }
+            (Move::DMove(dm), _) => (format!("{}x", dm.tile.to_id()), dm.player_id, 1),
+            _ => return Err(format!("move {} is not the start of a turn", idx)),
+        };
+        let seat = turns.seat;
+        if player_id != seat as i32 {
+            return Err(format!(
+                "move {} is made by player {} out of turn",
+                idx, player_id
+            ));
+        }
+        turns.next(matches!(record.moves[idx], Move::DMove(_)));
+
+        let token = format!("{}. {}", turns.turn, token);
+        if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+            out += &line;
+            out += "\n";
+            line.clear();
+        }
+        if !line.is_empty() {
+            line += " ";
+        }
+        line += &token;
+        idx += consumed;
+    }
+    if !line.is_empty() {
+        out += &line;
+        out += "\n";
+    }
+    Ok(out)
+}
+
+pub fn parse(text: &str) -> Result<GameRecord, String> {
+    let mut player_names: Vec<(usize, String)> = vec![];
+    let mut first_seat = 0;
+    let mut date = None;
+    let mut result = None;
+    let mut expansions = vec![];
+    let mut rule_set = RuleSet::default();
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if !line.starts_with('[') {
+            movetext += line;
+            movetext += " ";
+            continue;
+        }
+        let (name, value) = parse_tag(line)?;
+        match name.as_str() {
+            "First" => {
+                first_seat = match value.parse::<usize>() {
+                    Ok(s) if s >= 1 => s - 1,
+                    _ => return Err(format!("invalid first player {}", value)),
+                }
+            }
+            "Date" => {
+                date = match chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+                    Ok(d) => Some(d),
+                    Err(_) => return Err(format!("invalid date {}", value)),
+                }
+            }
+            "Result" => {
+                if value != "*" {
+                    let mut points = vec![];
+                    for p in value.split('-') {
+                        match p.parse::<i32>() {
+                            Ok(p) => points.push(p),
+                            Err(_) => return Err(format!("invalid result {}", value)),
+                        }
+                    }
+                    result = Some(points);
+                }
+            }
+            "Expansions" => {
+                for e in value.split_whitespace() {
+                    match Expansion::from_name(e) {
+                        Some(e) => expansions.push(e),
+                        None => return Err(format!("unknown expansion {}", e)),
+                    }
+                }
+            }
+            "FieldEdition" => {
+                rule_set.field_edition = match FieldEdition::from_name(&value) {
+                    Some(e) => e,
+                    None => return Err(format!("unknown field edition {}", value)),
+                }
+            }
+            "MeeplesPerPlayer" => {
+                rule_set.meeples_per_player = match value.parse() {
+                    Ok(n) => n,
+                    Err(_) => return Err(format!("invalid number of meeples {}", value)),
+                }
+            }
+            "TieIsDraw" => rule_set.tie_is_draw = parse_bool(&name, &value)?,
+            "RedrawOnDiscard" => rule_set.redraw_on_discard = parse_bool(&name, &value)?,
+            _ => {
+                // `Player<n>`. other tags are ignored, as they are in PGN
+                if let Some(n) = name.strip_prefix("Player") {
+                    match n.parse::<usize>() {
+                        Ok(n) if n >= 1 => player_names.push((n - 1, value)),
+                        _ => return Err(format!("invalid tag {}", name)),
+                    }
+                }
+            }
+        }
+    }
+
+    player_names.sort();
+    if !(2..=5).contains(&player_names.len())
+        || player_names.iter().enumerate().any(|(i, (n, _))| i != *n)
+    {
+        return Err("there must be 2 to 5 players numbered from 1".to_string());
+    }
+    let player_names: Vec<String> = player_names.into_iter().map(|(_, name)| name).collect();
+    let player_num = player_names.len();
+    if first_seat >= player_num {
+        return Err(format!("invalid first player {}", first_seat + 1));
+    }
+    rule_set.validate()?;
+    if let Some(points) = &result {
+        if points.len() != player_num {
+            return Err("the result must have the points of each player".to_string());
+        }
+    }
+
+    let mut record = GameRecord {
+        player_names,
+        first_seat,
+        date,
+        result,
+        expansions,
+        rule_set,
+        moves: vec![],
+    };
+    record.moves = parse_movetext(&movetext, &record)?;
+    Ok(record)
+}
+
+fn parse_movetext(movetext: &str, record: &GameRecord) -> Result<Vec<Move>, String> {
+    let player_num = record.player_names.len();
+    let expansions = &record.expansions;
+    let mut state = GameState::new(player_num, expansions, &record.rule_set);
+    let mut turns = TurnOrder::new(player_num, record.first_seat, record);
+
+    let starting_seat = (record.first_seat + player_num - 1) % player_num;
+    let mut moves = vec![
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 0,
+            game_id: None,
+            player_id: starting_seat as i32,
+            tile: tile::starting_tile(expansions),
+            rot: 0,
+            pos: (0, 0),
+        }),
+        Move::MMove(MeepleMove {
+            id: -1,
+            ord: 1,
+            game_id: None,
+            player_id: starting_seat as i32,
+            meeple_id: -1,
+            tile_pos: (0, 0),
+            meeple_pos: -1,
+        }),
+    ];
+    for mv in &moves {
+        if let Err(e) = state.apply(mv) {
+            return Err(e.detail.msg);
+        }
+    }
+
+    for token in movetext.split_whitespace() {
+        // turn numbers
+        if token.ends_with('.') && token[..token.len() - 1].chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        if turns.turn >= tile::tiles(expansions).len() {
+            return Err("there are more turns than tiles".to_string());
+        }
+        let turn = turns.turn + 1;
+        let seat = turns.seat as i32;
+        let ord = moves.len() as i32;
+        let t = parse_token(token)?;
+        if !t.tile.is_available(expansions) || t.tile == Tile::Invalid {
+            return Err(format!("turn {}: tile {} can't be drawn", turn, token));
+        }
+
+        let is_discard = t.placement.is_none();
+        let mut turn_moves = vec![];
+        match t.placement {
+            Some(Placement { rot, pos, meeple }) => {
+                turn_moves.push(Move::TMove(TileMove {
+                    id: -1,
+                    ord,
+                    game_id: None,
+                    player_id: seat,
+                    tile: t.tile,
+                    rot,
+                    pos,
+                }));
+                if let Err(e) = state.apply(&turn_moves[0]) {
+                    return Err(format!("turn {}: {}", turn, e.detail.msg));
+                }
+                let (meeple_id, meeple_pos) = match meeple {
+                    Some((big, feature)) => {
+                        let remaining = &state.status().player_remaining_meeples[seat as usize];
+                        let meeple_id = if big {
+                            Some(big_meeple_id_of(seat as usize)).filter(|m| remaining.contains(m))
+                        } else {
+                            remaining
+                                .iter()
+                                .filter(|m| !is_big_meeple(**m))
+                                .min()
+                                .copied()
+                        };
+                        match meeple_id {
+                            Some(m) => (m, feature),
+                            None => return Err(format!("turn {}: no meeple is left", turn)),
+                        }
+                    }
+                    None => (-1, -1),
+                };
+                turn_moves.push(Move::MMove(MeepleMove {
+                    id: -1,
+                    ord: ord + 1,
+                    game_id: None,
+                    player_id: seat,
+                    meeple_id,
+                    tile_pos: pos,
+                    meeple_pos,
+                }));
+            }
+            None => {
+                if let Err(e) =
+                    validate_discard(&moves, player_num, expansions, &record.rule_set, t.tile)
+                {
+                    return Err(format!("turn {}: {}", turn, e.detail.msg));
+                }
+                turn_moves.push(Move::DMove(DiscardMove {
+                    id: -1,
+                    ord,
+                    game_id: None,
+                    player_id: seat,
+                    tile: t.tile,
+                }));
+            }
+        }
+        for mv in &turn_moves[if is_discard { 0 } else { 1 }..] {
+            if let Err(e) = state.apply(mv) {
+                return Err(format!("turn {}: {}", turn, e.detail.msg));
+            }
+        }
+        moves.append(&mut turn_moves);
+        turns.next(is_discard);
+    }
+    Ok(moves)
+}
+
+// whose turn it is. the turn passes after a tile is placed, and after a tile is discarded
+// unless the player draws another one
+struct TurnOrder {
+    player_num: usize,
+    seat: usize,
+    // number of the turns so far, which is also the number of tiles drawn for them
+    turn: usize,
+    tile_num: usize,
+    redraw_on_discard: bool,
+}
+
+impl TurnOrder {
+    fn new(player_num: usize, first_seat: usize, record: &GameRecord) -> TurnOrder {
+        TurnOrder {
+            player_num,
+            seat: first_seat,
+            turn: 0,
+            tile_num: tile::tiles(&record.expansions).len(),
+            redraw_on_discard: record.rule_set.redraw_on_discard,
+        }
+    }
+
+    fn next(&mut self, is_discard: bool) {
+        self.turn += 1;
+        // the next player holds the next tile, so another one can be drawn if any is left after it
+        let redraws = is_discard && self.redraw_on_discard && self.turn + 1 < self.tile_num;
+        if !redraws {
+            self.seat = (self.seat + 1) % self.player_num;
+        }
+    }
+}
+
+struct Token {
+    tile: Tile,
+    // `None` for a discard
+    placement: Option<Placement>,
+}
+
+struct Placement {
+    rot: i32,
+    pos: (i32, i32),
+    // whether it's a big meeple, and the feature it's placed on
+    meeple: Option<(bool, i32)>,
+}
+
+fn parse_token(token: &str) -> Result<Token, String> {
+    let invalid = || format!("invalid turn {}", token);
+    if let Some(tile_id) = token.strip_suffix('x') {
+        return match tile_id.parse::<i32>() {
+            Ok(id) => Ok(Token {
+                tile: to_tile(id),
+                placement: None,
+            }),
+            Err(_) => Err(invalid()),
+        };
+    }
+    let (tile_id, rest) = token.split_once('r').ok_or_else(invalid)?;
+    let (rot, rest) = rest.split_once('@').ok_or_else(invalid)?;
+    let (pos, meeple) = match rest.find(['m', 'M']) {
+        Some(i) => (&rest[..i], Some((&rest[i..i + 1] == "M", &rest[i + 1..]))),
+        None => (rest, None),
+    };
+    let (y, x) = pos.split_once(',').ok_or_else(invalid)?;
+    let tile_id: i32 = tile_id.parse().map_err(|_| invalid())?;
+    let rot: i32 = rot.parse().map_err(|_| invalid())?;
+    let y: i32 = y.parse().map_err(|_| invalid())?;
+    let x: i32 = x.parse().map_err(|_| invalid())?;
+    let meeple = match meeple {
+        Some((big, feature)) => Some((big, feature.parse().map_err(|_| invalid())?)),
+        None => None,
+    };
+    Ok(Token {
+        tile: to_tile(tile_id),
+        placement: Some(Placement {
+            rot,
+            pos: (y, x),
+            meeple,
+        }),
+    })
+}
+
+// values are quoted, with `\` escaping `"` and `\` as in PGN
+fn tag(name: &str, value: &str) -> String {
+    format!(
+        "[{} \"{}\"]\n",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let invalid = || format!("invalid tag {}", line);
+    let inner = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        Some(inner) => inner,
+        None => return Err(invalid()),
+    };
+    let (name, quoted) = inner.split_once(' ').ok_or_else(invalid)?;
+    let quoted = quoted.trim();
+    if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+        return Err(invalid());
+    }
+    let mut value = String::new();
+    let mut chars = quoted[1..quoted.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) => value.push(c),
+                None => return Err(invalid()),
+            },
+            '"' => return Err(invalid()),
+            c => value.push(c),
+        }
+    }
+    Ok((name.to_string(), value))
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value.parse() {
+        Ok(b) => Ok(b),
+        Err(_) => Err(format!("{} must be true or false", name)),
+    }
+}
+
+#[cfg(test)]
+fn record(moves: Vec<Move>) -> GameRecord {
+    GameRecord {
+        player_names: vec!["Alice".to_string(), "Bob \"B\"".to_string()],
+        first_seat: 1,
+        date: chrono::NaiveDate::from_ymd_opt(2024, 7, 22),
+        result: None,
+        expansions: vec![],
+        rule_set: RuleSet::default(),
+        moves,
+    }
+}
+
+#[test]
+fn notation_test() {
+    let text = r#"[Player1 "Alice"]
+[Player2 "Bob \"B\""]
+[First "2"]
+[Date "2024-07-22"]
+[Result "*"]
+[FieldEdition "third"]
+[MeeplesPerPlayer "7"]
+[TieIsDraw "false"]
+[RedrawOnDiscard "true"]
+
+1. 6r1@0,1m1 2. 6r1@0,-1 3. 7r2@-1,0m0
+"#;
+    let r = parse(text).unwrap();
+    assert_eq!(r, record(r.moves.clone()));
+    assert_eq!(r.moves.len(), 8);
+    // Alice places the starting tile, since Bob plays first
+    assert_eq!(r.moves[0].player_id(), 0);
+    assert_eq!(
+        r.moves[2],
+        Move::TMove(TileMove {
+            id: -1,
+            ord: 2,
+            game_id: None,
+            player_id: 1,
+            tile: Tile::Straight,
+            rot: 1,
+            pos: (0, 1),
+        })
+    );
+    match (&r.moves[3], &r.moves[7]) {
+        (Move::MMove(m3), Move::MMove(m7)) => {
+            assert_eq!((m3.player_id, m3.meeple_id, m3.meeple_pos), (1, 7, 1));
+            assert_eq!((m7.player_id, m7.meeple_id, m7.meeple_pos), (1, 8, 0));
+        }
+        _ => panic!("meeple moves expected"),
+    }
+    assert_eq!(encode(&r).unwrap(), text);
+
+    // turn numbers and line breaks are optional
+    let compact = text
+        .replace("1. ", "")
+        .replace("2. ", "")
+        .replace("3. ", "");
+    assert_eq!(parse(&compact).unwrap(), r);
+
+    let e = parse(&text.replace("7r2@-1,0m0", "7r2@5,5m0")).unwrap_err();
+    assert!(e.starts_with("turn 3: "), "{}", e);
+    let e = parse(&text.replace("7r2@-1,0m0", "7q2")).unwrap_err();
+    assert_eq!(e, "invalid turn 7q2");
+    let e = parse(&text.replace("[First \"2\"]", "[First \"3\"]")).unwrap_err();
+    assert_eq!(e, "invalid first player 3");
+    let e = parse(&text.replace("6r1@0,1m1", "6x")).unwrap_err();
+    assert!(e.starts_with("turn 1: "), "{}", e);
+
+    // moves out of turn can't be written down
+    let mut moves = r.moves.clone();
+    if let Move::TMove(tm) = &mut moves[4] {
+        tm.player_id = 1;
+    }
+    assert!(encode(&record(moves)).is_err());
+}
+
+#[test]
+fn notation_round_trip_test() {
+    // plays a whole game, placing each tile at the first position it fits and a meeple wherever possible
+    for (seed, expansions) in [
+        (1, vec![]),
+        (2, vec![Expansion::River, Expansion::InnsAndCathedrals]),
+    ] {
+        let rule_set = RuleSet::default();
+        let player_num = 3;
+        let mut r = record(vec![]);
+        r.player_names.push("Carol".to_string());
+        r.expansions = expansions.clone();
+        let mut state = GameState::new(player_num, &expansions, &rule_set);
+        let mut turns = TurnOrder::new(player_num, r.first_seat, &r);
+        let starting_seat = (r.first_seat + player_num - 1) % player_num;
+        let mut moves = vec![
+            Move::TMove(TileMove {
+                id: -1,
+                ord: 0,
+                game_id: None,
+                player_id: starting_seat as i32,
+                tile: tile::starting_tile(&expansions),
+                rot: 0,
+                pos: (0, 0),
+            }),
+            Move::MMove(MeepleMove {
+                id: -1,
+                ord: 1,
+                game_id: None,
+                player_id: starting_seat as i32,
+                meeple_id: -1,
+                tile_pos: (0, 0),
+                meeple_pos: -1,
+            }),
+        ];
+        for mv in &moves {
+            state.apply(mv).unwrap();
+        }
+        for t in tile::deck(seed, &expansions) {
+            let seat = turns.seat as i32;
+            let ord = moves.len() as i32;
+            let is_discard = match state.tileable_positions(t).first() {
+                Some(p) => {
+                    let tm = Move::TMove(TileMove {
+                        id: -1,
+                        ord,
+                        game_id: None,
+                        player_id: seat,
+                        tile: t,
+                        rot: p.rot,
+                        pos: p.pos,
+                    });
+                    state.apply(&tm).unwrap();
+                    let remaining = &state.status().player_remaining_meeples[seat as usize];
+                    let meeple_id = remaining.iter().filter(|m| !is_big_meeple(**m)).min();
+                    let (meeple_id, meeple_pos) =
+                        match (meeple_id, state.status().meepleable_positions.first()) {
+                            (Some(m), Some(f)) => (*m, *f),
+                            _ => (-1, -1),
+                        };
+                    let mm = Move::MMove(MeepleMove {
+                        id: -1,
+                        ord: ord + 1,
+                        game_id: None,
+                        player_id: seat,
+                        meeple_id,
+                        tile_pos: p.pos,
+                        meeple_pos,
+                    });
+                    state.apply(&mm).unwrap();
+                    moves.push(tm);
+                    moves.push(mm);
+                    false
+                }
+                None => {
+                    let dm = Move::DMove(DiscardMove {
+                        id: -1,
+                        ord,
+                        game_id: None,
+                        player_id: seat,
+                        tile: t,
+                    });
+                    state.apply(&dm).unwrap();
+                    moves.push(dm);
+                    true
+                }
+            };
+            turns.next(is_discard);
+        }
+        state.apply_final_scoring();
+        r.moves = moves;
+        r.result = Some(state.status().player_points.clone());
+
+        let text = encode(&r).unwrap();
+        assert_eq!(parse(&text).unwrap(), r);
+        assert!(text.lines().all(|l| l.len() <= LINE_WIDTH));
+    }
+}
@@ -19,6 +19,17 @@ pub fn calculate_rating(
     (ra.round() as i32, rb.round() as i32)
 }
 
+pub fn calculate_draw_rating(player_a_rating: i32, player_b_rating: i32) -> (i32, i32) {
+    let mut ra = f64::from(player_a_rating);
+    let mut rb = f64::from(player_b_rating);
+    let base: f64 = 10.0;
+    let k = 32.0;
+    let win_ab = 1.0 / (base.powf((rb - ra) / 400.0) + 1.0);
+    ra = ra + k * (0.5 - win_ab);
+    rb = rb - k * (0.5 - win_ab);
+    (ra.round() as i32, rb.round() as i32)
+}
+
 #[test]
 fn calculate_rating_test() {
     let (ra, rb) = calculate_rating(1500, 1700, true);
@@ -31,3 +42,13 @@ fn calculate_rating_test() {
     assert_eq!(ra, 1816);
     assert_eq!(rb, 1784);
 }
+
+#[test]
+fn calculate_draw_rating_test() {
+    let (ra, rb) = calculate_draw_rating(1500, 1700);
+    assert_eq!(ra, 1508);
+    assert_eq!(rb, 1692);
+    let (ra, rb) = calculate_draw_rating(1800, 1800);
+    assert_eq!(ra, 1800);
+    assert_eq!(rb, 1800);
+}
@@ -0,0 +1,374 @@
+// draws boards as SVG, or as PNG converted from it, with the tile artwork of the frontend.
+// the artwork is read once from `TILE_IMAGE_DIR` (`../frontend/src/assets/img` by default, which is where it is
+// when the server runs in `backend`). tiles without artwork, such as those of expansions, are drawn with shapes,
+// and so is everything when the directory can't be read
+use std::collections::HashMap;
+use std::env;
+use std::sync::OnceLock;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use resvg::tiny_skia;
+use resvg::usvg::{self, TreeParsing, TreePostProc};
+
+use super::calculate::{meeple_owner, Feature, Side, TileItem};
+use super::tile::{catalogue, FeatureDefinition, Tile, TileDefinition};
+
+const DEFAULT_TILE_IMAGE_DIR: &str = "../frontend/src/assets/img";
+const TILE_SIZE: i32 = 64;
+const MARGIN: i32 = 16;
+const MEEPLE_SIZE: i32 = 22;
+
+// names and colours of meeples by their colour ids, as in the frontend
+const MEEPLE_COLORS: [(&str, &str); 5] = [
+    ("red", "#e53935"),
+    ("yellow", "#fdd835"),
+    ("green", "#43a047"),
+    ("black", "#212121"),
+    ("blue", "#1e88e5"),
+];
+
+// images as data URIs, so that the SVG is self-contained
+struct Artwork {
+    tiles: HashMap<Tile, String>,
+    // standing and lying meeples by colour id
+    meeples: HashMap<(i32, bool), String>,
+}
+
+static ARTWORK: OnceLock<Artwork> = OnceLock::new();
+
+fn artwork() -> &'static Artwork {
+    ARTWORK.get_or_init(|| {
+        let dir = env::var("TILE_IMAGE_DIR").unwrap_or(DEFAULT_TILE_IMAGE_DIR.to_string());
+        let load = |name: &str| match std::fs::read(format!("{}/{}", dir, name)) {
+            Ok(png) => Some(format!("data:image/png;base64,{}", STANDARD.encode(png))),
+            Err(_) => None,
+        };
+        let mut tiles = HashMap::new();
+        for d in catalogue() {
+            if let Some(src) = image_name(d.tile).and_then(load) {
+                tiles.insert(d.tile, src);
+            }
+        }
+        let mut meeples = HashMap::new();
+        for (color, (name, _)) in MEEPLE_COLORS.iter().enumerate() {
+            for lying in [false, true] {
+                let pose = if lying { "lying" } else { "standing" };
+                if let Some(src) = load(&format!("{}_{}.png", name, pose)) {
+                    meeples.insert((color as i32, lying), src);
+                }
+            }
+        }
+        Artwork { tiles, meeples }
+    })
+}
+
+// the artwork of base game tiles in the frontend
+fn image_name(tile: Tile) -> Option<&'static str> {
+    match tile {
+        Tile::StartingTile => Some("city_cap_with_straight.png"),
+        Tile::Monastery => Some("monastery.png"),
+        Tile::MonasteryWithRoad => Some("monastery_with_road.png"),
+        Tile::CityCapWithCrossroad => Some("city_cap_with_crossroads.png"),
+        Tile::TriangleWithRoad => Some("triangle_with_road.png"),
+        Tile::TriangleWithRoadWithCOA => Some("triangle_with_road_with_coa.png"),
+        Tile::Straight => Some("straight.png"),
+        Tile::CityCap => Some("city_cap.png"),
+        Tile::Separator => Some("separator.png"),
+        Tile::TripleRoad => Some("triple_road.png"),
+        Tile::Curve => Some("curve.png"),
+        Tile::QuadrupleRoad => Some("quadruple_road.png"),
+        Tile::Connector => Some("connector.png"),
+        Tile::ConnectorWithCOA => Some("connector_with_coa.png"),
+        Tile::Left => Some("left.png"),
+        Tile::Right => Some("right.png"),
+        Tile::TripleCity => Some("triple_city.png"),
+        Tile::TripleCityWithCOA => Some("triple_city_with_coa.png"),
+        Tile::VerticalSeparator => Some("vertical_separator.png"),
+        Tile::TripleCityWithRoad => Some("triple_city_with_road.png"),
+        Tile::TripleCityWithRoadWithCOA => Some("triple_city_with_road_with_coa.png"),
+        Tile::Triangle => Some("triangle.png"),
+        Tile::TriangleWithCOA => Some("triangle_with_coa.png"),
+        Tile::QuadrupleCityWithCOA => Some("quadruple_city_with_coa.png"),
+        _ => None,
+    }
+}
+
+// `player_colors` are the colour ids of the players by seat
+pub fn svg(board: &HashMap<(i32, i32), TileItem>, player_colors: &[i32]) -> String {
+    let artwork = artwork();
+    let min_y = board.keys().map(|p| p.0).min().unwrap_or(0);
+    let max_y = board.keys().map(|p| p.0).max().unwrap_or(0);
+    let min_x = board.keys().map(|p| p.1).min().unwrap_or(0);
+    let max_x = board.keys().map(|p| p.1).max().unwrap_or(0);
+    let width = (max_x - min_x + 1) * TILE_SIZE + MARGIN * 2;
+    let height = (max_y - min_y + 1) * TILE_SIZE + MARGIN * 2;
+
+    let mut out = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"##,
+        width, height
+    );
+    out += &format!(
+        r##"<rect width="{}" height="{}" fill="#f3ead8"/>"##,
+        width, height
+    );
+
+    // sorted so that the same board is always drawn the same
+    let mut positions: Vec<&(i32, i32)> = board.keys().collect();
+    positions.sort();
+    let origin = |(y, x): (i32, i32)| {
+        (
+            (x - min_x) * TILE_SIZE + MARGIN,
+            (y - min_y) * TILE_SIZE + MARGIN,
+        )
+    };
+    for pos in &positions {
+        let t = &board[pos];
+        let (left, top) = origin(**pos);
+        out += &format!(
+            r##"<g transform="translate({} {}) rotate({} {} {})">"##,
+            left,
+            top,
+            t.rot.rem_euclid(4) * 90,
+            TILE_SIZE / 2,
+            TILE_SIZE / 2
+        );
+        match artwork.tiles.get(&t.tile) {
+            Some(src) => {
+                out += &format!(
+                    r##"<image width="{0}" height="{0}" href="{1}"/>"##,
+                    TILE_SIZE, src
+                )
+            }
+            None => out += &tile_shapes(t.tile),
+        }
+        out += "</g>";
+    }
+
+    // meeples are drawn upright on top of all the tiles
+    for pos in &positions {
+        let t = &board[pos];
+        let (meeple_id, meeple_pos) = match (t.meeple_id, t.meeple_pos) {
+            (Some(m), Some(p)) if m != -1 && p != -1 => (m, p),
+            _ => continue,
+        };
+        let feature = match t.tile.definition() {
+            Some(d) => match d.features.get(meeple_pos as usize) {
+                Some(f) => (d, f),
+                None => continue,
+            },
+            None => continue,
+        };
+        let (x, y) = rotate(spot(feature.0, meeple_pos as usize), t.rot);
+        let (left, top) = origin(**pos);
+        let cx = left as f64 + (1.0 + x) * TILE_SIZE as f64 / 2.0;
+        let cy = top as f64 + (1.0 - y) * TILE_SIZE as f64 / 2.0;
+        let color = match player_colors.get(meeple_owner(meeple_id)) {
+            Some(c) => *c,
+            None => 0,
+        };
+        let lying = feature.1.kind == Feature::FieldFeature;
+        out += &meeple(artwork, cx, cy, color, lying);
+    }
+
+    out += "</svg>";
+    out
+}
+
+pub fn png(svg: &str) -> Result<Vec<u8>, String> {
+    let mut tree = match usvg::Tree::from_str(svg, &usvg::Options::default()) {
+        Ok(tree) => tree,
+        Err(e) => return Err(format!("failed to parse the SVG: {}", e)),
+    };
+    // boards have no text, so no fonts are needed
+    tree.postprocess(
+        usvg::PostProcessingSteps::default(),
+        &usvg::fontdb::Database::new(),
+    );
+    let size = tree.size.to_int_size();
+    let mut pixmap = match tiny_skia::Pixmap::new(size.width(), size.height()) {
+        Some(p) => p,
+        None => return Err(format!("invalid image size {:?}", size)),
+    };
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    match pixmap.encode_png() {
+        Ok(png) => Ok(png),
+        Err(e) => Err(format!("failed to encode the PNG: {}", e)),
+    }
+}
+
+// where a meeple is drawn on the feature of the unrotated tile, in [-1, 1] with y upwards.
+// features without a spot in tiles.json are drawn between the middles of the sides they touch
+fn spot(d: &TileDefinition, feature: usize) -> (f64, f64) {
+    let f: &FeatureDefinition = &d.features[feature];
+    if let Some([x, y]) = f.spot {
+        return (x, y);
+    }
+    if f.kind == Feature::MonasteryFeature {
+        return (0.0, 0.0);
+    }
+    let mut points = vec![];
+    for (side, fs) in d.side_features.iter().enumerate() {
+        let (dx, dy) = rotate((1.0, 0.0), -(side as i32));
+        // from the left end of the side to the right end seen from the centre, like the features on it
+        let (lx, ly) = (-dy, dx);
+        let parts = if fs.len() == 1 {
+            vec![(fs[0], 0.0)]
+        } else {
+            vec![(fs[0], 0.5), (fs[1], 0.0), (fs[2], -0.5)]
+        };
+        for (i, offset) in parts {
+            if i == feature {
+                points.push((dx * 0.7 + lx * offset, dy * 0.7 + ly * offset));
+            }
+        }
+    }
+    if points.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = points.len() as f64;
+    (
+        points.iter().map(|p| p.0).sum::<f64>() / n,
+        points.iter().map(|p| p.1).sum::<f64>() / n,
+    )
+}
+
+// rotates a point (y upwards) clockwise `rot` times by 90 degrees, as tiles are rotated
+fn rotate((x, y): (f64, f64), rot: i32) -> (f64, f64) {
+    match rot.rem_euclid(4) {
+        0 => (x, y),
+        1 => (y, -x),
+        2 => (-x, -y),
+        _ => (-y, x),
+    }
+}
+
+// a tile drawn with shapes: cities, roads and rivers running from the sides to the centre
+fn tile_shapes(tile: Tile) -> String {
+    let s = TILE_SIZE;
+    let h = TILE_SIZE / 2;
+    let mut out = format!(r##"<rect width="{0}" height="{0}" fill="#8bbf5a"/>"##, s);
+    let d = match tile.definition() {
+        Some(d) => d,
+        None => return out,
+    };
+    // the corners and the middle of each side in the order of `sides`
+    let sides = [
+        ((s, 0), (s, s), (s, h)),
+        ((0, 0), (s, 0), (h, 0)),
+        ((0, 0), (0, s), (0, h)),
+        ((0, s), (s, s), (h, s)),
+    ];
+    for (side, ((ax, ay), (bx, by), _)) in d.sides.iter().zip(sides) {
+        if *side == Side::City {
+            out += &format!(
+                r##"<polygon points="{},{} {},{} {},{}" fill="#c79a5b"/>"##,
+                ax, ay, bx, by, h, h
+            );
+        }
+    }
+    for (side, (_, _, (mx, my))) in d.sides.iter().zip(sides) {
+        let (color, width) = match side {
+            Side::Road => ("#f4f1e8", 6),
+            Side::Water => ("#4fa3d8", 10),
+            _ => continue,
+        };
+        out += &format!(
+            r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"/>"##,
+            mx, my, h, h, color, width
+        );
+    }
+    if d.features
+        .iter()
+        .any(|f| f.kind == Feature::MonasteryFeature)
+    {
+        out += &format!(
+            r##"<rect x="{0}" y="{0}" width="{1}" height="{1}" fill="#a5472f"/>"##,
+            h - 10,
+            20
+        );
+    }
+    out += &format!(
+        r##"<rect width="{0}" height="{0}" fill="none" stroke="#5b5b5b" stroke-width="1"/>"##,
+        s
+    );
+    out
+}
+
+fn meeple(artwork: &Artwork, cx: f64, cy: f64, color: i32, lying: bool) -> String {
+    let size = MEEPLE_SIZE as f64;
+    match artwork.meeples.get(&(color, lying)) {
+        Some(src) => format!(
+            r##"<image x="{}" y="{}" width="{2}" height="{2}" href="{3}"/>"##,
+            cx - size / 2.0,
+            cy - size / 2.0,
+            size,
+            src
+        ),
+        None => {
+            let fill = match MEEPLE_COLORS.get(color as usize) {
+                Some((_, fill)) => *fill,
+                None => MEEPLE_COLORS[0].1,
+            };
+            format!(
+                r##"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="#ffffff" stroke-width="2"/>"##,
+                cx,
+                cy,
+                size / 2.0 - 2.0,
+                fill
+            )
+        }
+    }
+}
+
+#[test]
+fn render_test() {
+    use super::calculate::calculate;
+    use super::notation;
+
+    let record = notation::parse(
+        r#"[Player1 "Alice"]
+[Player2 "Bob"]
+[First "2"]
+[Result "*"]
+
+1. 6r1@0,1m1 2. 1r0@1,0m1
+"#,
+    )
+    .unwrap();
+    let s = calculate(&record.moves, 2, &[], &record.rule_set, false).unwrap();
+    let svg = svg(&s.board, &[4, 2]);
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="160" height="160""#));
+    assert!(svg.ends_with("</svg>"));
+    // the straight road is rotated, and Bob's meeple stands on it
+    assert!(svg.contains(r#"<g transform="translate(80 16) rotate(90 32 32)">"#));
+    let fallback = artwork().meeples.is_empty();
+    if fallback {
+        assert!(svg.contains(r##"fill="#43a047""##));
+    }
+    assert_eq!(svg.matches("<g ").count(), 3);
+    assert_eq!(
+        svg.matches("<image").count() + svg.matches("<circle").count(),
+        if fallback { 2 } else { 3 + 2 }
+    );
+
+    let png = png(&svg).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn spot_test() {
+    // as the frontend draws them
+    let d = Tile::Straight.definition().unwrap();
+    assert_eq!(spot(d, 1), (0.0, 0.0));
+    assert_eq!(rotate((0.0, 0.8), 1), (0.8, 0.0));
+    assert_eq!(rotate((0.0, 0.8), 3), (-0.8, 0.0));
+    // drawn between the sides the feature touches
+    let d = Tile::RiverCurve.definition().unwrap();
+    for f in 0..d.features.len() {
+        let (x, y) = spot(d, f);
+        assert!(x.abs() <= 1.0 && y.abs() <= 1.0);
+    }
+    assert!(tile_shapes(Tile::RiverCurve).contains("#4fa3d8"));
+    assert!(tile_shapes(Tile::Monastery).contains("#a5472f"));
+}
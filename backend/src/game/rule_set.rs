@@ -0,0 +1,88 @@
+use rocket::serde::{Deserialize, Serialize};
+
+use super::calculate::MEEPLE_NUM;
+
+// how fields are scored at the end of a game
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum FieldEdition {
+    // each completed city gives 4 points once, to the players with the most farmers on the fields supplying it
+    First,
+    // each field gives 3 points per completed city it faces
+    Third,
+}
+
+impl FieldEdition {
+    pub fn name(self) -> String {
+        match self {
+            FieldEdition::First => "first".to_string(),
+            FieldEdition::Third => "third".to_string(),
+        }
+    }
+    pub fn from_name(s: &str) -> Option<FieldEdition> {
+        match s {
+            "first" => Some(FieldEdition::First),
+            "third" => Some(FieldEdition::Third),
+            _ => None,
+        }
+    }
+}
+
+// rule variants chosen when a game is created. the default is the rules that have always been used
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RuleSet {
+    pub field_edition: FieldEdition,
+    // at most `MEEPLE_NUM`, since meeple ids are allocated per player
+    pub meeples_per_player: i32,
+    // if false, the player who plays later in the turn order wins when tied
+    pub tie_is_draw: bool,
+    // if true, the player who discards a tile draws another one. otherwise the turn ends
+    pub redraw_on_discard: bool,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            field_edition: FieldEdition::Third,
+            meeples_per_player: MEEPLE_NUM,
+            tie_is_draw: false,
+            redraw_on_discard: true,
+        }
+    }
+}
+
+impl RuleSet {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MEEPLE_NUM).contains(&self.meeples_per_player) {
+            return Err(format!(
+                "meeples per player must be between 1 and {}",
+                MEEPLE_NUM
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn rule_set_test() {
+    assert_eq!(RuleSet::default().validate(), Ok(()));
+    let rule_set: RuleSet =
+        rocket::serde::json::from_str(r#"{"field_edition": "first", "meeples_per_player": 5}"#)
+            .unwrap();
+    assert_eq!(rule_set.field_edition, FieldEdition::First);
+    assert_eq!(rule_set.meeples_per_player, 5);
+    assert!(rule_set.redraw_on_discard);
+    assert_eq!(
+        FieldEdition::from_name(&FieldEdition::First.name()),
+        Some(FieldEdition::First)
+    );
+    let rule_set = RuleSet {
+        meeples_per_player: 8,
+        ..Default::default()
+    };
+    assert_eq!(
+        rule_set.validate(),
+        Err("meeples per player must be between 1 and 7".to_string())
+    );
+}
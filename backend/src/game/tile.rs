@@ -1,7 +1,13 @@
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use rocket::serde::{Deserialize, Serialize};
 
+use super::calculate::{Feature, Side};
+
 #[derive(Hash, Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub enum Tile {
@@ -30,67 +36,186 @@ pub enum Tile {
     Triangle,
     TriangleWithCOA,
     QuadrupleCityWithCOA,
+    // Inns & Cathedrals
+    Cathedral,
+    StraightWithInn,
+    CurveWithInn,
+    TripleRoadWithInn,
+    LeftWithInn,
+    CityCapWithStraightWithInn,
+    CityCapWithCrossroadWithInn,
+    QuadrupleSeparator,
+    SeparatorWithRoad,
+    VerticalSeparatorWithRoad,
+    TriangleWithCityCap,
+    // River
+    RiverSpring,
+    RiverLake,
+    RiverStraight,
+    RiverCurve,
+    RiverWithBridge,
+    RiverWithCities,
+    RiverCurveWithRoad,
+    RiverWithMonastery,
+    RiverCurveWithCity,
+    RiverWithCity,
+}
+
+// expansions that can be added to a game
+#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum Expansion {
+    InnsAndCathedrals,
+    River,
+}
+
+impl Expansion {
+    pub fn name(self) -> String {
+        match self {
+            Expansion::InnsAndCathedrals => "inns_and_cathedrals".to_string(),
+            Expansion::River => "river".to_string(),
+        }
+    }
+    pub fn from_name(s: &str) -> Option<Expansion> {
+        match s {
+            "inns_and_cathedrals" => Some(Expansion::InnsAndCathedrals),
+            "river" => Some(Expansion::River),
+            _ => None,
+        }
+    }
+}
+
+// tile geometry, counts and BGA ids are described in tiles.json.
+// `side_features` lists the feature indexes on each side (either 1 feature, or 3 features when a road or the river splits the side),
+// and `cities` of a field are the indexes of the cities the field is facing.
+// `spot` is where a meeple on the feature is drawn on the unrotated tile, in [-1, 1] with y upwards.
+// tiles of an expansion have `expansion` set and no BGA ids
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct FeatureDefinition {
+    pub kind: Feature,
+    pub open_sides: i32,
+    #[serde(default)]
+    pub coa: bool,
+    #[serde(default)]
+    pub inn: bool,
+    #[serde(default)]
+    pub cathedral: bool,
+    #[serde(default)]
+    pub cities: Vec<usize>,
+    pub bga_position: Option<i32>,
+    pub spot: Option<[f64; 2]>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TileDefinition {
+    pub tile: Tile,
+    pub id: i32,
+    pub expansion: Option<Expansion>,
+    pub count: i32,
+    pub bga_type: Option<i32>,
+    #[serde(default = "default_rotations")]
+    pub rotations: i32,
+    pub sides: [Side; 4],
+    pub features: Vec<FeatureDefinition>,
+    pub side_features: [Vec<usize>; 4],
+}
+
+fn default_rotations() -> i32 {
+    4
+}
+
+static CATALOGUE: OnceLock<Vec<TileDefinition>> = OnceLock::new();
+
+pub fn catalogue() -> &'static Vec<TileDefinition> {
+    CATALOGUE.get_or_init(|| {
+        let defs: Vec<TileDefinition> = match serde_json::from_str(include_str!("tiles.json")) {
+            Ok(defs) => defs,
+            Err(e) => panic!("failed to parse tiles.json: {:?}", e),
+        };
+        for (i, d) in defs.iter().enumerate() {
+            assert_eq!(d.id, i as i32, "tiles.json must be ordered by id");
+            for fs in &d.side_features {
+                assert!(fs.len() == 1 || fs.len() == 3);
+                assert!(fs.iter().all(|f| *f < d.features.len()));
+            }
+        }
+        defs
+    })
 }
 
+// the ids of the tiles in tiles.json, indexed by the variants of `Tile`
+static IDS: OnceLock<Vec<i32>> = OnceLock::new();
+
 impl Tile {
+    // the id in tiles.json (which is also the id saved in the database), or -1 for `Invalid`
     pub fn to_id(self) -> i32 {
-        match self {
-            Tile::StartingTile => 0,
-            Tile::Monastery => 1,
-            Tile::MonasteryWithRoad => 2,
-            Tile::CityCapWithCrossroad => 3,
-            Tile::TriangleWithRoad => 4,
-            Tile::TriangleWithRoadWithCOA => 5,
-            Tile::Straight => 6,
-            Tile::CityCap => 7,
-            Tile::Separator => 8,
-            Tile::TripleRoad => 9,
-            Tile::Curve => 10,
-            Tile::QuadrupleRoad => 11,
-            Tile::Connector => 12,
-            Tile::ConnectorWithCOA => 13,
-            Tile::Left => 14,
-            Tile::Right => 15,
-            Tile::TripleCity => 16,
-            Tile::TripleCityWithCOA => 17,
-            Tile::VerticalSeparator => 18,
-            Tile::TripleCityWithRoad => 19,
-            Tile::TripleCityWithRoadWithCOA => 20,
-            Tile::Triangle => 21,
-            Tile::TriangleWithCOA => 22,
-            Tile::QuadrupleCityWithCOA => 23,
-            Tile::Invalid => -1,
+        let ids = IDS.get_or_init(|| {
+            let mut ids = vec![];
+            for d in catalogue() {
+                let v = d.tile as usize;
+                if ids.len() <= v {
+                    ids.resize(v + 1, -1);
+                }
+                assert_eq!(ids[v], -1, "{:?} is listed twice in tiles.json", d.tile);
+                ids[v] = d.id;
+            }
+            ids
+        });
+        match ids.get(self as usize) {
+            Some(id) => *id,
+            None => -1,
+        }
+    }
+    pub fn definition(self) -> Option<&'static TileDefinition> {
+        if self == Tile::Invalid {
+            return None;
+        }
+        catalogue().get(self.to_id() as usize)
+    }
+    pub fn has_monastery(self) -> bool {
+        match self.definition() {
+            Some(d) => d
+                .features
+                .iter()
+                .any(|f| f.kind == Feature::MonasteryFeature),
+            None => false,
+        }
+    }
+    // whether the tile is used in a game with `expansions`
+    pub fn is_available(self, expansions: &[Expansion]) -> bool {
+        match self.definition() {
+            Some(d) => match d.expansion {
+                Some(e) => expansions.contains(&e),
+                None => true,
+            },
+            None => false,
+        }
+    }
+    // whether the tile is a part of the river
+    pub fn is_river(self) -> bool {
+        match self.definition() {
+            Some(d) => d.sides.contains(&Side::Water),
+            None => false,
+        }
+    }
+    // number of rotations that give different tiles (e.g., 1 for a monastery, 2 for a straight road)
+    pub fn rotations(self) -> i32 {
+        match self.definition() {
+            Some(d) => d.rotations,
+            None => 4,
         }
     }
 }
 
 pub fn to_tile(id: i32) -> Tile {
-    match id {
-        0 => Tile::StartingTile,
-        1 => Tile::Monastery,
-        2 => Tile::MonasteryWithRoad,
-        3 => Tile::CityCapWithCrossroad,
-        4 => Tile::TriangleWithRoad,
-        5 => Tile::TriangleWithRoadWithCOA,
-        6 => Tile::Straight,
-        7 => Tile::CityCap,
-        8 => Tile::Separator,
-        9 => Tile::TripleRoad,
-        10 => Tile::Curve,
-        11 => Tile::QuadrupleRoad,
-        12 => Tile::Connector,
-        13 => Tile::ConnectorWithCOA,
-        14 => Tile::Left,
-        15 => Tile::Right,
-        16 => Tile::TripleCity,
-        17 => Tile::TripleCityWithCOA,
-        18 => Tile::VerticalSeparator,
-        19 => Tile::TripleCityWithRoad,
-        20 => Tile::TripleCityWithRoadWithCOA,
-        21 => Tile::Triangle,
-        22 => Tile::TriangleWithCOA,
-        23 => Tile::QuadrupleCityWithCOA,
-        _ => Tile::Invalid,
+    if id < 0 {
+        return Tile::Invalid;
+    }
+    match catalogue().get(id as usize) {
+        Some(d) => d.tile,
+        None => Tile::Invalid,
     }
 }
 
@@ -108,7 +233,7 @@ pub fn discard_test_tiles() -> Vec<Tile> {
 #[allow(dead_code)]
 pub fn discard_test_remaining_tiles(out_tiles: Vec<Tile>) -> Vec<Tile> {
     let mut map = HashMap::new();
-    for al in &tiles() {
+    for al in &tiles(&[]) {
         map.entry(*al).or_insert(0);
     }
     map.entry(Tile::StartingTile).or_insert(0);
@@ -128,171 +253,102 @@ pub fn discard_test_remaining_tiles(out_tiles: Vec<Tile>) -> Vec<Tile> {
     tiles
 }
 
-pub fn tiles() -> Vec<Tile> {
+pub fn tiles(expansions: &[Expansion]) -> Vec<Tile> {
     let mut tiles = vec![];
-    for _ in 0..3 {
-        tiles.push(Tile::StartingTile);
-    }
-    for _ in 0..4 {
-        tiles.push(Tile::Monastery);
-    }
-    for _ in 0..2 {
-        tiles.push(Tile::MonasteryWithRoad);
-    }
-    for _ in 0..3 {
-        tiles.push(Tile::CityCapWithCrossroad);
-    }
-    for _ in 0..3 {
-        tiles.push(Tile::TriangleWithRoad);
-    }
-    for _ in 0..2 {
-        tiles.push(Tile::TriangleWithRoadWithCOA);
-    }
-    for _ in 0..8 {
-        tiles.push(Tile::Straight);
-    }
-    for _ in 0..5 {
-        tiles.push(Tile::CityCap);
-    }
-    for _ in 0..2 {
-        tiles.push(Tile::Separator);
-    }
-    for _ in 0..4 {
-        tiles.push(Tile::TripleRoad);
-    }
-    for _ in 0..9 {
-        tiles.push(Tile::Curve);
-    }
-    for _ in 0..1 {
-        tiles.push(Tile::QuadrupleRoad);
-    }
-    for _ in 0..1 {
-        tiles.push(Tile::Connector);
-    }
-    for _ in 0..2 {
-        tiles.push(Tile::ConnectorWithCOA);
-    }
-    for _ in 0..3 {
-        tiles.push(Tile::Left);
-    }
-    for _ in 0..3 {
-        tiles.push(Tile::Right);
-    }
-    for _ in 0..3 {
-        tiles.push(Tile::TripleCity);
-    }
-    for _ in 0..1 {
-        tiles.push(Tile::TripleCityWithCOA);
-    }
-    for _ in 0..3 {
-        tiles.push(Tile::VerticalSeparator);
-    }
-    for _ in 0..1 {
-        tiles.push(Tile::TripleCityWithRoad);
-    }
-    for _ in 0..2 {
-        tiles.push(Tile::TripleCityWithRoadWithCOA);
-    }
-    for _ in 0..3 {
-        tiles.push(Tile::Triangle);
-    }
-    for _ in 0..2 {
-        tiles.push(Tile::TriangleWithCOA);
-    }
-    for _ in 0..1 {
-        tiles.push(Tile::QuadrupleCityWithCOA);
+    for d in catalogue() {
+        if !d.tile.is_available(expansions) {
+            continue;
+        }
+        let mut count = d.count;
+        // one starting tile is placed before the game starts
+        if d.tile == starting_tile(expansions) {
+            count -= 1;
+        }
+        for _ in 0..count {
+            tiles.push(d.tile);
+        }
     }
-    assert_eq!(tiles.len(), 71);
     tiles
 }
 
-pub fn remaining_tiles(out_tiles: Vec<Tile>) -> Vec<Tile> {
+pub fn remaining_tiles(out_tiles: Vec<Tile>, expansions: &[Expansion]) -> Vec<Tile> {
     let mut map = HashMap::new();
-    for al in &tiles() {
-        map.entry(*al).or_insert(0);
-    }
     for ot in &out_tiles {
-        map.entry(*ot).and_modify(|v| *v += 1);
+        map.entry(*ot).and_modify(|v| *v += 1).or_insert(1);
     }
     let mut tiles = vec![];
-    for _ in 0..4 - *map.get(&Tile::StartingTile).unwrap() {
-        tiles.push(Tile::StartingTile);
-    }
-    for _ in 0..4 - *map.get(&Tile::Monastery).unwrap() {
-        tiles.push(Tile::Monastery);
-    }
-    for _ in 0..2 - *map.get(&Tile::MonasteryWithRoad).unwrap() {
-        tiles.push(Tile::MonasteryWithRoad);
-    }
-    for _ in 0..3 - *map.get(&Tile::CityCapWithCrossroad).unwrap() {
-        tiles.push(Tile::CityCapWithCrossroad);
-    }
-    for _ in 0..3 - *map.get(&Tile::TriangleWithRoad).unwrap() {
-        tiles.push(Tile::TriangleWithRoad);
-    }
-    for _ in 0..2 - *map.get(&Tile::TriangleWithRoadWithCOA).unwrap() {
-        tiles.push(Tile::TriangleWithRoadWithCOA);
-    }
-    for _ in 0..8 - *map.get(&Tile::Straight).unwrap() {
-        tiles.push(Tile::Straight);
-    }
-    for _ in 0..5 - *map.get(&Tile::CityCap).unwrap() {
-        tiles.push(Tile::CityCap);
-    }
-    for _ in 0..2 - *map.get(&Tile::Separator).unwrap() {
-        tiles.push(Tile::Separator);
-    }
-    for _ in 0..4 - *map.get(&Tile::TripleRoad).unwrap() {
-        tiles.push(Tile::TripleRoad);
-    }
-    for _ in 0..9 - *map.get(&Tile::Curve).unwrap() {
-        tiles.push(Tile::Curve);
-    }
-    for _ in 0..1 - *map.get(&Tile::QuadrupleRoad).unwrap() {
-        tiles.push(Tile::QuadrupleRoad);
-    }
-    for _ in 0..1 - *map.get(&Tile::Connector).unwrap() {
-        tiles.push(Tile::Connector);
-    }
-    for _ in 0..2 - *map.get(&Tile::ConnectorWithCOA).unwrap() {
-        tiles.push(Tile::ConnectorWithCOA);
-    }
-    for _ in 0..3 - *map.get(&Tile::Left).unwrap() {
-        tiles.push(Tile::Left);
-    }
-    for _ in 0..3 - *map.get(&Tile::Right).unwrap() {
-        tiles.push(Tile::Right);
-    }
-    for _ in 0..3 - *map.get(&Tile::TripleCity).unwrap() {
-        tiles.push(Tile::TripleCity);
-    }
-    for _ in 0..1 - *map.get(&Tile::TripleCityWithCOA).unwrap() {
-        tiles.push(Tile::TripleCityWithCOA);
-    }
-    for _ in 0..3 - *map.get(&Tile::VerticalSeparator).unwrap() {
-        tiles.push(Tile::VerticalSeparator);
-    }
-    for _ in 0..1 - *map.get(&Tile::TripleCityWithRoad).unwrap() {
-        tiles.push(Tile::TripleCityWithRoad);
-    }
-    for _ in 0..2 - *map.get(&Tile::TripleCityWithRoadWithCOA).unwrap() {
-        tiles.push(Tile::TripleCityWithRoadWithCOA);
-    }
-    for _ in 0..3 - *map.get(&Tile::Triangle).unwrap() {
-        tiles.push(Tile::Triangle);
+    for d in catalogue() {
+        if !d.tile.is_available(expansions) {
+            continue;
+        }
+        let out_count = match map.get(&d.tile) {
+            Some(c) => *c,
+            None => 0,
+        };
+        for _ in 0..d.count - out_count {
+            tiles.push(d.tile);
+        }
     }
-    for _ in 0..2 - *map.get(&Tile::TriangleWithCOA).unwrap() {
-        tiles.push(Tile::TriangleWithCOA);
+    tiles
+}
+
+// the tile placed at (0, 0) before the game starts
+pub fn starting_tile(expansions: &[Expansion]) -> Tile {
+    if expansions.contains(&Expansion::River) {
+        Tile::RiverSpring
+    } else {
+        Tile::StartingTile
     }
-    for _ in 0..1 - *map.get(&Tile::QuadrupleCityWithCOA).unwrap() {
-        tiles.push(Tile::QuadrupleCityWithCOA);
+}
+
+// tiles of a game in the order they are drawn, shuffled with the game's seed.
+// ChaCha8 is used since, unlike `StdRng`, its output is stable across `rand` versions.
+// with the River, the river tiles come first and the lake is the last of them
+pub fn deck(seed: u64, expansions: &[Expansion]) -> Vec<Tile> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let tiles = tiles(expansions);
+    let mut river_tiles: Vec<Tile> = tiles
+        .iter()
+        .filter(|t| t.is_river() && **t != Tile::RiverLake)
+        .copied()
+        .collect();
+    let lake: Vec<Tile> = tiles
+        .iter()
+        .filter(|t| **t == Tile::RiverLake)
+        .copied()
+        .collect();
+    let mut others: Vec<Tile> = tiles.iter().filter(|t| !t.is_river()).copied().collect();
+    river_tiles.shuffle(&mut rng);
+    others.shuffle(&mut rng);
+    river_tiles.into_iter().chain(lake).chain(others).collect()
+}
+
+// the tile drawn after `out_tiles` (including the starting tile), or `Invalid` when the deck has run out.
+// each out tile uses up its first occurrence in the deck, so the draws don't depend on the order tiles were placed
+pub fn draw(deck: &[Tile], out_tiles: &[Tile], expansions: &[Expansion]) -> Tile {
+    let mut out_counts = HashMap::new();
+    for ot in out_tiles {
+        out_counts.entry(*ot).and_modify(|v| *v += 1).or_insert(1);
+    }
+    // the starting tile is not in the deck
+    if let Some(c) = out_counts.get_mut(&starting_tile(expansions)) {
+        *c -= 1;
+    }
+    for t in deck {
+        match out_counts.get_mut(t) {
+            Some(c) if *c > 0 => *c -= 1,
+            _ => return *t,
+        }
     }
-    tiles
+    Tile::Invalid
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{remaining_tiles, Tile::*};
+    use super::super::calculate::{Feature, Feature::*, Side};
+    use super::{
+        catalogue, deck, draw, remaining_tiles, tiles, to_tile, Expansion::*, Tile, Tile::*,
+    };
     #[test]
     fn test_remaining_tiles() {
         let out_tiles = Vec::from([
@@ -372,7 +428,93 @@ mod tests {
             VerticalSeparator,
             TripleCityWithRoadWithCOA,
         ]);
-        let remaining_tiles = remaining_tiles(out_tiles);
+        let remaining_tiles = remaining_tiles(out_tiles, &[]);
         assert_eq!(remaining_tiles, exp_remaining_tiles);
     }
+
+    #[test]
+    fn test_deck() {
+        // the same seed always gives the same deck
+        assert_eq!(deck(42, &[]), deck(42, &[]));
+        assert_ne!(deck(42, &[]), deck(43, &[]));
+        let mut sorted = deck(42, &[]);
+        sorted.sort_by_key(|t| t.to_id());
+        let mut all = tiles(&[]);
+        all.sort_by_key(|t| t.to_id());
+        assert_eq!(sorted, all);
+
+        // the river comes first, then the lake, then the others
+        let river_deck = deck(42, &[River]);
+        assert!(river_deck[..10]
+            .iter()
+            .all(|t| t.is_river() && *t != RiverLake));
+        assert_eq!(river_deck[10], RiverLake);
+        assert!(river_deck[11..].iter().all(|t| !t.is_river()));
+
+        // tiles are drawn from the front of the deck
+        let d = deck(7, &[]);
+        let mut out_tiles = vec![StartingTile];
+        for t in &d {
+            assert_eq!(draw(&d, &out_tiles, &[]), *t);
+            out_tiles.push(*t);
+        }
+        assert_eq!(draw(&d, &out_tiles, &[]), Tile::Invalid);
+
+        // tiles placed out of the drawing order use up their first occurrence
+        let d = vec![Curve, Straight, Curve, Monastery];
+        assert_eq!(draw(&d, &[StartingTile, Straight], &[]), Curve);
+        assert_eq!(draw(&d, &[StartingTile, Straight, Curve], &[]), Curve);
+        assert_eq!(
+            draw(&d, &[StartingTile, Curve, Straight, Curve], &[]),
+            Monastery
+        );
+    }
+
+    #[test]
+    fn test_catalogue() {
+        assert_eq!(tiles(&[]).len(), 71);
+        assert_eq!(tiles(&[InnsAndCathedrals]).len(), 71 + 12);
+        assert!(!Cathedral.is_available(&[]));
+        assert!(Cathedral.is_available(&[InnsAndCathedrals]));
+        assert!(Tile::Invalid.definition().is_none());
+        assert_eq!(Tile::Invalid.to_id(), -1);
+        assert_eq!(to_tile(-1), Tile::Invalid);
+        assert_eq!(to_tile(catalogue().len() as i32), Tile::Invalid);
+        assert!(Monastery.has_monastery());
+        assert!(MonasteryWithRoad.has_monastery());
+        assert!(!StartingTile.has_monastery());
+        assert_eq!(Straight.rotations(), 2);
+        assert_eq!(Curve.rotations(), 4);
+        assert_eq!(tiles(&[River]).len(), 72 + 11);
+        assert!(RiverCurve.is_river());
+        assert!(!Straight.is_river());
+        assert!(!RiverSpring.is_available(&[InnsAndCathedrals]));
+        // the same tile is never listed twice
+        for d in catalogue() {
+            // features on each side must agree with the side
+            for (side, fs) in d.sides.iter().zip(d.side_features.iter()) {
+                let kinds: Vec<Feature> = fs.iter().map(|f| d.features[*f].kind.clone()).collect();
+                match side {
+                    Side::Road => assert_eq!(kinds, vec![FieldFeature, RoadFeature, FieldFeature]),
+                    Side::City => assert_eq!(kinds, vec![CityFeature]),
+                    Side::Water => {
+                        assert_eq!(kinds, vec![FieldFeature, RiverFeature, FieldFeature])
+                    }
+                    _ => assert_eq!(kinds, vec![FieldFeature]),
+                }
+            }
+            assert_eq!(catalogue().iter().filter(|e| e.tile == d.tile).count(), 1);
+            assert_eq!(d.tile.to_id(), d.id);
+            assert_eq!(to_tile(d.id), d.tile);
+            if d.bga_type.is_some() {
+                assert_eq!(
+                    catalogue()
+                        .iter()
+                        .filter(|e| e.bga_type == d.bga_type)
+                        .count(),
+                    1
+                );
+            }
+        }
+    }
 }
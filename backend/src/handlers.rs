@@ -17,7 +17,10 @@ use rocket_multipart_form_data::{
     mime, MultipartFormData, MultipartFormDataField, MultipartFormDataOptions,
 };
 
+use crate::auth;
+use crate::challenge;
 use crate::database;
+use crate::error::{bad_request_error, forbidden_error, Error};
 use crate::event;
 use crate::game;
 use crate::game::tile;
@@ -30,7 +33,6 @@ pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 #[serde(crate = "rocket::serde")]
 pub struct CreateTileMove {
     pub game_id: Option<i32>,
-    pub player_id: i32,
     pub tile_id: i32,
     pub rot: i32,
     pub pos_y: i32,
@@ -41,7 +43,6 @@ pub struct CreateTileMove {
 #[serde(crate = "rocket::serde")]
 pub struct CreateDiscardMove {
     pub game_id: Option<i32>,
-    pub player_id: i32,
     pub tile_id: i32,
 }
 
@@ -49,7 +50,6 @@ pub struct CreateDiscardMove {
 #[serde(crate = "rocket::serde")]
 pub struct CreateMove {
     pub game_id: Option<i32>,
-    pub player_id: i32,
     pub tile_id: i32,
     pub rot: i32,
     pub pos_y: i32,
@@ -66,19 +66,16 @@ pub struct CreateGame {
     pub player0_color: i32,
     pub player1_id: i32,
     pub player1_color: i32,
+    // the third and later players for games with more than two players
+    pub additional_player_ids: Option<Vec<i32>>,
+    pub additional_player_colors: Option<Vec<i32>>,
     pub is_rated: Option<bool>,
-}
-
-#[derive(Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct CreateWaitingGame {
-    pub player_id: i32,
-}
-
-#[derive(Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct DeleteWaitingGame {
-    pub player_id: i32,
+    pub expansions: Option<Vec<game::tile::Expansion>>,
+    pub rule_set: Option<game::rule_set::RuleSet>,
+    // an ended game to play again with the same deck
+    pub rematch_of: Option<i32>,
+    // the difficulty the bots play at (see `GET /ai-levels`). bots play with their own agents without it
+    pub ai_level: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -123,11 +120,20 @@ pub fn get_players(db: &State<DbPool>) -> (Status, (ContentType, String)) {
 pub fn update_player(
     player_id: i32,
     params: Json<player::UpdatePlayer>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    if caller.id != player_id {
+        let e = forbidden_error("players can only update themselves".to_string());
+        return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap()));
+    }
     match player::update_player(
         db.inner(),
-        player_id,
+        caller.id,
         params.name.clone(),
         params.meeple_color,
         params.tile_edition.clone(),
@@ -140,13 +146,18 @@ pub fn update_player(
 #[post("/players/create", format = "application/json", data = "<params>")]
 pub fn create_player(
     params: Json<player::CreatePlayer>,
+    user: Result<auth::AuthenticatedUser, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let user = match user {
+        Ok(u) => u,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
     match database::create_player(
         db.inner(),
         params.name.clone(),
         params.email.clone(),
-        params.user_id.clone(),
+        user.claims.sub,
         0, // red
     ) {
         Ok(player) => (Status::Ok, (ContentType::JSON, to_string(&player).unwrap())),
@@ -175,75 +186,196 @@ pub fn get_game(game_id: i32, db: &State<DbPool>) -> (Status, (ContentType, Stri
     }
 }
 
+#[get("/games/<game_id>/deck")]
+pub fn get_deck(game_id: i32, db: &State<DbPool>) -> (Status, (ContentType, String)) {
+    match game::get_deck(db.inner(), game_id) {
+        Ok(deck) => (Status::Ok, (ContentType::JSON, to_string(&deck).unwrap())),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+// bots make the opening moves of a game in which a bot plays first
+fn spawn_first_ai_move(db: DbPool, queue: Sender<event::Event>, game_id: i32) {
+    thread::spawn(move || {
+        thread::sleep(std::time::Duration::from_secs(1));
+        make_ai_moves(&db, &queue, game_id);
+    });
+}
+
+// bots move as long as the current seat is a bot's, so that consecutive bot seats all move
+// and a bot's discard hands the turn back to a human when the tile isn't redrawn
+fn make_ai_moves(db: &DbPool, queue: &Sender<event::Event>, game_id: i32) {
+    loop {
+        match game::get_game(db, game_id) {
+            Ok(gm) => {
+                if gm.winner_player_id.is_some() || gm.is_draw || !gm.is_agent_turn() {
+                    break;
+                }
+            }
+            Err(e) => {
+                eprintln!("failed to load game {}: {:?}", game_id, e.detail);
+                break;
+            }
+        }
+
+        let (mvs, complete_events, game_end) = match game::wait_ai_move(db, game_id) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                eprintln!("failed to make an AI move in game {}: {:?}", game_id, e.detail);
+                break;
+            }
+        };
+
+        // tile move, meeple move
+        if mvs.len() == 2 {
+            match (&mvs[0], &mvs[1]) {
+                (game::mov::Move::TMove(tm), game::mov::Move::MMove(mm)) => {
+                    let _ = queue.send(event::Event {
+                        id: game_id,
+                        name: "move_created_event".to_string(),
+                        player_id: tm.player_id,
+                        tile: tm.tile,
+                        rot: tm.rot,
+                        tile_pos: tm.pos,
+                        meeple_id: mm.meeple_id,
+                        meeple_pos: mm.meeple_pos,
+                        complete_events,
+                    });
+                }
+                _ => {
+                    eprintln!("unexpected AI moves in game {}: {:?}", game_id, mvs);
+                    break;
+                }
+            }
+        } else if mvs.len() == 1 {
+            match &mvs[0] {
+                game::mov::Move::DMove(dm) => {
+                    let _ = queue.send(event::Event {
+                        id: game_id,
+                        name: "move_created_event".to_string(),
+                        player_id: dm.player_id,
+                        tile: dm.tile,
+                        rot: -1,
+                        tile_pos: (-1, -1),
+                        meeple_id: -1,
+                        meeple_pos: -1,
+                        complete_events,
+                    });
+                }
+                _ => {
+                    eprintln!("unexpected AI moves in game {}: {:?}", game_id, mvs);
+                    break;
+                }
+            }
+        } else {
+            eprintln!("unexpected AI moves in game {}: {:?}", game_id, mvs);
+            break;
+        }
+
+        if game_end.is_some() {
+            send_game_ended(queue, game_id, game_end);
+            break;
+        }
+    }
+}
+
+#[get("/games/<game_id>/export")]
+pub fn export_game(game_id: i32, db: &State<DbPool>) -> (Status, (ContentType, String)) {
+    match game::export_game(db.inner(), game_id) {
+        Ok(text) => (Status::Ok, (ContentType::Plain, text)),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[post("/games/import", format = "text/plain", data = "<text>")]
+pub fn import_game(
+    text: String,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, String)) {
+    if let Err(e) = caller {
+        return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap()));
+    }
+    match game::import_game(db.inner(), &text) {
+        Ok(game) => (Status::Ok, (ContentType::JSON, to_string(&game).unwrap())),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[get("/games/<game_id>/export/jcz")]
+pub fn export_jcz_game(game_id: i32, db: &State<DbPool>) -> (Status, (ContentType, String)) {
+    match game::export_jcz_game(db.inner(), game_id) {
+        Ok(data) => (Status::Ok, (ContentType::JSON, data)),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[post("/games/import/jcz", format = "application/json", data = "<data>")]
+pub fn import_jcz_game(
+    data: String,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, String)) {
+    if let Err(e) = caller {
+        return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap()));
+    }
+    match game::import_jcz_game(db.inner(), &data) {
+        Ok(game) => (Status::Ok, (ContentType::JSON, to_string(&game).unwrap())),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
 #[post("/games/create", format = "application/json", data = "<params>")]
 pub fn create_game(
     params: Json<CreateGame>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     queue: &State<Sender<event::Event>>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
     let is_rated = match params.is_rated {
         Some(ir) => ir,
         None => false,
     };
+    let mut player_ids = vec![params.player0_id, params.player1_id];
+    if let Some(ids) = &params.additional_player_ids {
+        player_ids.extend(ids);
+    }
+    if !player_ids.contains(&caller.id) {
+        let e = forbidden_error("players can only create games they play in".to_string());
+        return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap()));
+    }
+    let mut player_colors = vec![params.player0_color, params.player1_color];
+    if let Some(colors) = &params.additional_player_colors {
+        player_colors.extend(colors);
+    }
+    let expansions = match &params.expansions {
+        Some(es) => es.clone(),
+        None => vec![],
+    };
+    let rule_set = match &params.rule_set {
+        Some(rs) => rs.clone(),
+        None => game::rule_set::RuleSet::default(),
+    };
     let cloned_db = db.inner().clone();
     let q = queue.inner().clone();
 
     match game::create_game(
         db.inner(),
-        params.player0_id,
-        params.player1_id,
-        params.player0_color,
-        params.player1_color,
+        player_ids,
+        player_colors,
         is_rated,
+        expansions,
+        rule_set,
+        params.rematch_of,
+        params.ai_level.clone(),
     ) {
         Ok(game) => {
-            if game.current_player_id == Some(1) {
-                thread::spawn(move || {
-                    thread::sleep(std::time::Duration::from_secs(1));
-
-                    let (mvs, complete_events) = game::wait_ai_move(&cloned_db, game.id).unwrap();
-
-                    // tile move, meeple move
-                    if mvs.len() == 2 {
-                        match (&mvs[0], &mvs[1]) {
-                            (game::mov::Move::TMove(tm), game::mov::Move::MMove(mm)) => {
-                                let _ = q.send(event::Event {
-                                    id: game.id,
-                                    name: "move_created_event".to_string(),
-                                    player_id: tm.player_id,
-                                    tile: tm.tile,
-                                    rot: tm.rot,
-                                    tile_pos: tm.pos,
-                                    meeple_id: mm.meeple_id,
-                                    meeple_pos: mm.meeple_pos,
-                                    complete_events,
-                                });
-                            }
-                            _ => {
-                                panic!("invalid event");
-                            }
-                        }
-                    } else if mvs.len() == 1 {
-                        match &mvs[0] {
-                            game::mov::Move::DMove(dm) => {
-                                let _ = q.send(event::Event {
-                                    id: game.id,
-                                    name: "move_created_event".to_string(),
-                                    player_id: dm.player_id,
-                                    tile: dm.tile,
-                                    rot: -1,
-                                    tile_pos: (-1, -1),
-                                    meeple_id: -1,
-                                    meeple_pos: -1,
-                                    complete_events,
-                                });
-                            }
-                            _ => {
-                                panic!("invalid event");
-                            }
-                        }
-                    }
-                });
+            if game.is_agent_turn() {
+                spawn_first_ai_move(cloned_db, q, game.id);
             }
 
             (Status::Ok, (ContentType::JSON, to_string(&game).unwrap()))
@@ -252,30 +384,81 @@ pub fn create_game(
     }
 }
 
-#[post(
-    "/waiting-games/delete",
-    format = "application/json",
-    data = "<params>"
-)]
+#[get("/ai-levels", format = "application/json")]
+pub fn get_ai_levels(db: &State<DbPool>) -> (Status, (ContentType, String)) {
+    match game::get_ai_levels(db.inner()) {
+        Ok(levels) => (Status::Ok, (ContentType::JSON, to_string(&levels).unwrap())),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[get("/challenges/today", format = "application/json")]
+pub fn get_today_challenge(db: &State<DbPool>) -> (Status, (ContentType, String)) {
+    match challenge::get_today_challenge(db.inner()) {
+        Ok(c) => (Status::Ok, (ContentType::JSON, to_string(&c).unwrap())),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[post("/challenges/today/games/create", format = "application/json")]
+pub fn create_challenge_game(
+    caller: Result<auth::AuthenticatedPlayer, Error>,
+    queue: &State<Sender<event::Event>>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match challenge::create_challenge_game(db.inner(), &caller) {
+        Ok(game) => {
+            if game.is_agent_turn() {
+                spawn_first_ai_move(db.inner().clone(), queue.inner().clone(), game.id);
+            }
+            (Status::Ok, (ContentType::JSON, to_string(&game).unwrap()))
+        }
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[get("/challenges/<challenge_id>/leaderboard", format = "application/json")]
+pub fn get_challenge_leaderboard(
+    challenge_id: i32,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, String)) {
+    match challenge::get_leaderboard(db.inner(), challenge_id) {
+        Ok(entries) => (
+            Status::Ok,
+            (ContentType::JSON, to_string(&entries).unwrap()),
+        ),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[post("/waiting-games/delete", format = "application/json")]
 pub fn delete_waiting_game(
-    params: Json<DeleteWaitingGame>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match game::delete_waiting_games(db.inner(), params.player_id) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match game::delete_waiting_games(db.inner(), caller.id) {
         Ok(games) => (Status::Ok, (ContentType::JSON, to_string(&games).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
 }
-#[post(
-    "/waiting-games/create",
-    format = "application/json",
-    data = "<params>"
-)]
+#[post("/waiting-games/create", format = "application/json")]
 pub fn create_waiting_game(
-    params: Json<CreateWaitingGame>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match game::create_waiting_game(db.inner(), params.player_id) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match game::create_waiting_game(db.inner(), caller.id) {
         Ok(games) => (Status::Ok, (ContentType::JSON, to_string(&games).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -295,9 +478,21 @@ pub fn get_waiting_games(db: &State<DbPool>) -> (Status, (ContentType, String))
 pub fn update_waiting_game(
     id: Option<i32>,
     params: Json<game::UpdateWaitingGame>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match game::update_waiting_game(db.inner(), id.unwrap(), params.game_id) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    let id = match id {
+        Some(id) => id,
+        None => {
+            let e = bad_request_error("waiting game id must be a number".to_string());
+            return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap()));
+        }
+    };
+    match game::update_waiting_game(db.inner(), caller.id, id, params.game_id) {
         Ok(games) => (Status::Ok, (ContentType::JSON, to_string(&games).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -310,12 +505,17 @@ pub fn update_waiting_game(
 )]
 pub fn try_create_tile_move(
     params: Json<CreateTileMove>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
     let r = game::try_create_tile_move(
         db.inner(),
         params.game_id,
-        params.player_id,
+        caller.id,
         tile::to_tile(params.tile_id),
         params.rot,
         (params.pos_y, params.pos_x),
@@ -334,20 +534,25 @@ pub fn try_create_tile_move(
 )]
 pub fn create_discard_move(
     params: Json<CreateDiscardMove>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     queue: &State<Sender<event::Event>>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
     match game::create_discard_move(
         db.inner(),
         params.game_id,
-        params.player_id,
+        caller.id,
         tile::to_tile(params.tile_id),
     ) {
-        Ok(res) => {
+        Ok((res, game_end)) => {
             let _ = queue.send(event::Event {
                 id: params.game_id.unwrap(),
                 name: "move_created_event".to_string(),
-                player_id: params.player_id,
+                player_id: caller.id,
                 tile: tile::to_tile(params.tile_id),
                 rot: -1,
                 tile_pos: (-1, -1),
@@ -355,6 +560,7 @@ pub fn create_discard_move(
                 meeple_pos: -1,
                 complete_events: vec![],
             });
+            send_game_ended(queue.inner(), params.game_id.unwrap(), game_end);
             (Status::Ok, (ContentType::JSON, to_string(&res).unwrap()))
         }
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
@@ -364,15 +570,20 @@ pub fn create_discard_move(
 #[post("/moves/create", format = "application/json", data = "<params>")]
 pub fn create_move(
     params: Json<CreateMove>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     queue: &State<Sender<event::Event>>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
     let q = queue.inner().clone();
     let cloned_db = db.inner().clone();
     let r = game::create_move(
         db.inner(),
         params.game_id,
-        params.player_id,
+        caller.id,
         tile::to_tile(params.tile_id),
         params.rot,
         (params.pos_y, params.pos_x),
@@ -395,55 +606,10 @@ pub fn create_move(
                             meeple_pos: mm.meeple_pos,
                             complete_events: res.complete_events.clone(),
                         });
+                        send_game_ended(&q, gid, res.game_end.clone());
 
-                        if params.wait_ai_move {
-                            thread::spawn(move || loop {
-                                let (mvs, complete_events) =
-                                    game::wait_ai_move(&cloned_db, gid).unwrap();
-                                if mvs.len() == 2 {
-                                    match (&mvs[0], &mvs[1]) {
-                                        (
-                                            game::mov::Move::TMove(tm),
-                                            game::mov::Move::MMove(mm),
-                                        ) => {
-                                            let _ = q.send(event::Event {
-                                                id: gid,
-                                                name: "move_created_event".to_string(),
-                                                player_id: tm.player_id,
-                                                tile: tm.tile,
-                                                rot: tm.rot,
-                                                tile_pos: tm.pos,
-                                                meeple_id: mm.meeple_id,
-                                                meeple_pos: mm.meeple_pos,
-                                                complete_events,
-                                            });
-                                        }
-                                        _ => {
-                                            panic!("invalid response");
-                                        }
-                                    }
-                                    break;
-                                } else if mvs.len() == 1 {
-                                    match &mvs[0] {
-                                        game::mov::Move::DMove(dm) => {
-                                            let _ = q.send(event::Event {
-                                                id: gid,
-                                                name: "move_created_event".to_string(),
-                                                player_id: dm.player_id,
-                                                tile: dm.tile,
-                                                rot: -1,
-                                                tile_pos: (-1, -1),
-                                                meeple_id: -1,
-                                                meeple_pos: -1,
-                                                complete_events,
-                                            });
-                                        }
-                                        _ => {
-                                            panic!("invalid response");
-                                        }
-                                    }
-                                }
-                            });
+                        if params.wait_ai_move && res.game_end.is_none() {
+                            thread::spawn(move || make_ai_moves(&cloned_db, &q, gid));
                         }
                     }
                     _ => {
@@ -457,6 +623,24 @@ pub fn create_move(
     }
 }
 
+// tells clients that the game ended, with the winner and the final scoring
+fn send_game_ended(queue: &Sender<event::Event>, game_id: i32, game_end: Option<game::GameEnd>) {
+    if let Some(ge) = game_end {
+        let _ = queue.send(event::Event {
+            id: game_id,
+            name: "game_ended".to_string(),
+            // -1 for a draw
+            player_id: ge.winner_player_id.unwrap_or(-1),
+            tile: tile::Tile::Invalid,
+            rot: -1,
+            tile_pos: (-1, -1),
+            meeple_id: -1,
+            meeple_pos: -1,
+            complete_events: ge.complete_events,
+        });
+    }
+}
+
 #[get("/moves?<game>&<m>", format = "application/json")]
 pub fn get_moves(
     game: Option<i32>,
@@ -477,6 +661,33 @@ pub fn get_final_events(game: Option<i32>, db: &State<DbPool>) -> (Status, (Cont
     }
 }
 
+#[get("/games/<game_id>/board.svg?<m>")]
+pub fn get_board_svg(
+    game_id: i32,
+    m: Option<i32>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, String)) {
+    match game::render_board(db.inner(), game_id, m) {
+        Ok(svg) => (Status::Ok, (ContentType::SVG, svg)),
+        Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    }
+}
+
+#[get("/games/<game_id>/board.png?<m>")]
+pub fn get_board_png(
+    game_id: i32,
+    m: Option<i32>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, Vec<u8>)) {
+    match game::render_board(db.inner(), game_id, m).and_then(|svg| game::render_png(&svg)) {
+        Ok(png) => (Status::Ok, (ContentType::PNG, png)),
+        Err(e) => (
+            e.status,
+            (ContentType::JSON, to_string(&e.detail).unwrap().into_bytes()),
+        ),
+    }
+}
+
 #[get("/board?<game>&<m>", format = "application/json")]
 pub fn get_board(
     game: Option<i32>,
@@ -522,8 +733,22 @@ pub async fn events(
     }
 }
 
+// the events players send each other from the lobby. the others (e.g., `game_ended`) only come from the server
+const PLAYER_EVENTS: [&str; 2] = ["wait_game", "join_game"];
+
 #[post("/send-event", format = "application/json", data = "<params>")]
-pub fn send_event(params: Json<SendEvent>, queue: &State<Sender<event::Event>>) {
+pub fn send_event(
+    params: Json<SendEvent>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
+    queue: &State<Sender<event::Event>>,
+) -> (Status, (ContentType, String)) {
+    if let Err(e) = caller {
+        return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap()));
+    }
+    if !PLAYER_EVENTS.contains(&params.name.as_str()) {
+        let e = bad_request_error(format!("event {} can't be sent", params.name));
+        return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap()));
+    }
     let _ = queue.send(event::Event {
         id: params.id,
         name: params.name.clone(),
@@ -535,6 +760,7 @@ pub fn send_event(params: Json<SendEvent>, queue: &State<Sender<event::Event>>)
         meeple_pos: -1,
         complete_events: vec![],
     });
+    (Status::Ok, (ContentType::JSON, "".to_string()))
 }
 
 #[post("/players/<player_id>/upload-profile-image", data = "<data>")]
@@ -543,8 +769,14 @@ pub async fn upload_profile_image(
     storage_client: &State<Client>,
     player_id: i32,
     data: Data<'_>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
-) {
+) -> Status {
+    match caller {
+        Ok(auth::AuthenticatedPlayer(p)) if p.id == player_id => {}
+        Ok(_) => return Status::Forbidden,
+        Err(e) => return e.status,
+    }
     let options = MultipartFormDataOptions::with_multipart_form_data_fields(vec![
         MultipartFormDataField::file("profile_image")
             .content_type_by_string(Some(mime::IMAGE_STAR))
@@ -565,20 +797,49 @@ pub async fn upload_profile_image(
             .await
             .unwrap();
     }
+    Status::Ok
 }
 
 #[get("/problems/<id>?<player>", format = "application/json")]
 pub async fn get_problem(
     id: Option<i32>,
     player: Option<i32>,
+    caller: Option<auth::AuthenticatedPlayer>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match problem::get_problem(db.inner(), id.unwrap(), player) {
+    let caller = caller.map(|c| c.0);
+    match problem::get_problem(db.inner(), caller.as_ref(), id.unwrap(), player) {
         Ok(p) => (Status::Ok, (ContentType::JSON, to_string(&p).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
 }
 
+// PNG by default, or SVG with `format=svg`
+#[get("/problems/<id>/image?<format>")]
+pub fn get_problem_image(
+    id: i32,
+    format: Option<String>,
+    caller: Option<auth::AuthenticatedPlayer>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, Vec<u8>)) {
+    let caller = caller.map(|c| c.0);
+    let image = match format.as_deref() {
+        None | Some("png") => problem::render_problem(db.inner(), caller.as_ref(), id)
+            .and_then(|svg| game::render_png(&svg))
+            .map(|png| (ContentType::PNG, png)),
+        Some("svg") => problem::render_problem(db.inner(), caller.as_ref(), id)
+            .map(|svg| (ContentType::SVG, svg.into_bytes())),
+        Some(f) => Err(bad_request_error(format!("unknown image format {}", f))),
+    };
+    match image {
+        Ok(image) => (Status::Ok, image),
+        Err(e) => (
+            e.status,
+            (ContentType::JSON, to_string(&e.detail).unwrap().into_bytes()),
+        ),
+    }
+}
+
 #[get(
     "/problems?<page>&<order_by>&<limit>&<creator>&<is_draft>&<is_private>&<player>",
     format = "application/json"
@@ -591,10 +852,13 @@ pub fn get_problems(
     is_draft: Option<bool>,
     is_private: Option<bool>,
     player: Option<i32>,
+    caller: Option<auth::AuthenticatedPlayer>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let caller = caller.map(|c| c.0);
     match problem::get_problems(
         db.inner(),
+        caller.as_ref(),
         page,
         order_by,
         limit,
@@ -611,14 +875,19 @@ pub fn get_problems(
 #[post("/votes/create", format = "application/json", data = "<params>")]
 pub fn create_vote(
     params: Json<problem::CreateVote>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
     let cloned_db = db.inner().clone();
     match problem::create_vote(
         db.inner(),
         params.problem_id,
-        params.player_id,
-        params.player_name.clone(),
+        caller.id,
+        caller.name,
         params.note.clone(),
         params.tile_move_id,
         params.meeple_move_id,
@@ -655,14 +924,14 @@ pub fn get_votes(
 #[post("/favorites/create", format = "application/json", data = "<params>")]
 pub fn create_favorite(
     params: Json<problem::CreateFavorite>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match problem::create_favorite(
-        db.inner(),
-        params.problem_id,
-        params.player_id,
-        params.player_name.clone(),
-    ) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::create_favorite(db.inner(), params.problem_id, caller.id, caller.name) {
         Ok(f) => (Status::Ok, (ContentType::JSON, to_string(&f).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -671,9 +940,14 @@ pub fn create_favorite(
 #[post("/favorites/delete", format = "application/json", data = "<params>")]
 pub fn delete_favorite(
     params: Json<problem::DeleteFavorite>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match problem::delete_favorite(db.inner(), params.problem_id, params.player_id) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::delete_favorite(db.inner(), params.problem_id, caller.id) {
         Ok(v) => (Status::Ok, (ContentType::JSON, to_string(&v).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -694,9 +968,14 @@ pub fn get_favorites(
 #[post("/problems/create", format = "application/json", data = "<params>")]
 pub fn create_problem(
     params: Json<problem::CreateProblem>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match problem::create_draft_problem(db.inner(), &params) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::create_draft_problem(db.inner(), &caller, &params) {
         Ok(v) => (Status::Ok, (ContentType::JSON, to_string(&v).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -710,9 +989,14 @@ pub fn create_problem(
 pub fn update_problem(
     id: i32,
     params: Json<problem::UpdateProblem>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match problem::update_problem(db.inner(), id, &params) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::update_problem(db.inner(), &caller, id, &params) {
         Ok(v) => (Status::Ok, (ContentType::JSON, to_string(&v).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -726,17 +1010,30 @@ pub fn update_problem(
 pub fn publish_problem(
     id: i32,
     params: Json<problem::PublishProblem>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match problem::publish_problem(db.inner(), id, &params) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::publish_problem(db.inner(), &caller, id, &params) {
         Ok(v) => (Status::Ok, (ContentType::JSON, to_string(&v).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
 }
 
 #[post("/problems/<id>/delete", format = "application/json")]
-pub fn delete_problem(id: i32, db: &State<DbPool>) -> (Status, (ContentType, String)) {
-    match problem::delete_problem(db.inner(), id) {
+pub fn delete_problem(
+    id: i32,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::delete_problem(db.inner(), &caller, id) {
         Ok(v) => (Status::Ok, (ContentType::JSON, to_string(&v).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -760,17 +1057,30 @@ pub fn get_problem_proposals(
 )]
 pub fn create_problem_proposal(
     params: Json<problem::CreateProblemProposal>,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
     db: &State<DbPool>,
 ) -> (Status, (ContentType, String)) {
-    match problem::create_problem_proposal(db.inner(), &params) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::create_problem_proposal(db.inner(), &caller, &params) {
         Ok(v) => (Status::Ok, (ContentType::JSON, to_string(&v).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
 }
 
 #[post("/problem-proposals/<id>/use", format = "application/json")]
-pub fn use_problem_proposal(id: i32, db: &State<DbPool>) -> (Status, (ContentType, String)) {
-    match problem::use_problem_proposal(db.inner(), id) {
+pub fn use_problem_proposal(
+    id: i32,
+    caller: Result<auth::AuthenticatedPlayer, Error>,
+    db: &State<DbPool>,
+) -> (Status, (ContentType, String)) {
+    let caller = match caller {
+        Ok(auth::AuthenticatedPlayer(p)) => p,
+        Err(e) => return (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
+    };
+    match problem::use_problem_proposal(db.inner(), &caller, id) {
         Ok(v) => (Status::Ok, (ContentType::JSON, to_string(&v).unwrap())),
         Err(e) => (e.status, (ContentType::JSON, to_string(&e.detail).unwrap())),
     }
@@ -1,12 +1,13 @@
 #[macro_use]
 extern crate rocket;
 
+mod auth;
+mod challenge;
 mod database;
 mod error;
 mod event;
 mod game;
 mod handlers;
-mod optimal_move;
 mod player;
 mod problem;
 mod schema;
@@ -18,7 +19,7 @@ use handlers::all_options;
 use handlers::create_player;
 use handlers::create_problem_proposal;
 use handlers::events;
-use handlers::get_board;
+use handlers::get_ai_levels;
 use handlers::get_creators;
 use handlers::get_final_events;
 use handlers::get_moves;
@@ -31,13 +32,16 @@ use handlers::send_event;
 use handlers::update_player;
 use handlers::upload_profile_image;
 use handlers::use_problem_proposal;
+use handlers::{create_challenge_game, get_challenge_leaderboard, get_today_challenge};
 use handlers::{create_discard_move, create_move, try_create_tile_move};
 use handlers::{create_favorite, delete_favorite, get_favorites};
-use handlers::{create_game, get_game, get_games};
+use handlers::{create_game, get_deck, get_game, get_games};
 use handlers::{create_problem, delete_problem, publish_problem, update_problem};
 use handlers::{create_vote, get_vote, get_votes};
 use handlers::{create_waiting_game, delete_waiting_game, get_waiting_games, update_waiting_game};
-use handlers::{get_problem, get_problems};
+use handlers::{export_game, export_jcz_game, import_game, import_jcz_game};
+use handlers::{get_board, get_board_png, get_board_svg};
+use handlers::{get_problem, get_problem_image, get_problems};
 
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::Client;
@@ -89,8 +93,13 @@ async fn rocket() -> _ {
     let config = aws_config::from_env().region(region_provider).load().await;
     let storage_client = Client::new(&config);
 
+    let verifier = auth::Verifier::from_env()
+        .await
+        .expect("Loading Firebase public keys failed");
+
     let r = rocket::build()
         .manage(channel::<Event>(1024).0)
+        .manage(verifier)
         .manage(storage_client)
         .manage(pool)
         .attach(CORS)
@@ -103,6 +112,11 @@ async fn rocket() -> _ {
                 update_player,
                 get_game,
                 get_games,
+                get_deck,
+                export_game,
+                import_game,
+                export_jcz_game,
+                import_jcz_game,
                 create_game,
                 get_waiting_games,
                 create_waiting_game,
@@ -115,12 +129,15 @@ async fn rocket() -> _ {
                 get_moves,
                 get_final_events,
                 get_board,
+                get_board_svg,
+                get_board_png,
                 all_options,
                 health,
                 events,
                 send_event,
                 upload_profile_image,
                 get_problem,
+                get_problem_image,
                 get_problems,
                 create_vote,
                 get_vote,
@@ -136,6 +153,10 @@ async fn rocket() -> _ {
                 delete_problem,
                 get_problem_proposals,
                 get_creators,
+                get_today_challenge,
+                create_challenge_game,
+                get_challenge_leaderboard,
+                get_ai_levels,
             ],
         );
     r
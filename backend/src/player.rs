@@ -3,7 +3,6 @@ use crate::{database, error::Error};
 use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::Client;
 use diesel::pg::PgConnection;
-use diesel::prelude::*;
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::Pool;
 use rocket::serde::{Deserialize, Serialize};
@@ -15,7 +14,6 @@ pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 pub struct CreatePlayer {
     pub name: String,
     pub email: String,
-    pub user_id: String,
 }
 
 #[derive(Deserialize)]
@@ -26,7 +24,37 @@ pub struct UpdatePlayer {
     pub tile_edition: String,
 }
 
-#[derive(Serialize, Queryable, Clone, Debug)]
+// what a player is allowed to do with problems, from the most privileged
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    // reviews, publishes and schedules problems
+    Tester,
+    // creates problems and edits their own drafts
+    Creator,
+    Player,
+}
+
+impl Role {
+    pub fn from_name(s: &str) -> Option<Role> {
+        match s {
+            "admin" => Some(Role::Admin),
+            "tester" => Some(Role::Tester),
+            "creator" => Some(Role::Creator),
+            "player" => Some(Role::Player),
+            _ => None,
+        }
+    }
+    pub fn can_test(self) -> bool {
+        matches!(self, Role::Admin | Role::Tester)
+    }
+    pub fn can_create(self) -> bool {
+        self != Role::Player
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct Player {
     pub id: i32,
@@ -37,6 +65,9 @@ pub struct Player {
     pub rating: Option<i32>,
     pub tile_edition: String,
     pub profile_image_url: String,
+    pub role: Role,
+    // the engine the player is played by, if the player is a bot (see `agent::by_name`)
+    pub agent: Option<String>,
 }
 
 pub fn update_player(
@@ -136,3 +167,13 @@ pub async fn upload_profile_image(
 
     Ok(())
 }
+
+#[test]
+fn role_test() {
+    assert_eq!(Role::from_name("tester"), Some(Role::Tester));
+    assert_eq!(Role::from_name("owner"), None);
+    assert!(Role::Tester.can_test());
+    assert!(!Role::Creator.can_test());
+    assert!(Role::Creator.can_create());
+    assert!(!Role::Player.can_create());
+}
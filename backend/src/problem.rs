@@ -1,8 +1,9 @@
 use crate::{
-    error::Error,
+    error::{bad_request_error, decode_error, forbidden_error, Error},
     game::{
-        calculate,
+        self, calculate,
         mov::{DiscardMove, MeepleMove, Move, TileMove},
+        rule_set::RuleSet,
         tile::Tile,
     },
     translate,
@@ -11,13 +12,14 @@ use chrono::Duration;
 use diesel::pg::PgConnection;
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::Pool;
-use diesel::Queryable;
+use diesel::{Connection, Queryable};
 use rocket::serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
 use super::game::decoder;
 use super::game::mov::Move::*;
 use crate::database;
+use crate::player::Player;
 
 pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 
@@ -77,8 +79,6 @@ pub struct Vote {
 #[serde(crate = "rocket::serde")]
 pub struct CreateVote {
     pub problem_id: i32,
-    pub player_id: i32,
-    pub player_name: String,
     pub note: String,
     pub tile_move_id: i32,
     pub meeple_move_id: i32,
@@ -87,7 +87,6 @@ pub struct CreateVote {
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct CreateProblem {
-    pub creator_id: i32,
     pub remaining_tile_count: i32,
     pub moves: String,
     pub note: String,
@@ -103,7 +102,8 @@ pub struct PublishProblem {
 #[serde(crate = "rocket::serde")]
 pub struct UpdateProblem {
     pub name: String,
-    pub start_at: chrono::NaiveDateTime,
+    // only testers can schedule a problem. `None` keeps the current schedule
+    pub start_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Serialize, Queryable, Clone, Debug)]
@@ -121,15 +121,12 @@ pub struct Favorite {
 #[serde(crate = "rocket::serde")]
 pub struct CreateFavorite {
     pub problem_id: i32,
-    pub player_id: i32,
-    pub player_name: String,
 }
 
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct DeleteFavorite {
     pub problem_id: i32,
-    pub player_id: i32,
 }
 
 #[derive(Deserialize)]
@@ -137,7 +134,8 @@ pub struct DeleteFavorite {
 pub struct CreateProblemProposal {
     pub table_id: String,
     pub remaining_tile_count: i32,
-    pub creator_id: i32,
+    // testers can propose on behalf of another creator
+    pub creator_id: Option<i32>,
     pub note: String,
 }
 
@@ -160,25 +158,74 @@ pub struct Creator {
     pub name: String,
 }
 
-pub fn create_draft_problem(db: &DbPool, params: &CreateProblem) -> Result<Problem, Error> {
-    let all_mvs = decoder::decode(params.moves.clone());
+fn require_tester(caller: &Player) -> Result<(), Error> {
+    if !caller.role.can_test() {
+        return Err(forbidden_error(
+            "only testers can review or schedule problems".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// testers can edit any problem, and creators only their own drafts
+fn require_editor(caller: &Player, problem: &Problem) -> Result<(), Error> {
+    if caller.role.can_test() {
+        return Ok(());
+    }
+    if caller.role.can_create() && problem.is_draft && problem.creator_id == Some(caller.id) {
+        return Ok(());
+    }
+    Err(forbidden_error(
+        "creators can only edit their own drafts".to_string(),
+    ))
+}
+
+// drafts are visible to their creators and testers
+fn can_view(caller: Option<&Player>, problem: &Problem) -> bool {
+    if !problem.is_draft {
+        return true;
+    }
+    match caller {
+        Some(c) => c.role.can_test() || problem.creator_id == Some(c.id),
+        None => false,
+    }
+}
+
+pub fn create_draft_problem(
+    db: &DbPool,
+    caller: &Player,
+    params: &CreateProblem,
+) -> Result<Problem, Error> {
+    if !caller.role.can_create() {
+        return Err(forbidden_error(
+            "only creators can create problems".to_string(),
+        ));
+    }
+    // everything is validated before the game is created, so that a replay which can't be made into a problem
+    // doesn't leave a game behind
+    let all_mvs = match decoder::decode(params.moves.clone()) {
+        Ok(mvs) => mvs,
+        Err(e) => return Err(decode_error(e)),
+    };
     let remaining_tile_count = params.remaining_tile_count;
     let problem_name = "".to_string();
-    let creator_id = Some(params.creator_id);
+    let creator_id = Some(caller.id);
     let mut creator_name = None;
     if let Some(pid) = creator_id {
-        let player = database::get_player(&db, pid).unwrap();
+        let player = database::get_player(&db, pid)?;
         creator_name = Some(player.name);
     }
 
     let mut tile_count = 0;
     let mut mv_idx = 0;
+    let mut found = false;
     for mv in &all_mvs {
         mv_idx += 1;
         match mv {
             MMove(_) => {
                 let rem_tile = 72 - tile_count - 2;
                 if remaining_tile_count == rem_tile {
+                    found = true;
                     break;
                 }
 
@@ -189,13 +236,19 @@ pub fn create_draft_problem(db: &DbPool, params: &CreateProblem) -> Result<Probl
             }
         }
     }
+    if !found {
+        return Err(bad_request_error(format!(
+            "the replay has no turn with {} remaining tiles",
+            remaining_tile_count
+        )));
+    }
 
     let mut mvs = all_mvs[0..mv_idx].to_vec();
 
     let you = -2;
     let opponent = -3;
-    let (cur_tile_id, player_map) = match &all_mvs[mv_idx] {
-        TMove(tm) => {
+    let (cur_tile_id, player_map) = match all_mvs.get(mv_idx) {
+        Some(TMove(tm)) => {
             if tm.player_id == 0 {
                 (tm.tile.to_id(), vec![-2, -3])
             } else {
@@ -203,66 +256,95 @@ pub fn create_draft_problem(db: &DbPool, params: &CreateProblem) -> Result<Probl
             }
         }
         _ => {
-            panic!("something wrong");
+            return Err(bad_request_error(format!(
+                "the replay has no tile placed with {} remaining tiles",
+                remaining_tile_count
+            )));
         }
     };
 
     let you_name = "You".to_string();
     let opponent_name = "Opponent".to_string();
 
-    let first_player_id = player_map[mvs[2].player_id() as usize];
-
-    let g = database::create_game(
-        &db,
-        you,
-        opponent,
-        None,
-        Some(opponent),
-        Some(cur_tile_id),
-        Some(you),
-        you_name,
-        opponent_name,
-        1,
-        0,
-        false,
-        Some(first_player_id),
-    )
-    .unwrap();
-
-    update_moves_fields(g.id, &mut mvs, you, player_map);
-
-    let s = calculate::calculate(&mvs, true)?;
-    let point_diff = s.player0_point - s.player1_point;
+    // the first two moves are of the starting tile, which no one places
+    let first_player_id = match all_mvs.get(2) {
+        Some(mv) => player_map[mv.player_id() as usize],
+        None => {
+            return Err(bad_request_error(
+                "the replay has no move by a player".to_string(),
+            ))
+        }
+    };
 
-    for mv in mvs {
-        database::create_move(&db, mv).unwrap();
-    }
+    let mut remapped_mvs = mvs.clone();
+    update_moves_fields(-1, &mut remapped_mvs, you, player_map.clone());
+    let s = calculate::calculate(&remapped_mvs, 2, &[], &RuleSet::default(), true)?;
+    let point_diff = s.player_points[0] - s.player_points[1];
+
+    // the game, its moves and the problem are created together, so that a failure doesn't leave a game without a
+    // problem behind
+    let conn = &mut db.get().unwrap();
+    conn.transaction(|conn| {
+        let g = database::insert_game(
+            conn,
+            vec![you, opponent],
+            None,
+            Some(opponent),
+            Some(cur_tile_id),
+            Some(you),
+            vec![you_name, opponent_name],
+            vec![1, 0],
+            false,
+            Some(first_player_id),
+            vec![],
+            &RuleSet::default(),
+            0, // problems never draw tiles
+            None,
+            vec![None, None],
+        )?;
+
+        update_moves_fields(g.id, &mut mvs, you, player_map);
+
+        for mv in mvs {
+            database::insert_move(conn, mv)?;
+        }
 
-    database::create_problem(
-        db,
-        &database::NewProblem {
-            game_id: g.id,
-            name: problem_name,
-            start_at: None,
-            creator_id,
-            creator_name,
-            vote_count: 0,
-            is_solved: false,
-            optimal_move_count: None,
-            tester_id: None,
-            tester_name: None,
-            is_draft: true,
-            point_diff: Some(point_diff),
-            note: params.note.clone(),
-            is_deleted: false,
-            num: None,
-            favorite_count: 0,
-        },
-    )
+        database::insert_problem(
+            conn,
+            &database::NewProblem {
+                game_id: g.id,
+                name: problem_name,
+                start_at: None,
+                creator_id,
+                creator_name,
+                vote_count: 0,
+                is_solved: false,
+                optimal_move_count: None,
+                tester_id: None,
+                tester_name: None,
+                is_draft: true,
+                point_diff: Some(point_diff),
+                note: params.note.clone(),
+                is_deleted: false,
+                num: None,
+                favorite_count: 0,
+            },
+        )
+    })
 }
 
-pub fn get_problem(db: &DbPool, id: i32, player: Option<i32>) -> Result<Problem, Error> {
+pub fn get_problem(
+    db: &DbPool,
+    caller: Option<&Player>,
+    id: i32,
+    player: Option<i32>,
+) -> Result<Problem, Error> {
     let mut problem = database::get_problem(db, id)?;
+    if !can_view(caller, &problem) {
+        return Err(forbidden_error(
+            "drafts are only visible to their creators and testers".to_string(),
+        ));
+    }
 
     if let Some(plid) = player {
         let votes = database::get_votes(db, Some(id), Some(plid), false, 0, 1)?;
@@ -276,8 +358,15 @@ pub fn get_problem(db: &DbPool, id: i32, player: Option<i32>) -> Result<Problem,
     Ok(problem)
 }
 
+// the position of the problem drawn as SVG, e.g. for share cards
+pub fn render_problem(db: &DbPool, caller: Option<&Player>, id: i32) -> Result<String, Error> {
+    let problem = get_problem(db, caller, id, None)?;
+    game::render_board(db, problem.game_id, None)
+}
+
 pub fn get_problems(
     db: &DbPool,
+    caller: Option<&Player>,
     page: Option<i32>,
     order_by: Option<String>,
     limit: Option<i32>,
@@ -321,6 +410,18 @@ pub fn get_problems(
         is_prvt = isp;
     }
 
+    // creators can list their own drafts, and only testers see every draft and scheduled problem
+    let allowed = match caller {
+        Some(c) if c.role.can_test() => true,
+        Some(c) => !is_prvt && (!is_drft || creator == Some(c.id)),
+        None => !is_prvt && !is_drft,
+    };
+    if !allowed {
+        return Err(forbidden_error(
+            "drafts and scheduled problems are only visible to testers".to_string(),
+        ));
+    }
+
     let mut problem_res = database::get_problems(db, p, o, l, creator, is_drft, is_prvt)?;
 
     if let Some(plid) = player {
@@ -534,7 +635,7 @@ fn create_problem_test() {
         .expect("Creating a pool failed");
 
     // should-be-modified lines start
-    let all_mvs = decoder::decode_from_file_path("src/data/559900177.json".to_string());
+    let all_mvs = decoder::decode_from_file_path("src/data/559900177.json".to_string()).unwrap();
     // let all_mvs = create_moves_manually();
     // let all_mvs = create_moves_from_game_against_ai(&db, 7944);
 
@@ -608,34 +709,36 @@ fn create_problem_test() {
 
     let first_player_id = player_map[mvs[2].player_id() as usize];
 
-    let g = database::create_game(
-        &db,
-        you,
-        opponent,
+    let g = database::insert_game(
+        &mut db.get().unwrap(),
+        vec![you, opponent],
         None,
         Some(opponent),
         Some(cur_tile_id),
         Some(you),
-        you_name,
-        opponent_name,
-        1,
-        0,
+        vec![you_name, opponent_name],
+        vec![1, 0],
         false,
         Some(first_player_id),
+        vec![],
+        &RuleSet::default(),
+        0, // problems never draw tiles
+        None,
+        vec![None, None],
     )
     .unwrap();
 
     update_moves_fields(g.id, &mut mvs, you, player_map);
 
-    let s = calculate::calculate(&mvs, true).unwrap();
-    let point_diff = s.player0_point - s.player1_point;
+    let s = calculate::calculate(&mvs, 2, &[], &RuleSet::default(), true).unwrap();
+    let point_diff = s.player_points[0] - s.player_points[1];
 
     for mv in mvs {
         database::create_move(&db, mv).unwrap();
     }
 
-    database::create_problem(
-        &db,
+    database::insert_problem(
+        &mut db.get().unwrap(),
         &database::NewProblem {
             game_id: g.id,
             name: problem_name,
@@ -822,20 +925,41 @@ fn list_daily_number_of_vote() {
 
 pub fn create_problem_proposal(
     db: &DbPool,
+    caller: &Player,
     params: &CreateProblemProposal,
 ) -> Result<ProblemProposal, Error> {
+    let creator_id = match params.creator_id {
+        Some(cid) if cid != caller.id => {
+            require_tester(caller)?;
+            cid
+        }
+        _ => caller.id,
+    };
+    // BGA table ids are numbers
+    if params.table_id.is_empty() || !params.table_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(bad_request_error(format!(
+            "{} is not a BGA table id",
+            params.table_id
+        )));
+    }
     database::create_problem_proposal(
         db,
         &database::NewProblemProposal {
             table_id: params.table_id.clone(),
             remaining_tile_count: params.remaining_tile_count,
-            creator_id: Some(params.creator_id),
+            creator_id: Some(creator_id),
             note: params.note.clone(),
         },
     )
 }
 
-pub fn publish_problem(db: &DbPool, id: i32, params: &PublishProblem) -> Result<Problem, Error> {
+pub fn publish_problem(
+    db: &DbPool,
+    caller: &Player,
+    id: i32,
+    params: &PublishProblem,
+) -> Result<Problem, Error> {
+    require_tester(caller)?;
     let prb = database::get_problem(db, id)?;
 
     let private_prbs =
@@ -867,8 +991,9 @@ pub fn publish_problem(db: &DbPool, id: i32, params: &PublishProblem) -> Result<
     )
 }
 
-pub fn delete_problem(db: &DbPool, id: i32) -> Result<Problem, Error> {
+pub fn delete_problem(db: &DbPool, caller: &Player, id: i32) -> Result<Problem, Error> {
     let prb = database::get_problem(db, id)?;
+    require_editor(caller, &prb)?;
 
     database::update_problem(
         db,
@@ -883,14 +1008,27 @@ pub fn delete_problem(db: &DbPool, id: i32) -> Result<Problem, Error> {
     )
 }
 
-pub fn update_problem(db: &DbPool, id: i32, params: &UpdateProblem) -> Result<Problem, Error> {
+pub fn update_problem(
+    db: &DbPool,
+    caller: &Player,
+    id: i32,
+    params: &UpdateProblem,
+) -> Result<Problem, Error> {
     let prb = database::get_problem(db, id)?;
+    require_editor(caller, &prb)?;
+    let start_at = match params.start_at {
+        Some(sa) if Some(sa) != prb.start_at => {
+            require_tester(caller)?;
+            Some(sa)
+        }
+        _ => prb.start_at,
+    };
 
     database::update_problem(
         db,
         id,
         params.name.clone(),
-        Some(params.start_at),
+        start_at,
         prb.is_draft,
         prb.vote_count,
         prb.point_diff,
@@ -906,7 +1044,12 @@ pub fn get_problem_proposals(
     database::get_problem_proposals(db, player)
 }
 
-pub fn use_problem_proposal(db: &DbPool, id: i32) -> Result<ProblemProposal, Error> {
+pub fn use_problem_proposal(
+    db: &DbPool,
+    caller: &Player,
+    id: i32,
+) -> Result<ProblemProposal, Error> {
+    require_tester(caller)?;
     database::use_problem_proposal(db, id)
 }
 
@@ -931,8 +1074,8 @@ fn update_all_point_diff() {
             Ok(problem) => {
                 let mvs = database::list_moves(&db, problem.game_id, None).unwrap();
 
-                let s = calculate::calculate(&mvs, true).unwrap();
-                let point_diff = s.player0_point - s.player1_point;
+                let s = calculate::calculate(&mvs, 2, &[], &RuleSet::default(), true).unwrap();
+                let point_diff = s.player_points[0] - s.player_points[1];
 
                 println!("before problem = {:?}", problem);
                 let problem = database::update_problem(
@@ -1036,3 +1179,66 @@ pub fn get_creators(db: &DbPool) -> Result<Vec<Creator>, Error> {
 
     Ok(creators)
 }
+
+#[test]
+fn problem_permission_test() {
+    use crate::player::Role;
+
+    let player = |id: i32, role: Role| Player {
+        id,
+        name: "".to_string(),
+        email: "".to_string(),
+        user_id: "".to_string(),
+        meeple_color: 0,
+        rating: None,
+        tile_edition: "second".to_string(),
+        profile_image_url: "".to_string(),
+        role,
+        agent: None,
+    };
+    let mut draft = Problem {
+        id: 1,
+        game_id: 1,
+        created_at: chrono::NaiveDateTime::default(),
+        name: "".to_string(),
+        start_at: None,
+        creator_id: Some(10),
+        creator_name: None,
+        vote_count: 0,
+        is_solved: false,
+        optimal_move_count: None,
+        tester_id: None,
+        tester_name: None,
+        is_draft: true,
+        point_diff: None,
+        note: "".to_string(),
+        is_deleted: false,
+        num: None,
+        favorite_count: 0,
+        voted: None,
+        favorited: None,
+    };
+
+    let creator = player(10, Role::Creator);
+    let other_creator = player(11, Role::Creator);
+    let tester = player(12, Role::Tester);
+
+    assert!(require_editor(&creator, &draft).is_ok());
+    assert!(require_editor(&tester, &draft).is_ok());
+    assert_eq!(
+        require_editor(&other_creator, &draft).unwrap_err().status,
+        rocket::http::Status::Forbidden
+    );
+    assert!(require_tester(&creator).is_err());
+    assert!(require_tester(&player(13, Role::Admin)).is_ok());
+
+    assert!(can_view(Some(&creator), &draft));
+    assert!(can_view(Some(&tester), &draft));
+    assert!(!can_view(Some(&other_creator), &draft));
+    assert!(!can_view(None, &draft));
+
+    // once published, the creator can't edit it anymore
+    draft.is_draft = false;
+    assert!(require_editor(&creator, &draft).is_err());
+    assert!(can_view(None, &draft));
+}
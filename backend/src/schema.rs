@@ -1,5 +1,22 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    ai_level (name) {
+        name -> Text,
+        rating -> Int4,
+    }
+}
+
+diesel::table! {
+    challenge (id) {
+        id -> Int4,
+        date -> Date,
+        seed -> Int8,
+        ai_first -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     color (id) {
         id -> Int4,
@@ -41,6 +58,19 @@ diesel::table! {
         after_player1_rating -> Nullable<Int4>,
         first_player_id -> Nullable<Int4>,
         winner_player_id -> Nullable<Int4>,
+        player_ids -> Array<Int4>,
+        player_names -> Array<Text>,
+        player_colors -> Array<Int4>,
+        player_points -> Array<Int4>,
+        expansions -> Array<Text>,
+        field_edition -> Text,
+        meeples_per_player -> Int4,
+        tie_is_draw -> Bool,
+        redraw_on_discard -> Bool,
+        seed -> Int8,
+        challenge_id -> Nullable<Int4>,
+        player_agents -> Array<Nullable<Text>>,
+        is_draw -> Bool,
     }
 }
 
@@ -80,6 +110,8 @@ diesel::table! {
         rating -> Nullable<Int4>,
         profile_image_url -> Varchar,
         tile_edition -> Varchar,
+        role -> Text,
+        agent -> Nullable<Text>,
     }
 }
 
@@ -152,9 +184,12 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(game -> challenge (challenge_id));
 diesel::joinable!(player -> color (meeple_color));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    ai_level,
+    challenge,
     color,
     favorite,
     game,
@@ -1,11 +1,14 @@
 import axios from "axios";
+import { getAuth } from "firebase/auth";
 import {
+  boardCenter,
   boardSize,
   Color,
   colorIDToColor,
   colorToColorID,
   idToTileKind,
   newTile,
+  Tile,
   TileEdition,
 } from "../tiles";
 import {
@@ -27,8 +30,19 @@ import {
   FinalEvents,
   Favorite,
   Creator,
+  TilePosition,
 } from "../types";
 
+// the backend identifies the caller by their Firebase ID token
+axios.interceptors.request.use(async (config) => {
+  const user = getAuth().currentUser;
+  if (user) {
+    const token = await user.getIdToken();
+    config.headers.Authorization = `Bearer ${token}`;
+  }
+  return config;
+});
+
 export class API {
   base_url: string;
   constructor() {
@@ -74,6 +88,7 @@ export class API {
         profileImageURL: res.data.profile_image_url,
         rating: res.data.rating,
         tileEdition: res.data.tile_edition,
+        role: res.data.role,
       };
       return p;
     } catch (e) {
@@ -95,6 +110,7 @@ export class API {
         profileImageURL: res.data.profile_image_url,
         rating: res.data.rating,
         tileEdition: res.data.tile_edition,
+        role: res.data.role,
       };
       return p;
     } catch (e) {
@@ -117,6 +133,7 @@ export class API {
           profileImageURL: v.profile_image_url,
           rating: v.rating,
           tileEdition: v.tile_edition,
+          role: v.role,
         };
         return player;
       });
@@ -165,6 +182,7 @@ export class API {
         profileImageURL: res.data.profile_image_url,
         meepleColor: colorIDToColor(res.data.meeple_color),
         tileEdition: res.data.tile_edition,
+        role: res.data.role,
       };
       return player;
     } catch (e) {
@@ -192,6 +210,7 @@ export class API {
         profileImageURL: res.data.profile_image_url,
         meepleColor: colorIDToColor(res.data.meeple_color),
         tileEdition: res.data.tile_edition,
+        role: res.data.role,
       };
       return player;
     } catch (e) {
@@ -295,6 +314,7 @@ export class API {
           player0Color: colorIDToColor(g.player0_color),
           player1Color: colorIDToColor(g.player1_color),
           winnerPlayerID: g.winner_player_id,
+          isDraw: g.is_draw,
           beforePlayer0Rating: g.before_player0_rating,
           afterPlayer0Rating: g.after_player0_rating,
           beforePlayer1Rating: g.before_player1_rating,
@@ -328,6 +348,7 @@ export class API {
         player0Color: colorIDToColor(res.data.player0_color),
         player1Color: colorIDToColor(res.data.player1_color),
         winnerPlayerID: res.data.winner_player_id,
+        isDraw: res.data.is_draw,
         beforePlayer0Rating: res.data.before_player0_rating,
         afterPlayer0Rating: res.data.after_player0_rating,
         beforePlayer1Rating: res.data.before_player1_rating,
@@ -371,6 +392,7 @@ export class API {
         player0Color: colorIDToColor(res.data.player0_color),
         player1Color: colorIDToColor(res.data.player1_color),
         winnerPlayerID: res.data.winner_player_id,
+        isDraw: res.data.is_draw,
         beforePlayer0Rating: res.data.before_player0_rating,
         afterPlayer0Rating: res.data.after_player0_rating,
         beforePlayer1Rating: res.data.before_player1_rating,
@@ -390,7 +412,8 @@ export class API {
     tileID: number,
     rot: number,
     posY: number,
-    posX: number
+    posX: number,
+    origin: TilePosition = boardCenter
   ): Promise<TileMoveResult> {
     try {
       const res = await axios.post(`${this.base_url}/tile-moves/try-create`, {
@@ -398,8 +421,8 @@ export class API {
         player_id: playerID,
         tile_id: tileID,
         rot: rot,
-        pos_y: posY - Math.floor(boardSize / 2),
-        pos_x: posX - Math.floor(boardSize / 2),
+        pos_y: posY - origin.y,
+        pos_x: posX - origin.x,
       });
       console.log({ res });
       const tileMoveResult: TileMoveResult = {
@@ -446,7 +469,8 @@ export class API {
     posX: number,
     meepleID: number,
     meeplePos: number,
-    waitAIMove: boolean
+    waitAIMove: boolean,
+    origin: TilePosition = boardCenter
   ): Promise<CreateMoveResult> {
     try {
       const res = await axios.post(`${this.base_url}/moves/create`, {
@@ -454,8 +478,8 @@ export class API {
         player_id: playerID,
         tile_id: tileID,
         rot: rot,
-        pos_y: posY - Math.floor(boardSize / 2),
-        pos_x: posX - Math.floor(boardSize / 2),
+        pos_y: posY - origin.y,
+        pos_x: posX - origin.x,
         meeple_id: meepleID,
         meeple_pos: meeplePos,
         wait_ai_move: waitAIMove,
@@ -468,8 +492,8 @@ export class API {
           ord: res.data.tile_move.TMove.ord,
           tile: res.data.tile_move.TMove.tile,
           pos: {
-            y: res.data.tile_move.TMove.pos[0] + Math.floor(boardSize / 2),
-            x: res.data.tile_move.TMove.pos[1] + Math.floor(boardSize / 2),
+            y: res.data.tile_move.TMove.pos[0] + origin.y,
+            x: res.data.tile_move.TMove.pos[1] + origin.x,
           },
           rot: res.data.tile_move.TMove.rot,
         },
@@ -510,7 +534,11 @@ export class API {
     }
   }
 
-  async getMoves(gameID: number, moveID?: number): Promise<Move[]> {
+  async getMoves(
+    gameID: number,
+    moveID?: number,
+    origin: TilePosition = boardCenter
+  ): Promise<Move[]> {
     try {
       const res = await axios.get(
         `${this.base_url}/moves?game=${gameID}` + (moveID ? `&m=${moveID}` : "")
@@ -524,8 +552,8 @@ export class API {
             ord: mv.TMove.ord,
             tile: mv.TMove.tile,
             pos: {
-              y: mv.TMove.pos[0] + Math.floor(boardSize / 2),
-              x: mv.TMove.pos[1] + Math.floor(boardSize / 2),
+              y: mv.TMove.pos[0] + origin.y,
+              x: mv.TMove.pos[1] + origin.x,
             },
             rot: mv.TMove.rot,
           };
@@ -591,30 +619,41 @@ export class API {
       const res = await axios.get(
         `${this.base_url}/board?game=${gameID}` + (moveID ? `&m=${moveID}` : "")
       );
+      // the grid covers all the tiles with an empty square around them (to place the next tiles on),
+      // and is at least as large as the grid of a new game
+      const bounds = res.data.bounds;
+      const minY = Math.min(bounds.min_y - 1, -boardCenter.y);
+      const minX = Math.min(bounds.min_x - 1, -boardCenter.x);
+      const maxY = Math.max(bounds.max_y + 1, boardSize - 1 - boardCenter.y);
+      const maxX = Math.max(bounds.max_x + 1, boardSize - 1 - boardCenter.x);
+      const origin: TilePosition = { y: -minY, x: -minX };
+      const tiles: Tile[][] = [...Array(maxY - minY + 1)].map(() =>
+        Array(maxX - minX + 1).fill(null)
+      );
+      res.data.tiles.forEach((tile: any) => {
+        const y = tile.y + origin.y;
+        const x = tile.x + origin.x;
+        const meepleColor =
+          tile.meeple_id === -1
+            ? null
+            : tile.meeple_id < 7
+            ? player0MeepleColor
+            : player1MeepleColor;
+        tiles[y][x] = newTile(
+          tile.rot,
+          idToTileKind(tile.id),
+          meepleColor,
+          tile.meeple_pos,
+          tile.meeple_id,
+          tileEdition
+        );
+      });
       const board: Board = {
         player0Point: res.data.player0_point,
         player1Point: res.data.player1_point,
         meepleablePositions: res.data.meepleable_positions,
-        tiles: res.data.tiles.map((row: any) => {
-          return row.map((tile: any) => {
-            const meepleColor =
-              tile.meeple_id === -1
-                ? null
-                : tile.meeple_id < 7
-                ? player0MeepleColor
-                : player1MeepleColor;
-            return tile.id === -1
-              ? null
-              : newTile(
-                  tile.rot,
-                  idToTileKind(tile.id),
-                  meepleColor,
-                  tile.meeple_pos,
-                  tile.meeple_id,
-                  tileEdition
-                );
-          });
-        }),
+        tiles: tiles,
+        origin: origin,
         completeEvents: res.data.complete_events.map(
           (e: any): CompleteEvent => {
             return {
@@ -769,7 +808,8 @@ export class API {
     playerName: string,
     note: string,
     tileMoveID: number,
-    meepleMoveID: number
+    meepleMoveID: number,
+    origin: TilePosition = boardCenter
   ): Promise<Vote> {
     try {
       const res = await axios.post(`${this.base_url}/votes/create`, {
@@ -786,8 +826,8 @@ export class API {
         ord: res.data.tile_move.ord,
         tile: res.data.tile_move.tile,
         pos: {
-          y: res.data.tile_move.pos[0] + Math.floor(boardSize / 2),
-          x: res.data.tile_move.pos[1] + Math.floor(boardSize / 2),
+          y: res.data.tile_move.pos[0] + origin.y,
+          x: res.data.tile_move.pos[1] + origin.x,
         },
         rot: res.data.tile_move.rot,
       };
@@ -821,7 +861,8 @@ export class API {
 
   async getVotes(
     problemID: number | null,
-    playerID: number | null
+    playerID: number | null,
+    origin: TilePosition = boardCenter
   ): Promise<Vote[]> {
     try {
       let url = `${this.base_url}/votes`;
@@ -842,8 +883,8 @@ export class API {
             ord: v.tile_move.ord,
             tile: v.tile_move.tile,
             pos: {
-              y: v.tile_move.pos[0] + Math.floor(boardSize / 2),
-              x: v.tile_move.pos[1] + Math.floor(boardSize / 2),
+              y: v.tile_move.pos[0] + origin.y,
+              x: v.tile_move.pos[1] + origin.x,
             },
             rot: v.tile_move.rot,
           };
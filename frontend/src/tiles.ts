@@ -729,6 +729,8 @@ export function idToTileKind(id: number): TileKind {
 }
 
 export const boardSize = 2 * 20 + 1;
+// the index in a grid of `boardSize` of position (0, 0), where the starting tile is placed
+export const boardCenter = { y: (boardSize - 1) / 2, x: (boardSize - 1) / 2 };
 export function getInitialBoard(tileEdition: TileEdition): (Tile | null)[][] {
   const initialBoard: (Tile | null)[][] = [];
   for (let i = 0; i < boardSize; i++) {
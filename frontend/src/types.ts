@@ -9,8 +9,11 @@ export interface Player {
   profileImageURL: string;
   rating?: number;
   tileEdition: TileEdition;
+  role: Role;
 }
 
+export type Role = "admin" | "tester" | "creator" | "player";
+
 export interface Game {
   id: number;
   player0ID: number;
@@ -26,6 +29,7 @@ export interface Game {
   currentTileID: number;
   nextTileID: number;
   winnerPlayerID: number;
+  isDraw: boolean;
   beforePlayer0Rating: number;
   afterPlayer0Rating: number;
   beforePlayer1Rating: number;
@@ -96,6 +100,8 @@ export interface Board {
   player0Point: number;
   player1Point: number;
   tiles: Tile[][];
+  // the index in `tiles` of position (0, 0), where the starting tile is placed
+  origin: TilePosition;
   meepleablePositions: number[];
   completeEvents: CompleteEvent[];
 }